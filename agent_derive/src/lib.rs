@@ -10,14 +10,26 @@ pub fn agent_derive(input: TokenStream) -> TokenStream {
   impl_agent_derive(&ast)
 }
 
+/// The fields which every Agent has and which are initialized by the arguments of `new`
+const COMMON_FIELDS: [&str; 6] = ["map", "robot", "noise", "camera", "actual", "observed"];
+
 fn impl_agent_derive(ast: &syn::DeriveInput) -> TokenStream {
   let name = &ast.ident;
+  // the fields which are specific to the Agent are initialized by their default values
+  let specific_fields = match &ast.data {
+    syn::Data::Struct(data) => data.fields
+                                   .iter()
+                                   .filter_map(|field| field.ident.as_ref())
+                                   .filter(|ident| !COMMON_FIELDS.contains(&ident.to_string().as_str()))
+                                   .collect::<Vec<_>>(),
+    _ => Vec::new(),
+  };
   let gen = quote! {
     impl #name {
-      pub fn new(landmarks: Vec<Point>) -> #name {
+      pub fn new(map: LandmarkMap, robot: RobotConfig, noise: NoiseConfig, camera: CameraConfig) -> #name {
         let actual = na::Vector3::new(0.0, 0.0, 0.0);
         let observed: Vec<Observed> = Vec::new();
-        #name { landmarks, actual, observed }
+        #name { map, robot, noise, camera, actual, observed, #(#specific_fields: Default::default(),)* }
       }
     }
 
@@ -25,8 +37,17 @@ fn impl_agent_derive(ast: &syn::DeriveInput) -> TokenStream {
       fn get_name(&self) -> &str {
         stringify!(#name)
       }
-      fn get_landmarks(&self) -> &Vec<Point> {
-        &self.landmarks
+      fn get_map(&self) -> &LandmarkMap {
+        &self.map
+      }
+      fn get_robot_config(&self) -> &RobotConfig {
+        &self.robot
+      }
+      fn get_noise_config(&self) -> &NoiseConfig {
+        &self.noise
+      }
+      fn get_camera_config(&self) -> &CameraConfig {
+        &self.camera
       }
       fn set_actual(&mut self, actual: na::Vector3<f64>) -> () {
         self.actual = actual;
@@ -8,47 +8,53 @@ use std::fmt;
 
 extern crate nalgebra as na;
 extern crate rand;
+use rand::rngs::StdRng;
 use rand_distr::{Normal, Distribution};
 
-use crate::data::{Point, Observed};
-use crate::models::robot;
-
-/// **\[private\]** The standard deviation value which is used to simulate the gaussian noise of the autonomous mobile robot's (position: x, y)
-const ACTUAL_XY_SD: f64 = 0.005;
-/// **\[private\]** The standard deviation value which is used to simulate the gaussian noise of the autonomous mobile robot's (direction: theta)
-const ACTUAL_THETA_SD: f64 = 0.01;
-/// **\[private\]** The standard deviation value which is used to simulate the gaussian noise of the camera's observation (distance to marker)
-const OBSERVED_DIST_SD: f64 = 0.02;
-/// **\[private\]** The standard deviation value which is used to simulate the gaussian noise of the camera's observation (angle between robot heading and marker)
-const OBSERVED_ANGLE_SD: f64 = 0.02;
+use crate::config::{RobotConfig, NoiseConfig, CameraConfig};
+use crate::data::Observed;
+use crate::map::LandmarkMap;
+use crate::models::{robot, camera};
+
+/// The default value of the alpha parameters of the velocity motion model which is used to simulate the gaussian noise of the autonomous mobile robot's motion
+pub const ACTUAL_ALPHAS: [f64; 6] = [0.003, 0.001, 0.001, 0.01, 0.0005, 0.0005];
+/// The default value of the standard deviation which is used to simulate the gaussian noise of the autonomous mobile robot's (position: x, y) in the world frame
+pub const ACTUAL_XY_SD: f64 = 0.0;
+/// The default value of the standard deviation which is used to simulate the gaussian noise of the autonomous mobile robot's (direction: theta) in the world frame
+pub const ACTUAL_THETA_SD: f64 = 0.0;
+/// The default value of the standard deviation which is used to simulate the gaussian noise of the camera's observation (distance to marker)
+pub const OBSERVED_DIST_SD: f64 = 0.02;
+/// The default value of the standard deviation which is used to simulate the gaussian noise of the camera's observation (angle between robot heading and marker)
+pub const OBSERVED_ANGLE_SD: f64 = 0.02;
+/// The default value of the increase of the standard deviation of the camera's observation (distance to marker) per meter
+pub const OBSERVED_DIST_SD_PER_METER: f64 = 0.0;
+
+/// The names of the available Agents which can be given to [create_agent]
+pub const AGENT_NAMES: [&str; 3] = ["circular", "square", "waypoints"];
 
 /// Create a concrete Agent specified by the commandline argument such as CircularAgent, SquareAgent and WaypontsAgnet, and returns it as Agent trait object
 ///
 /// ## Arguments
-/// * `args` - a command line argument that means the Agent to be used. This crate can receive the following arguments:
+/// * `name` - a command line argument that means the Agent to be used. This crate can receive the following arguments:
 ///     * circular
 ///     * square
 ///     * waypoints
-/// * `landmarks` - the vector of landmark points which will be observed from robot
+/// * `map` - the landmark map which will be observed from robot
+/// * `robot` - the velocity and acceleration limits of the simulated robot
+/// * `noise` - the standard deviations of the gaussian noise added to the simulated robot and camera
+/// * `camera` - the sensor model of the camera which determines the visible landmarks
 ///
 /// ## Returns
 /// This function returns a instanciated Agent as an Agent trait object
 ///
 /// ## Errors
-/// When no command line argumet is given or unknown argument is given, this function returns Error
-pub fn create_agent(mut args: std::env::Args, landmarks: Vec<Point>) -> Result<Box<dyn Agent>, String> {
-  args.next();
-
-  let agent: Box<dyn Agent> = match args.next() {
-    Some(name) => {
-      match name.to_lowercase().as_str() {
-        "circular" => Box::new(circular_agent::CircularAgent::new(landmarks)),
-        "square" => Box::new(square_agent::SquareAgent::new(landmarks)),
-        "waypoints" => Box::new(waypoints_agent::WaypointsAgent::new(landmarks)),
-        _ => return Err(format!("No agent found: {}", name)),
-      }
-    },
-    None => return Err(format!("Agent name does not found")),
+/// When unknown argument is given, this function returns Error
+pub fn create_agent(name: &str, map: LandmarkMap, robot: RobotConfig, noise: NoiseConfig, camera: CameraConfig) -> Result<Box<dyn Agent>, String> {
+  let agent: Box<dyn Agent> = match name.to_lowercase().as_str() {
+    "circular" => Box::new(circular_agent::CircularAgent::new(map, robot, noise, camera)),
+    "square" => Box::new(square_agent::SquareAgent::new(map, robot, noise, camera)),
+    "waypoints" => Box::new(waypoints_agent::WaypointsAgent::new(map, robot, noise, camera)),
+    _ => return Err(format!("No agent found: {}", name)),
   };
   Ok(agent)
 }
@@ -61,8 +67,17 @@ pub trait AgentDerive: Send {
   /// Get the name of the concrete `struct` that implemented this trait
   fn get_name(&self) -> &str;
 
-  /// Get the landmarks field of the concrete `struct` that implemented this trait
-  fn get_landmarks(&self) -> &Vec<Point>;
+  /// Get the landmark map field of the concrete `struct` that implemented this trait
+  fn get_map(&self) -> &LandmarkMap;
+
+  /// Get the velocity and acceleration limits of the concrete `struct` that implemented this trait
+  fn get_robot_config(&self) -> &RobotConfig;
+
+  /// Get the standard deviations of the gaussian noise of the concrete `struct` that implemented this trait
+  fn get_noise_config(&self) -> &NoiseConfig;
+
+  /// Get the sensor model of the camera of the concrete `struct` that implemented this trait
+  fn get_camera_config(&self) -> &CameraConfig;
 
   /// Set the actual pose (x, y, theta) to the concrete `struct` that implemented this trait
   /// ## Arguments
@@ -83,40 +98,51 @@ pub trait AgentDerive: Send {
 
   /// Move the simulated robot with gaussian noise according to the robot's motion model
   ///
+  /// The simulated robot is propagated from its previous actual pose (not from the estimated pose), so that the actual pose is independent of the estimator.
   /// The simulated actual pose is stored to the concrete Agent's field
   ///
   /// ## Arguments
-  /// * `current` - current pose of robot which is defined as nalgebra::Vector3::new(x, y, theta)
   /// * `input` - input velocity vector (linear velocity, angular velocity) which is defined as nalgebra::Vector2::new(v, omega)
   /// * `delta` - time delta
-  fn noisy_move(&mut self, current: &na::Vector3<f64>, input: &na::Vector2<f64>, delta: f64) -> () {
-    let ideal_pose = robot::ideal_move(current, input, delta);
-    let noisy_pose = na::Vector3::new(
-      Normal::new(ideal_pose[0], ACTUAL_XY_SD).unwrap().sample(&mut rand::thread_rng()),
-      Normal::new(ideal_pose[1], ACTUAL_XY_SD).unwrap().sample(&mut rand::thread_rng()),
-      Normal::new(ideal_pose[2], ACTUAL_THETA_SD).unwrap().sample(&mut rand::thread_rng()),
-    );
+  /// * `rng` - the random number generator owned by the simulation, which generates the gaussian noise
+  fn noisy_move(&mut self, input: &na::Vector2<f64>, delta: f64, rng: &mut StdRng) {
+    let current = *self.get_actual();
+    let noisy_pose = robot::MotionNoise::new(self.get_noise_config()).sample(&current, input, delta, rng);
     self.set_actual(noisy_pose);
   }
 
   /// Observe the landmarks with gaussian noise according to the camera's obervation model
   ///
+  /// Only the landmarks which can be seen from the actual pose according to the camera's sensor model are observed.
   /// The simulated actual observations is stored to the concrete Agent's field
-  fn noisy_observe(&mut self) -> &Vec<Observed> {
-    let observed = self.get_landmarks()
+  ///
+  /// ## Arguments
+  /// * `rng` - the random number generator owned by the simulation, which generates the gaussian noise
+  fn noisy_observe(&mut self, rng: &mut StdRng) -> &Vec<Observed> {
+    let noise = camera::ObservationNoise::new(self.get_noise_config());
+    let points = self.get_map().landmarks().iter().map(|landmark| landmark.point()).collect::<Vec<_>>();
+    let observed = self.get_map()
+                       .landmarks()
                        .iter()
+                       .filter(|landmark| camera::is_visible(&landmark.point(), &points, self.get_actual(), self.get_camera_config()))
                        .map(|landmark| {
                          let actual = self.get_actual();
                          let actual_point = actual.fixed_rows::<2>(0);
                          let dist = (na::Vector2::new(landmark.x, landmark.y) - actual_point).norm_squared().sqrt();
-                         let noisy_dist = Normal::new(dist, OBSERVED_DIST_SD).unwrap().sample(&mut rand::thread_rng());
+                         let sd = noise.sd(dist);
+                         let noisy_dist = Normal::new(dist, sd[0]).unwrap().sample(rng);
                          let angle = (landmark.y - actual[1]).atan2(landmark.x - actual[0]) - actual[2];
-                         let noisy_angle = Normal::new(angle, OBSERVED_ANGLE_SD).unwrap().sample(&mut rand::thread_rng());
+                         let noisy_angle = Normal::new(angle, sd[1]).unwrap().sample(rng);
+                         let noisy_signature = self.get_camera_config()
+                                                   .signature_sd
+                                                   .map(|sd| Normal::new(landmark.signature(), sd).unwrap().sample(rng));
 
                          Observed {
-                           landmark: landmark.clone(),
+                           id: landmark.id,
+                           landmark: landmark.point(),
                            distance: noisy_dist,
                            angle: noisy_angle,
+                           signature: noisy_signature,
                          }
                        })
                        .collect::<Vec<_>>();
@@ -139,7 +165,8 @@ pub trait Agent: AgentDerive {
   /// ## Returns
   /// Tuple of (maximum linear accelaration, maximum angular accelaration)
   fn get_max_accelarations(&self, _: &na::Vector3<f64>) -> (f64, f64) {
-    (robot::MAX_LIN_ACC, robot::MAX_ANG_ACC)
+    let robot = self.get_robot_config();
+    (robot.max_lin_acc, robot.max_ang_acc)
   }
 
   /// Get the maximum and minimum linear velocity values
@@ -151,7 +178,8 @@ pub trait Agent: AgentDerive {
   /// ## Returns
   /// Tuple of (maximum linear velocity, minimum linear velocity)
   fn get_linear_velocities(&self, _: &na::Vector3<f64>) -> (f64, f64) {
-    (robot::MAX_V, robot::MIN_V)
+    let robot = self.get_robot_config();
+    (robot.max_v, robot.min_v)
   }
 
   /// Get the maximum and minimum angular velocity values
@@ -163,7 +191,8 @@ pub trait Agent: AgentDerive {
   /// ## Returns
   /// Tuple of (maximum angular velocity, minimum angular velocity)
   fn get_angular_velocities(&self, _: &na::Vector3<f64>) -> (f64, f64) {
-    (robot::MAX_OMEGA, robot::MIN_OMEGA)
+    let robot = self.get_robot_config();
+    (robot.max_omega, robot.min_omega)
   }
 
   /// Get the ideal pose of the simulated robot
@@ -5,7 +5,9 @@ use std::f64::consts::PI;
 extern crate nalgebra as na;
 
 use crate::agent::{AgentDerive, Agent};
-use crate::data::{Point, Observed};
+use crate::config::{RobotConfig, NoiseConfig, CameraConfig};
+use crate::data::Observed;
+use crate::map::LandmarkMap;
 use crate::utils;
 use agent_derive::AgentDerive;
 
@@ -15,7 +17,10 @@ const INPUT_OMEGA: f64 = 0.4;
 /// A struct which provides a ideal pose that moves on the circumference at a constant angular velocity
 #[derive(AgentDerive)]
 pub struct CircularAgent {
-  landmarks: Vec<Point>,
+  map: LandmarkMap,
+  robot: RobotConfig,
+  noise: NoiseConfig,
+  camera: CameraConfig,
   actual: na::Vector3<f64>,
   observed: Vec<Observed>,
 }
@@ -1,12 +1,14 @@
 //! **\[private\]** The `waypoints_agent` module provides a agent which define a trajectory according to given waypoints.
 
+use std::cell::Cell;
 use std::f64::consts::PI;
 
 extern crate nalgebra as na;
 
 use crate::agent::{AgentDerive, Agent};
-use crate::data::{Point, Observed};
-use crate::models::robot;
+use crate::config::{RobotConfig, NoiseConfig, CameraConfig};
+use crate::data::Observed;
+use crate::map::LandmarkMap;
 use crate::utils;
 use agent_derive::AgentDerive;
 
@@ -29,15 +31,17 @@ const WAYPOINTS: [na::Vector3<f64>; 5] = [na::Vector3::new( 1.0,  0.5,  PI * 3.0
                                           na::Vector3::new(-0.5, -1.0,  0.0),
                                           na::Vector3::new( 1.0, -1.0,  PI / 2.0)];
 
-/// **\[private\]** The index of [WAYPOINTS] which indicate current target waypoint
-static mut CURRENT_IDX: usize = 0;
-
 /// A struct which provides a ideal pose to move to the next waypoint when the simulated robot arrives a waypoint
 #[derive(AgentDerive)]
 pub struct WaypointsAgent {
-  landmarks: Vec<Point>,
+  map: LandmarkMap,
+  robot: RobotConfig,
+  noise: NoiseConfig,
+  camera: CameraConfig,
   actual: na::Vector3<f64>,
   observed: Vec<Observed>,
+  /// the index of [WAYPOINTS] which indicate current target waypoint (owned by each agent, so that the simulations can run in parallel)
+  current_idx: Cell<usize>,
 }
 
 /// The implementation for Agent trait
@@ -53,13 +57,13 @@ impl Agent for WaypointsAgent {
   /// ## Returns
   /// ideal pose of robot which is defined as nalgebra::Vector3::new(x, y, theta)
   fn get_ideal(&self, current: &na::Vector3<f64>, _: f64) -> na::Vector3<f64> {
-    let idx = match WAYPOINTS[current_idx()] {
+    let idx = match WAYPOINTS[self.current_idx.get()] {
       target if (target.fixed_rows::<2>(0) - current.fixed_rows::<2>(0)).norm_squared() < DISTANCE_SQUARED_THRESHOLD &&
                 utils::normalize_angle(target[2] - current[2]).abs() < ANGLE_THRESHOLD => {
-        next_idx()
+        self.next_idx()
       },
       _ => {
-        current_idx()
+        self.current_idx.get()
       },
     };
     WAYPOINTS[idx]
@@ -73,8 +77,8 @@ impl Agent for WaypointsAgent {
   /// ## Returns
   /// Tuple of (maximum linear accelaration, maximum angular accelaration)
   fn get_max_accelarations(&self, current: &na::Vector3<f64>) -> (f64, f64) {
-    let m = if check_dist(current) { NEAR_ACC_MAGNIFICATION } else { 1.0 };
-    (robot::MAX_LIN_ACC * m, robot::MAX_ANG_ACC * m)
+    let m = if self.check_dist(current) { NEAR_ACC_MAGNIFICATION } else { 1.0 };
+    (self.robot.max_lin_acc * m, self.robot.max_ang_acc * m)
   }
 
   /// Get the maximum and minimum linear velocity values
@@ -85,8 +89,8 @@ impl Agent for WaypointsAgent {
   /// ## Returns
   /// Tuple of (maximum linear velocity, minimum linear velocity)
   fn get_linear_velocities(&self, current: &na::Vector3<f64>) -> (f64, f64) {
-    let m = if check_dist(current) { NEAR_LINEAR_MAGNIFICATION } else { 1.0 };
-    (robot::MAX_V * m, robot::MIN_V * m)
+    let m = if self.check_dist(current) { NEAR_LINEAR_MAGNIFICATION } else { 1.0 };
+    (self.robot.max_v * m, self.robot.min_v * m)
   }
 
   /// Get the maximum and minimum angular velocity values
@@ -97,27 +101,20 @@ impl Agent for WaypointsAgent {
   /// ## Returns
   /// Tuple of (maximum angular velocity, minimum angular velocity)
   fn get_angular_velocities(&self, current: &na::Vector3<f64>) -> (f64, f64) {
-    let m = if check_dist(current) { NEAR_ANGULAR_MAGNIFICATION } else { 1.0 };
-    (robot::MAX_OMEGA * m, robot::MIN_OMEGA * m)
+    let m = if self.check_dist(current) { NEAR_ANGULAR_MAGNIFICATION } else { 1.0 };
+    (self.robot.max_omega * m, self.robot.min_omega * m)
   }
 }
 
-/// **\[private\]** Get the current index of [WAYPOINTS]
-fn current_idx() -> usize {
-  unsafe {
-    CURRENT_IDX
+impl WaypointsAgent {
+  /// **\[private\]** Move to the next index of [WAYPOINTS] and get it
+  fn next_idx(&self) -> usize {
+    self.current_idx.set((self.current_idx.get() + 1) % WAYPOINTS.len());
+    self.current_idx.get()
   }
-}
 
-/// **\[private\]** Get the next index of [WAYPOINTS]
-fn next_idx() -> usize {
-  unsafe {
-    CURRENT_IDX = (CURRENT_IDX + 1) % WAYPOINTS.len();
-    CURRENT_IDX
+  /// **\[private\]** Returns `true` when the current posision(x, y) is close to the current target waypoint
+  fn check_dist(&self, current: &na::Vector3<f64>) -> bool {
+    (WAYPOINTS[self.current_idx.get()].fixed_rows::<2>(0) - current.fixed_rows::<2>(0)).norm_squared() < DISTANCE_SQUARED_THRESHOLD
   }
 }
-
-/// **\[private\]** Returns `true` when the current posision(x, y) is close to the current target waypoint
-fn check_dist(current: &na::Vector3<f64>) -> bool {
-  (WAYPOINTS[current_idx()].fixed_rows::<2>(0) - current.fixed_rows::<2>(0)).norm_squared() < DISTANCE_SQUARED_THRESHOLD
-}
@@ -0,0 +1,200 @@
+//! The `cli` module parses the command line arguments of this simulator
+
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::agent;
+use crate::config::{FilterKind, PlannerKind};
+use crate::data::Pose;
+
+/// The usage of this simulator which is shown by `--help`
+pub const USAGE: &str = "\
+A simulator of an autonomous mobile robot using Extended Kalman Filter and Dynamic Window Approach.
+
+USAGE:
+    robot_simulator_rust run <AGENT> [OPTIONS]
+    robot_simulator_rust --list-agents
+    robot_simulator_rust --help
+
+AGENT:
+    circular, square or waypoints (see --list-agents)
+
+OPTIONS:
+    --config <FILE>           the JSON file of the scenario configuration
+    --seed <N>                the seed of the random number generator
+    --port <PORT>             the zeromq port number for the drawing engine
+    --interval-ms <MS>        the interval (milliseconds) of every tick
+    --steps <N>               run N steps in the headless batch mode and print the summary
+    --duration <SEC>          run SEC seconds (in simulation time) in the headless batch mode and print the summary
+    --initial-pose <X,Y,THETA>
+                              the initial pose of the robot
+    --filter <FILTER>         the filter which estimates the pose of the robot (ekf, ukf, pf, ekf_slam, fastslam1 or fastslam2)
+    --planner <PLANNER>       the planner which decides the input of the robot (dwa, pure_pursuit, stanley, pid or mpc)
+    --landmarks-file <FILE>   the map file (JSON, CSV or YAML) of the landmarks which will be observed from robot
+    --trials <N>              run N trials with the seeds SEED, SEED+1, ... in the headless batch mode and print the aggregated report
+    --jobs <N>                the number of threads which execute the trials in parallel
+    --output <FILE>           write the report of the trials to FILE (CSV when its extension is .csv, JSON otherwise)
+    --list-agents             print the available agents and exit
+    -h, --help                print this message and exit
+
+EXIT STATUS:
+    0 on success, 1 when the simulation fails, 2 when the command line arguments are invalid";
+
+/// The command given by the command line arguments
+#[derive(Debug)]
+pub enum Command {
+  /// run the simulation with the given options
+  Run(Box<RunOptions>),
+  /// print the available agents
+  ListAgents,
+  /// print the usage
+  Help,
+}
+
+/// A struct which stores the options of the `run` command
+///
+/// The options which are not `None` override the values of the scenario configuration
+#[derive(Debug)]
+#[derive(Default)]
+pub struct RunOptions {
+  /// the name of the Agent to be used
+  pub agent: String,
+  /// the JSON file of the scenario configuration
+  pub config: Option<PathBuf>,
+  /// the seed of the random number generator
+  pub seed: Option<u64>,
+  /// the zeromq port number for the drawing engine
+  pub port: Option<u64>,
+  /// the interval (milliseconds) of every tick
+  pub interval_ms: Option<u64>,
+  /// the number of steps executed in the headless batch mode
+  pub steps: Option<u64>,
+  /// the duration (sec) in simulation time executed in the headless batch mode
+  pub duration: Option<f64>,
+  /// the initial pose of the robot
+  pub initial_pose: Option<Pose>,
+  /// the filter which estimates the pose of the robot
+  pub filter: Option<FilterKind>,
+  /// the planner which decides the input of the robot
+  pub planner: Option<PlannerKind>,
+  /// the map file (JSON, CSV or YAML) of the landmarks
+  pub landmarks_file: Option<PathBuf>,
+  /// the number of trials of the Monte Carlo experiment
+  pub trials: Option<usize>,
+  /// the number of threads which execute the trials in parallel
+  pub jobs: Option<usize>,
+  /// the file where the report of the trials is written
+  pub output: Option<PathBuf>,
+}
+
+/// Parse the command line arguments
+///
+/// For compatibility, `robot_simulator_rust <AGENT> [OPTIONS]` is treated as `robot_simulator_rust run <AGENT> [OPTIONS]`
+///
+/// ## Arguments
+/// * `args` - the command line arguments except the program name
+///
+/// ## Returns
+/// The command given by the command line arguments
+///
+/// ## Errors
+/// When unknown command, unknown option, invalid value or unknown agent is given, this function returns Error
+pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Command, String> {
+  let mut args = args.into_iter().peekable();
+  let mut options = RunOptions::default();
+  let mut agent: Option<String> = None;
+
+  match args.peek().map(|arg| arg.as_str()) {
+    Some("run") => { args.next(); },
+    None => return Err("no command is given".to_string()),
+    _ => (),
+  }
+
+  while let Some(arg) = args.next() {
+    let (name, inline_value) = match arg.split_once('=') {
+      Some((name, value)) if name.starts_with("--") => (name.to_string(), Some(value.to_string())),
+      _ => (arg.clone(), None),
+    };
+    let mut value = || inline_value.clone().or_else(|| args.next()).ok_or(format!("{} requires a value", name));
+
+    match name.as_str() {
+      "-h" | "--help" => return Ok(Command::Help),
+      "--list-agents" => return Ok(Command::ListAgents),
+      "--config" => options.config = Some(PathBuf::from(value()?)),
+      "--seed" => options.seed = Some(parse_value(&name, &value()?)?),
+      "--port" => options.port = Some(parse_value(&name, &value()?)?),
+      "--interval-ms" => options.interval_ms = Some(parse_value(&name, &value()?)?),
+      "--steps" => options.steps = Some(parse_value(&name, &value()?)?),
+      "--duration" => options.duration = Some(parse_value(&name, &value()?)?),
+      "--initial-pose" => options.initial_pose = Some(parse_pose(&value()?)?),
+      "--filter" => options.filter = Some(parse_value(&name, &value()?)?),
+      "--planner" => options.planner = Some(parse_value(&name, &value()?)?),
+      "--landmarks-file" => options.landmarks_file = Some(PathBuf::from(value()?)),
+      "--trials" => options.trials = Some(parse_value(&name, &value()?)?),
+      "--jobs" => options.jobs = Some(parse_value(&name, &value()?)?),
+      "--output" => options.output = Some(PathBuf::from(value()?)),
+      option if option.starts_with('-') => return Err(format!("unknown option: {}", option)),
+      name if agent.is_none() => agent = Some(name.to_string()),
+      name => return Err(format!("unexpected argument: {}", name)),
+    }
+  }
+
+  options.agent = match agent {
+    Some(name) if agent::AGENT_NAMES.contains(&name.to_lowercase().as_str()) => name.to_lowercase(),
+    Some(name) => return Err(format!("No agent found: {} (available agents: {})", name, agent::AGENT_NAMES.join(", "))),
+    None => return Err("Agent name does not found".to_string()),
+  };
+  if options.steps.is_some() && options.duration.is_some() {
+    return Err("--steps and --duration cannot be given together".to_string());
+  }
+  if options.duration.is_some_and(|duration| !(duration.is_finite() && duration > 0.0)) {
+    return Err("--duration must be a finite number greater than 0".to_string());
+  }
+  if options.interval_ms == Some(0) {
+    return Err("--interval-ms must be greater than 0".to_string());
+  }
+  if options.trials == Some(0) {
+    return Err("--trials must be greater than 0".to_string());
+  }
+  if options.jobs == Some(0) {
+    return Err("--jobs must be greater than 0".to_string());
+  }
+
+  Ok(Command::Run(Box::new(options)))
+}
+
+/// **\[private\]** Parse the value of the given option
+///
+/// ## Arguments
+/// * `name` - the option name such as `--seed`
+/// * `value` - the value of the option
+///
+/// ## Returns
+/// The parsed value
+///
+/// ## Errors
+/// When the value cannot be parsed, this function returns Error
+fn parse_value<T>(name: &str, value: &str) -> Result<T, String>
+  where T: FromStr, T::Err: std::fmt::Display {
+  value.parse::<T>().map_err(|e| format!("invalid value of {}: {} ({})", name, value, e))
+}
+
+/// **\[private\]** Parse the pose given as `X,Y,THETA`
+///
+/// ## Arguments
+/// * `value` - the value of `--initial-pose`
+///
+/// ## Returns
+/// The parsed pose
+///
+/// ## Errors
+/// When the value does not consist of three float numbers, this function returns Error
+fn parse_pose(value: &str) -> Result<Pose, String> {
+  let v = value.split(',')
+               .map(|s| parse_value::<f64>("--initial-pose", s.trim()))
+               .collect::<Result<Vec<_>, _>>()?;
+  match v.as_slice() {
+    [x, y, theta] => Ok(Pose { x: *x, y: *y, theta: *theta }),
+    _ => Err(format!("invalid value of --initial-pose: {} (expected X,Y,THETA)", value)),
+  }
+}
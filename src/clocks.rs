@@ -0,0 +1,136 @@
+//! The `clocks` module provides some clocks which define how the simulation time advances at every tick
+
+use std::fmt;
+use std::time::Instant;
+
+use crate::config::{TimerConfig, ClockKind};
+
+/// Create a concrete Clock specified by the timer configuration, and returns it as Clock trait object
+///
+/// ## Arguments
+/// * `config` - the timer configuration which specifies the kind of clock and its time step
+///
+/// ## Returns
+/// This function returns a instanciated Clock as a Clock trait object
+pub fn create_clock(config: &TimerConfig) -> Box<dyn Clock> {
+  match config.clock {
+    ClockKind::Simulated => Box::new(SimulatedClock::new(config.interval_secs())),
+    ClockKind::Wall => Box::new(WallClock::new()),
+  }
+}
+
+/// A trait which provides the simulation time to [crate::simulation::Simulation]
+pub trait Clock: Send + fmt::Debug {
+
+  /// Advance this clock to the next tick
+  ///
+  /// ## Returns
+  /// Tuple of (elapsed time (sec) from the start of this simulation, time delta (sec) from the previous tick)
+  fn tick(&mut self) -> (f64, f64);
+
+  /// Get the elapsed time at the latest tick
+  ///
+  /// ## Returns
+  /// Elapsed time (sec) from the start of this simulation to the latest tick
+  fn elapsed(&self) -> f64;
+}
+
+/// A clock which advances according to the host's wall-clock time
+///
+/// The time delta depends on the host scheduler, so the simulated trajectory is not reproducible
+#[derive(Debug)]
+pub struct WallClock {
+  /// **\[private\]** the time started simulating
+  start_t: Instant,
+  /// **\[private\]** the time started current tick
+  t: Instant,
+}
+
+impl WallClock {
+  /// Create a WallClock instance which starts at now
+  ///
+  /// ## Returns
+  /// An instance of WallClock
+  pub fn new() -> WallClock {
+    let start_t = Instant::now();
+    let t = start_t;
+
+    WallClock { start_t, t }
+  }
+}
+
+impl Default for WallClock {
+  fn default() -> WallClock {
+    WallClock::new()
+  }
+}
+
+/// The implementation for Clock trait
+impl Clock for WallClock {
+
+  /// Advance this clock to now
+  ///
+  /// ## Returns
+  /// Tuple of (elapsed time (sec) from the start of this simulation, time delta (sec) from the previous tick)
+  fn tick(&mut self) -> (f64, f64) {
+    let t = Instant::now();
+    let delta = (t - self.t).as_secs_f64();
+    self.t = t;
+    (self.elapsed(), delta)
+  }
+
+  /// Get the elapsed time at the latest tick
+  ///
+  /// ## Returns
+  /// Elapsed time (sec) from the start of this simulation to the latest tick
+  fn elapsed(&self) -> f64 {
+    (self.t - self.start_t).as_secs_f64()
+  }
+}
+
+/// A clock which advances by a fixed time step at every tick regardless of the host's wall-clock time
+///
+/// The same step size always produces the same sequence of time, so the simulated trajectory is reproducible
+#[derive(Debug)]
+pub struct SimulatedClock {
+  /// **\[private\]** the fixed time step (sec)
+  step: f64,
+  /// **\[private\]** the number of ticks from the start of this simulation
+  ticks: u64,
+}
+
+impl SimulatedClock {
+  /// Create a SimulatedClock instance
+  ///
+  /// ## Arguments
+  /// * `step` - the fixed time step (sec) of every tick
+  ///
+  /// ## Returns
+  /// An instance of SimulatedClock
+  pub fn new(step: f64) -> SimulatedClock {
+    SimulatedClock { step, ticks: 0 }
+  }
+}
+
+/// The implementation for Clock trait
+impl Clock for SimulatedClock {
+
+  /// Advance this clock by the fixed time step
+  ///
+  /// The elapsed time is calculated from the number of ticks instead of accumulating the time step, so that the rounding error does not grow
+  ///
+  /// ## Returns
+  /// Tuple of (elapsed time (sec) from the start of this simulation, time delta (sec) from the previous tick)
+  fn tick(&mut self) -> (f64, f64) {
+    self.ticks += 1;
+    (self.elapsed(), self.step)
+  }
+
+  /// Get the elapsed time at the latest tick
+  ///
+  /// ## Returns
+  /// Elapsed time (sec) from the start of this simulation to the latest tick
+  fn elapsed(&self) -> f64 {
+    self.step * self.ticks as f64
+  }
+}
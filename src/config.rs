@@ -0,0 +1,1056 @@
+//! The `config` module provides the scenario configuration which stores all tunable parameters of this simulator
+//!
+//! The configuration is loaded from a JSON file. Every field can be omitted, and the omitted fields are filled with the default values.
+//! Unknown fields are rejected so that a misspelled parameter does not silently fall back to its default value.
+//! ```json
+//! {
+//!   "seed": 42,
+//!   "robot": { "max_v": 1.0 },
+//!   "filter": { "q": 0.02 }
+//! }
+//! ```
+
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::{de, Deserialize, Deserializer, Serialize};
+use serde::de::DeserializeOwned;
+use serde_json;
+
+use crate::agent;
+use crate::data::{Point, Pose};
+use crate::filters::{kalman_filter, unscented_kalman_filter, particle_filter, fast_slam, data_association};
+use crate::simulation;
+use crate::pose_graph;
+use crate::map::LandmarkMap;
+use crate::monte_carlo;
+use crate::models::{robot, camera};
+use crate::obstacles::{Obstacle, ObstacleMap};
+use crate::planners::{dwa_wo_obstacle, dwa_with_obstacle, pure_pursuit, stanley, pid, mpc};
+use crate::timers;
+
+/// A struct which stores all tunable parameters of this simulator
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+  /// the seed of the random number generator (a random seed is chosen when it is omitted)
+  pub seed: Option<u64>,
+  /// the parameters of the simulated robot
+  pub robot: RobotConfig,
+  /// the parameters of the noise which is added to the simulated robot and camera
+  pub noise: NoiseConfig,
+  /// the sensor model of the camera which determines the visible landmarks
+  pub camera: CameraConfig,
+  /// the parameters of the filter which estimates the pose of the simulated robot
+  pub filter: FilterConfig,
+  /// the parameters of the data association between the measurements and the landmarks
+  pub association: AssociationConfig,
+  /// the parameters of the pose graph SLAM back-end
+  pub graph: GraphConfig,
+  /// the kind of the planner and the parameters of the planners other than the Dynamic Window Approach
+  pub planner: PlannerConfig,
+  /// the parameters of the Dynamic Window Approach planner
+  pub dwa: DwaConfig,
+  /// the parameters of the timer which executes the simulation
+  pub timer: TimerConfig,
+  /// the parameters of the Monte Carlo experiment which repeats the simulation with different seeds
+  pub monte_carlo: MonteCarloConfig,
+  /// the initial condition and environment of the simulation
+  pub scenario: ScenarioConfig,
+}
+
+impl Config {
+  /// Load a configuration from the given JSON file
+  ///
+  /// ## Arguments
+  /// * `path` - the path of the JSON file
+  ///
+  /// ## Returns
+  /// The loaded configuration
+  ///
+  /// ## Errors
+  /// When the file cannot be read, the file is not a valid configuration or a parameter is out of its range, this function returns Error
+  pub fn load<P: AsRef<Path>>(path: P) -> Result<Config, Box<dyn Error>> {
+    let path = path.as_ref();
+    let content = fs::read_to_string(path).map_err(|e| format!("cannot read config file {}: {}", path.display(), e))?;
+    let config: Config = serde_json::from_str(&content).map_err(|e| format!("invalid config file {}: {}", path.display(), e))?;
+    config.validate().map_err(|e| format!("invalid config file {}: {}", path.display(), e))?;
+    Ok(config)
+  }
+
+  /// Validate the parameters which the simulation divides by or samples the noise with
+  ///
+  /// ## Errors
+  /// When the interval, a resolution, a horizon or a number of particles is not positive, a standard deviation is negative or not finite,
+  /// the accelarations of the robot are not positive, the maximum velocity is less than the minimum velocity, a probability is not between 0 and 1,
+  /// or the sigma points of UKF cannot be scaled, this function returns Error
+  pub fn validate(&self) -> Result<(), String> {
+    let positive = |name: &str, value: f64| {
+      if value.is_finite() && value > 0.0 { Ok(()) } else { Err(format!("{} must be a positive finite number: {}", name, value)) }
+    };
+    let non_negative = |name: &str, value: f64| {
+      if value.is_finite() && value >= 0.0 { Ok(()) } else { Err(format!("{} must be a non-negative finite number: {}", name, value)) }
+    };
+    let count = |name: &str, value: usize| if value > 0 { Ok(()) } else { Err(format!("{} must be positive", name)) };
+    let probability = |name: &str, value: f64| {
+      if value > 0.0 && value < 1.0 { Ok(()) } else { Err(format!("{} must be greater than 0 and less than 1: {}", name, value)) }
+    };
+    let ratio = |name: &str, value: f64| {
+      if (0.0..=1.0).contains(&value) { Ok(()) } else { Err(format!("{} must be between 0 and 1: {}", name, value)) }
+    };
+    let ordered = |max_name: &str, max: f64, min_name: &str, min: f64| {
+      if max.is_finite() && min.is_finite() && max >= min { Ok(()) } else { Err(format!("{} must not be less than {}: {} < {}", max_name, min_name, max, min)) }
+    };
+
+    count("timer.interval_ms", self.timer.interval_ms as usize)?;
+    positive("robot.max_lin_acc", self.robot.max_lin_acc)?;
+    positive("robot.max_ang_acc", self.robot.max_ang_acc)?;
+    ordered("robot.max_v", self.robot.max_v, "robot.min_v", self.robot.min_v)?;
+    ordered("robot.max_omega", self.robot.max_omega, "robot.min_omega", self.robot.min_omega)?;
+    for (i, alpha) in self.noise.actual_alphas.iter().enumerate() {
+      non_negative(&format!("noise.actual_alphas[{}]", i), *alpha)?;
+    }
+    non_negative("noise.actual_xy_sd", self.noise.actual_xy_sd)?;
+    non_negative("noise.actual_theta_sd", self.noise.actual_theta_sd)?;
+    non_negative("noise.observed_dist_sd", self.noise.observed_dist_sd)?;
+    non_negative("noise.observed_angle_sd", self.noise.observed_angle_sd)?;
+    non_negative("noise.observed_dist_sd_per_meter", self.noise.observed_dist_sd_per_meter)?;
+    if let Some(sd) = self.camera.signature_sd {
+      non_negative("camera.signature_sd", sd)?;
+    }
+    if let Some(q) = self.filter.q {
+      non_negative("filter.q", q)?;
+    }
+    if let Some(r) = self.filter.r {
+      non_negative("filter.r", r)?;
+    }
+    if let Some(gate_probability) = self.filter.gate_probability {
+      probability("filter.gate_probability", gate_probability)?;
+    }
+    probability("association.gate_probability", self.association.gate_probability)?;
+    let ukf = &self.filter.ukf;
+    // the sigma points are scaled by n + lambda = alpha^2 * (n + kappa), which is decomposed by Cholesky
+    let scale = ukf.alpha.powi(2) * (unscented_kalman_filter::N as f64 + ukf.kappa);
+    if !(scale.is_finite() && scale > 0.0 && ukf.beta.is_finite()) {
+      return Err(format!("filter.ukf must give a positive alpha^2 * (3 + kappa) and a finite beta: alpha = {}, beta = {}, kappa = {}",
+                         ukf.alpha, ukf.beta, ukf.kappa));
+    }
+    count("filter.pf.particles", self.filter.pf.particles)?;
+    ratio("filter.pf.resample_threshold", self.filter.pf.resample_threshold)?;
+    ratio("filter.pf.alpha_slow", self.filter.pf.alpha_slow)?;
+    ratio("filter.pf.alpha_fast", self.filter.pf.alpha_fast)?;
+    non_negative("filter.pf.margin", self.filter.pf.margin)?;
+    count("filter.fastslam.particles", self.filter.fastslam.particles)?;
+    ratio("filter.fastslam.resample_threshold", self.filter.fastslam.resample_threshold)?;
+    positive("dwa.v_resolution", self.dwa.v_resolution)?;
+    positive("dwa.omega_resolution", self.dwa.omega_resolution)?;
+    positive("dwa.horizon", self.dwa.horizon)?;
+    count("dwa.substeps", self.dwa.substeps)?;
+    positive("planner.mpc.horizon", self.planner.mpc.horizon)?;
+    Ok(())
+  }
+}
+
+/// A struct which stores the velocity and acceleration limits of the simulated robot
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RobotConfig {
+  /// maximum linear acceleration
+  pub max_lin_acc: f64,
+  /// maximum angular acceleration
+  pub max_ang_acc: f64,
+  /// maximum linear velocity
+  pub max_v: f64,
+  /// minimum linear velocity
+  pub min_v: f64,
+  /// maximum angular velocity
+  pub max_omega: f64,
+  /// minimum angular velocity
+  pub min_omega: f64,
+  /// the radius of the robot which is used to detect the collision with the obstacles
+  pub radius: f64,
+}
+
+impl Default for RobotConfig {
+  fn default() -> RobotConfig {
+    RobotConfig {
+      max_lin_acc: robot::MAX_LIN_ACC,
+      max_ang_acc: robot::MAX_ANG_ACC,
+      max_v: robot::MAX_V,
+      min_v: robot::MIN_V,
+      max_omega: robot::MAX_OMEGA,
+      min_omega: robot::MIN_OMEGA,
+      radius: robot::RADIUS,
+    }
+  }
+}
+
+/// A struct which stores the standard deviations of the gaussian noise added to the simulated robot and camera
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NoiseConfig {
+  /// the alpha parameters of the velocity motion model which scale the noise of the robot's motion with its velocities
+  pub actual_alphas: [f64; 6],
+  /// the standard deviation of the robot's position (x, y) in the world frame
+  pub actual_xy_sd: f64,
+  /// the standard deviation of the robot's direction (theta) in the world frame
+  pub actual_theta_sd: f64,
+  /// the standard deviation of the camera's observation (distance to marker)
+  pub observed_dist_sd: f64,
+  /// the standard deviation of the camera's observation (angle between robot heading and marker)
+  pub observed_angle_sd: f64,
+  /// the increase of the standard deviation of the camera's observation (distance to marker) per meter
+  pub observed_dist_sd_per_meter: f64,
+}
+
+impl Default for NoiseConfig {
+  fn default() -> NoiseConfig {
+    NoiseConfig {
+      actual_alphas: agent::ACTUAL_ALPHAS,
+      actual_xy_sd: agent::ACTUAL_XY_SD,
+      actual_theta_sd: agent::ACTUAL_THETA_SD,
+      observed_dist_sd: agent::OBSERVED_DIST_SD,
+      observed_angle_sd: agent::OBSERVED_ANGLE_SD,
+      observed_dist_sd_per_meter: agent::OBSERVED_DIST_SD_PER_METER,
+    }
+  }
+}
+
+/// A struct which stores the sensor model of the camera
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CameraConfig {
+  /// the minimum distance to observe a landmark
+  pub min_range: f64,
+  /// the maximum distance to observe a landmark (unlimited when it is omitted)
+  pub max_range: Option<f64>,
+  /// the angular field of view (radian) which is the whole angle centered on the robot's heading
+  pub fov: f64,
+  /// whether a landmark hidden behind other landmarks is not observed
+  pub occlusion: bool,
+  /// the radius of a landmark which is used to determine the occlusion
+  pub landmark_radius: f64,
+  /// the standard deviation of the measured signature of a landmark (the camera does not measure the signature when it is omitted)
+  pub signature_sd: Option<f64>,
+}
+
+impl Default for CameraConfig {
+  fn default() -> CameraConfig {
+    CameraConfig {
+      min_range: camera::MIN_RANGE,
+      max_range: None,
+      fov: camera::FOV,
+      occlusion: false,
+      landmark_radius: camera::LANDMARK_RADIUS,
+      signature_sd: None,
+    }
+  }
+}
+
+/// The method to associate the measurements with the landmarks of the map
+#[derive(Clone, Copy)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AssociationMethod {
+  /// the camera identifies the observed landmarks, so that no association is needed
+  Known,
+  /// associate each measurement with the nearest landmark in the Mahalanobis distance
+  NearestNeighbor,
+  /// associate all measurements jointly by the Joint Compatibility Branch and Bound
+  Jcbb,
+}
+
+/// A struct which stores the parameters of the data association
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AssociationConfig {
+  /// the method to associate the measurements with the landmarks
+  pub method: AssociationMethod,
+  /// the probability of the chi-square distribution which is used as the gate of the Mahalanobis distance
+  pub gate_probability: f64,
+  /// the maximum difference between the measured signature and the signature of the landmark
+  pub signature_tolerance: f64,
+}
+
+impl Default for AssociationConfig {
+  fn default() -> AssociationConfig {
+    AssociationConfig {
+      method: AssociationMethod::Known,
+      gate_probability: data_association::GATE_PROBABILITY,
+      signature_tolerance: data_association::SIGNATURE_TOLERANCE,
+    }
+  }
+}
+
+/// The method to optimize the pose graph
+#[derive(Clone, Copy)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OptimizationMethod {
+  /// the Gauss-Newton method which always accepts the step of the linearized problem
+  GaussNewton,
+  /// the Levenberg-Marquardt method which damps the step until the error decreases
+  LevenbergMarquardt,
+}
+
+/// A struct which stores the parameters of the pose graph SLAM back-end
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GraphConfig {
+  /// whether the pose graph is recorded and optimized
+  pub enabled: bool,
+  /// the method to optimize the pose graph
+  pub method: OptimizationMethod,
+  /// the number of steps between the periodic optimizations (the graph is optimized only at the end of the run when it is `null`)
+  pub optimize_every: Option<u64>,
+  /// the maximum number of iterations of an optimization
+  pub max_iterations: usize,
+  /// the norm of the step, below which the optimization is regarded as converged
+  pub tolerance: f64,
+  /// the maximum number of the poses of the sliding window
+  /// (the whole trajectory is kept when it is `null`, except in the real-time mode which keeps [pose_graph::REALTIME_MAX_POSES] poses)
+  pub max_poses: Option<usize>,
+}
+
+impl Default for GraphConfig {
+  fn default() -> GraphConfig {
+    GraphConfig {
+      enabled: false,
+      method: OptimizationMethod::LevenbergMarquardt,
+      optimize_every: None,
+      max_iterations: pose_graph::MAX_ITERATIONS,
+      tolerance: pose_graph::TOLERANCE,
+      max_poses: None,
+    }
+  }
+}
+
+/// A struct which stores the noise model assumed by the filter
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FilterConfig {
+  /// the kind of the filter which estimates the pose
+  pub kind: FilterKind,
+  /// the variance of process noise for every element of the pose (the noise model of the robot's motion in `noise` is assumed when it is `null`)
+  pub q: Option<f64>,
+  /// the variance of observation noise for both distance and angle (the noise model of the camera in `noise` is assumed when it is `null`)
+  pub r: Option<f64>,
+  /// the probability of the chi-square distribution which is used as the gate to reject the outliers (the gating is disabled when it is `null`)
+  pub gate_probability: Option<f64>,
+  /// the maximum trace of the covariance matrix, beyond which the filter is regarded as diverged
+  pub max_covariance_trace: f64,
+  /// the way to recover from the divergence
+  pub recovery: RecoveryMethod,
+  /// the variance which is set to the diagonal of the covariance matrix when the filter recovers from the divergence
+  pub reset_variance: f64,
+  /// the parameters of the sigma points of the Unscented Kalman Filter
+  pub ukf: UkfConfig,
+  /// the parameters of the particles of the Particle Filter
+  pub pf: PfConfig,
+  /// the parameters of the particles of FastSLAM
+  pub fastslam: FastSlamConfig,
+}
+
+impl Default for FilterConfig {
+  fn default() -> FilterConfig {
+    FilterConfig {
+      kind: FilterKind::Ekf,
+      q: None,
+      r: None,
+      gate_probability: Some(kalman_filter::GATE_PROBABILITY),
+      max_covariance_trace: simulation::MAX_COVARIANCE_TRACE,
+      recovery: RecoveryMethod::ResetCovariance,
+      reset_variance: simulation::RESET_VARIANCE,
+      ukf: UkfConfig::default(),
+      pf: PfConfig::default(),
+      fastslam: FastSlamConfig::default(),
+    }
+  }
+}
+
+/// The kind of the filter which estimates the pose of the simulated robot
+#[derive(Clone, Copy)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterKind {
+  /// [crate::filters::kalman_filter::EKF] which linearizes the motion and observation models by their jacobians
+  Ekf,
+  /// [crate::filters::unscented_kalman_filter::UKF] which propagates the sigma points through the motion and observation models
+  Ukf,
+  /// [crate::filters::particle_filter::PF] which represents the distribution of the pose by the particles
+  Pf,
+  /// [crate::filters::ekf_slam::EkfSlam] which estimates the landmark map together with the pose
+  #[serde(rename = "ekf_slam")]
+  EkfSlam,
+  /// [crate::filters::fast_slam::FastSlam] which samples the pose from the motion model (FastSLAM 1.0)
+  FastSlam1,
+  /// [crate::filters::fast_slam::FastSlam] which samples the pose from the proposal distribution improved by the measurements (FastSLAM 2.0)
+  FastSlam2,
+}
+
+impl FilterKind {
+  /// Whether this filter estimates the landmark map instead of using the given one
+  pub fn is_slam(&self) -> bool {
+    matches!(self, FilterKind::EkfSlam | FilterKind::FastSlam1 | FilterKind::FastSlam2)
+  }
+}
+
+impl FromStr for FilterKind {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<FilterKind, String> {
+    match s.to_lowercase().as_str() {
+      "ekf" => Ok(FilterKind::Ekf),
+      "ukf" => Ok(FilterKind::Ukf),
+      "pf" => Ok(FilterKind::Pf),
+      "ekf_slam" => Ok(FilterKind::EkfSlam),
+      "fastslam1" => Ok(FilterKind::FastSlam1),
+      "fastslam2" => Ok(FilterKind::FastSlam2),
+      _ => Err(format!("unknown filter (expected ekf, ukf, pf, ekf_slam, fastslam1 or fastslam2): {}", s)),
+    }
+  }
+}
+
+/// A struct which stores the parameters of the sigma points of the Unscented Kalman Filter
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UkfConfig {
+  /// the spread of the sigma points around the mean
+  pub alpha: f64,
+  /// the prior knowledge of the distribution (2 is optimal for gaussian)
+  pub beta: f64,
+  /// the secondary scaling parameter
+  pub kappa: f64,
+}
+
+impl Default for UkfConfig {
+  fn default() -> UkfConfig {
+    UkfConfig {
+      alpha: unscented_kalman_filter::ALPHA,
+      beta: unscented_kalman_filter::BETA,
+      kappa: unscented_kalman_filter::KAPPA,
+    }
+  }
+}
+
+/// A struct which stores the parameters of the particles of the Particle Filter
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PfConfig {
+  /// the number of particles
+  pub particles: usize,
+  /// the ratio of the effective sample size to the number of particles, below which the particles are resampled
+  pub resample_threshold: f64,
+  /// whether the particles are initialized uniformly over the map instead of the initial pose
+  pub global_localization: bool,
+  /// the decay rate of the long-term average of the likelihood
+  pub alpha_slow: f64,
+  /// the decay rate of the short-term average of the likelihood
+  pub alpha_fast: f64,
+  /// the margin (m) around the landmarks where the random particles are sampled
+  pub margin: f64,
+}
+
+impl Default for PfConfig {
+  fn default() -> PfConfig {
+    PfConfig {
+      particles: particle_filter::PARTICLES,
+      resample_threshold: particle_filter::RESAMPLE_THRESHOLD,
+      global_localization: false,
+      alpha_slow: particle_filter::ALPHA_SLOW,
+      alpha_fast: particle_filter::ALPHA_FAST,
+      margin: particle_filter::MARGIN,
+    }
+  }
+}
+
+/// A struct which stores the parameters of the particles of FastSLAM
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FastSlamConfig {
+  /// the number of particles, each of which has its own landmark map
+  pub particles: usize,
+  /// the ratio of the effective sample size to the number of particles, below which the particles are resampled
+  pub resample_threshold: f64,
+  /// the maximum number of ticks of the history of the particles to trace back the trajectory
+  /// (the whole history is kept when it is `null`, except in the real-time mode which keeps [fast_slam::REALTIME_MAX_HISTORY] ticks)
+  pub max_history: Option<usize>,
+}
+
+impl Default for FastSlamConfig {
+  fn default() -> FastSlamConfig {
+    FastSlamConfig {
+      particles: fast_slam::PARTICLES,
+      resample_threshold: fast_slam::RESAMPLE_THRESHOLD,
+      max_history: None,
+    }
+  }
+}
+
+/// The way to recover the filter from the divergence
+#[derive(Clone, Copy)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecoveryMethod {
+  /// keep the estimated pose (or the previous one when it is not finite) and reset the covariance matrix
+  ResetCovariance,
+  /// re-initialize the estimated pose from the measurements at the tick and reset the covariance matrix,
+  /// against the estimated landmarks instead of the map for the SLAM estimators
+  Reinitialize,
+}
+
+/// A struct which stores the parameters of the Dynamic Window Approach planner
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DwaConfig {
+  /// the linear velocity resolution to sampling the Dynamic Window
+  pub v_resolution: f64,
+  /// the angular velocity resolution to sampling the Dynamic Window
+  pub omega_resolution: f64,
+  /// the weights of the evaluation functions used when the goal is "far away"
+  pub far: DwaGains,
+  /// the weights of the evaluation functions used when the goal is "near by"
+  #[serde(deserialize_with = "deserialize_near_gains")]
+  pub near: DwaGains,
+  /// the threshold to determine whether the goal is "far away" or "near by"
+  pub distance_squared_threshold: f64,
+  /// the prediction horizon (sec) over which every candidate input is rolled out (at least the time delta)
+  pub horizon: f64,
+  /// the number of sub-steps of the rollout per time delta
+  pub substeps: usize,
+  /// the margin (m) which the planner keeps from the obstacles in addition to the radius of the robot
+  pub safety_margin: f64,
+  /// the clearance (m) from the obstacles, beyond which the "clearance" evaluation function does not distinguish the candidates
+  pub max_clearance: f64,
+  /// the weight of the "clearance" evaluation function
+  pub clearance_gain: f64,
+}
+
+impl Default for DwaConfig {
+  fn default() -> DwaConfig {
+    DwaConfig {
+      v_resolution: dwa_wo_obstacle::V_RESOLUTION,
+      omega_resolution: dwa_wo_obstacle::OMEGA_RESOLUTION,
+      far: DwaGains::default(),
+      near: DwaGains::near(),
+      distance_squared_threshold: dwa_wo_obstacle::DISTANCE_SQUARED_THRESHOLD,
+      horizon: dwa_wo_obstacle::HORIZON,
+      substeps: dwa_wo_obstacle::SUBSTEPS,
+      safety_margin: dwa_with_obstacle::SAFETY_MARGIN,
+      max_clearance: dwa_with_obstacle::MAX_CLEARANCE,
+      clearance_gain: dwa_with_obstacle::CLEARANCE_GAIN,
+    }
+  }
+}
+
+/// A struct which stores the weights of the evaluation functions of the Dynamic Window Approach planner
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DwaGains {
+  /// the weight of the "target heading" evaluation function
+  pub error_angle: f64,
+  /// the weight of the "velocity" evaluation function
+  pub velocity: f64,
+  /// the weight of the "distance" evaluation function
+  pub distance: f64,
+  /// the weight of the "theta" evaluation function
+  pub theta: f64,
+  /// the weight of the "path distance" evaluation function (the average distance to the goal along the rollout)
+  pub path_distance: f64,
+  /// the weight of the "path heading" evaluation function (the average heading error toward the goal along the rollout)
+  pub path_heading: f64,
+}
+
+impl DwaGains {
+  /// Get the default weights used when the goal is "near by"
+  pub fn near() -> DwaGains {
+    DwaGains {
+      error_angle: dwa_wo_obstacle::NEAR_ERROR_ANGLE_GAIN,
+      velocity: dwa_wo_obstacle::NEAR_VELOCITY_GAIN,
+      distance: dwa_wo_obstacle::NEAR_DISTANCE_GAIN,
+      theta: dwa_wo_obstacle::NEAR_THETA_GAIN,
+      path_distance: dwa_wo_obstacle::NEAR_PATH_DISTANCE_GAIN,
+      path_heading: dwa_wo_obstacle::NEAR_PATH_HEADING_GAIN,
+    }
+  }
+}
+
+/// The default weights are the ones used when the goal is "far away"
+impl Default for DwaGains {
+  fn default() -> DwaGains {
+    DwaGains {
+      error_angle: dwa_wo_obstacle::FAR_ERROR_ANGLE_GAIN,
+      velocity: dwa_wo_obstacle::FAR_VELOCITY_GAIN,
+      distance: dwa_wo_obstacle::FAR_DISTANCE_GAIN,
+      theta: dwa_wo_obstacle::FAR_THETA_GAIN,
+      path_distance: dwa_wo_obstacle::FAR_PATH_DISTANCE_GAIN,
+      path_heading: dwa_wo_obstacle::FAR_PATH_HEADING_GAIN,
+    }
+  }
+}
+
+/// **\[private\]** Overwrite the given default values by the given fields
+///
+/// ## Arguments
+/// * `defaults` - the default values of the omitted fields
+/// * `fields` - the deserialized fields, which may be only a part of the fields
+///
+/// ## Returns
+/// The default values overwritten by the fields
+///
+/// ## Errors
+/// When a field is unknown or has an invalid value, this function returns Error
+fn fill_omitted_fields<T: Serialize + DeserializeOwned>(defaults: T, fields: serde_json::Map<String, serde_json::Value>) -> serde_json::Result<T> {
+  let mut value = serde_json::to_value(defaults)?;
+  if let Some(defaults) = value.as_object_mut() {
+    defaults.extend(fields);
+  }
+  serde_json::from_value(value)
+}
+
+/// **\[private\]** Deserialize the weights used when the goal is "near by", whose omitted fields fall back to [DwaGains::near] instead of [DwaGains::default]
+fn deserialize_near_gains<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DwaGains, D::Error> {
+  fill_omitted_fields(DwaGains::near(), serde_json::Map::deserialize(deserializer)?).map_err(de::Error::custom)
+}
+
+/// A struct which stores the kind of the planner and the parameters of the planners other than the Dynamic Window Approach
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PlannerConfig {
+  /// the kind of the planner which decides the input of the simulated robot
+  pub kind: PlannerKind,
+  /// the parameters of the pure pursuit planner
+  pub pure_pursuit: PurePursuitConfig,
+  /// the parameters of the Stanley planner
+  pub stanley: StanleyConfig,
+  /// the parameters of the PID planner
+  pub pid: PidConfig,
+  /// the parameters of the Model Predictive Control planner
+  pub mpc: MpcConfig,
+}
+
+impl Default for PlannerConfig {
+  fn default() -> PlannerConfig {
+    PlannerConfig {
+      kind: PlannerKind::Dwa,
+      pure_pursuit: PurePursuitConfig::default(),
+      stanley: StanleyConfig::default(),
+      pid: PidConfig::default(),
+      mpc: MpcConfig::default(),
+    }
+  }
+}
+
+/// The kind of the planner which decides the input of the simulated robot
+#[derive(Clone, Copy)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlannerKind {
+  /// [crate::planners::dwa::DWA] which evaluates the sampled inputs of the Dynamic Window (parameterized by `dwa`)
+  Dwa,
+  /// [crate::planners::pure_pursuit::PurePursuit] which follows the arc through the look-ahead point
+  PurePursuit,
+  /// [crate::planners::stanley::Stanley] which steers by the heading error and the cross track error
+  Stanley,
+  /// [crate::planners::pid::PID] which controls the distance and the heading toward the goal
+  Pid,
+  /// [crate::planners::mpc::MPC] which optimizes the sequence of the inputs over the prediction horizon
+  Mpc,
+}
+
+impl FromStr for PlannerKind {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<PlannerKind, String> {
+    match s.to_lowercase().as_str() {
+      "dwa" => Ok(PlannerKind::Dwa),
+      "pure_pursuit" => Ok(PlannerKind::PurePursuit),
+      "stanley" => Ok(PlannerKind::Stanley),
+      "pid" => Ok(PlannerKind::Pid),
+      "mpc" => Ok(PlannerKind::Mpc),
+      _ => Err(format!("unknown planner (expected dwa, pure_pursuit, stanley, pid or mpc): {}", s)),
+    }
+  }
+}
+
+/// A struct which stores the parameters of the pure pursuit planner
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PurePursuitConfig {
+  /// the time (sec) to look ahead the ideal pose of the agent (the goal of next tick is pursued when it is `0`)
+  pub lookahead_time: f64,
+  /// the gain (1/sec) of the linear velocity against the distance to the look-ahead point
+  pub speed_gain: f64,
+  /// the gain (1/sec) of the angular velocity against the angle error when the robot turns in place
+  pub heading_gain: f64,
+  /// the distance (m) to the goal, within which the robot turns in place to the direction of the goal
+  pub arrival_distance: f64,
+}
+
+impl Default for PurePursuitConfig {
+  fn default() -> PurePursuitConfig {
+    PurePursuitConfig {
+      lookahead_time: pure_pursuit::LOOKAHEAD_TIME,
+      speed_gain: pure_pursuit::SPEED_GAIN,
+      heading_gain: pure_pursuit::HEADING_GAIN,
+      arrival_distance: pure_pursuit::ARRIVAL_DISTANCE,
+    }
+  }
+}
+
+/// A struct which stores the parameters of the Stanley planner
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StanleyConfig {
+  /// the gain of the cross track error
+  pub cross_track_gain: f64,
+  /// the softening linear velocity (m/sec) which keeps the steering finite at low speed
+  pub softening: f64,
+  /// the gain (1/sec) of the angular velocity against the steering angle
+  pub heading_gain: f64,
+  /// the gain (1/sec) of the linear velocity against the along track distance to the goal
+  pub speed_gain: f64,
+}
+
+impl Default for StanleyConfig {
+  fn default() -> StanleyConfig {
+    StanleyConfig {
+      cross_track_gain: stanley::CROSS_TRACK_GAIN,
+      softening: stanley::SOFTENING,
+      heading_gain: stanley::HEADING_GAIN,
+      speed_gain: stanley::SPEED_GAIN,
+    }
+  }
+}
+
+/// A struct which stores the parameters of the PID planner
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PidConfig {
+  /// the gains of the controller of the linear velocity against the distance to the goal
+  pub linear: PidGains,
+  /// the gains of the controller of the angular velocity against the heading error
+  #[serde(deserialize_with = "deserialize_angular_gains")]
+  pub angular: PidGains,
+  /// the maximum absolute value of the integrated errors, which prevents the windup of the integral terms
+  pub integral_limit: f64,
+  /// the distance (m) to the goal, within which the heading is controlled toward the direction of the goal
+  pub arrival_distance: f64,
+}
+
+impl Default for PidConfig {
+  fn default() -> PidConfig {
+    PidConfig {
+      linear: PidGains::default(),
+      angular: PidGains::angular(),
+      integral_limit: pid::INTEGRAL_LIMIT,
+      arrival_distance: pid::ARRIVAL_DISTANCE,
+    }
+  }
+}
+
+/// A struct which stores the gains of a PID controller
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PidGains {
+  /// the proportional gain
+  pub p: f64,
+  /// the integral gain
+  pub i: f64,
+  /// the derivative gain
+  pub d: f64,
+}
+
+impl PidGains {
+  /// Get the default gains of the controller of the angular velocity
+  pub fn angular() -> PidGains {
+    PidGains { p: pid::ANGULAR_P, i: pid::ANGULAR_I, d: pid::ANGULAR_D }
+  }
+}
+
+/// The default gains are the ones of the controller of the linear velocity
+impl Default for PidGains {
+  fn default() -> PidGains {
+    PidGains { p: pid::LINEAR_P, i: pid::LINEAR_I, d: pid::LINEAR_D }
+  }
+}
+
+/// **\[private\]** Deserialize the gains of the controller of the angular velocity, whose omitted fields fall back to [PidGains::angular] instead of [PidGains::default]
+fn deserialize_angular_gains<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PidGains, D::Error> {
+  fill_omitted_fields(PidGains::angular(), serde_json::Map::deserialize(deserializer)?).map_err(de::Error::custom)
+}
+
+/// A struct which stores the parameters of the Model Predictive Control planner
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MpcConfig {
+  /// the prediction horizon (sec) over which the sequence of the inputs is optimized (at least the time delta)
+  pub horizon: f64,
+  /// the number of iterations of the gradient descent at every tick
+  pub iterations: usize,
+  /// the weight of the squared distance between the predicted position and the reference position
+  pub position_weight: f64,
+  /// the weight of the squared angle error between the predicted direction and the reference direction
+  pub theta_weight: f64,
+  /// the weight of the squared inputs
+  pub input_weight: f64,
+  /// the weight of the squared changes of the inputs between the consecutive time deltas
+  pub rate_weight: f64,
+}
+
+impl Default for MpcConfig {
+  fn default() -> MpcConfig {
+    MpcConfig {
+      horizon: mpc::HORIZON,
+      iterations: mpc::ITERATIONS,
+      position_weight: mpc::POSITION_WEIGHT,
+      theta_weight: mpc::THETA_WEIGHT,
+      input_weight: mpc::INPUT_WEIGHT,
+      rate_weight: mpc::RATE_WEIGHT,
+    }
+  }
+}
+
+/// The kind of the clock which provides the simulation time
+#[derive(Clone, Copy)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClockKind {
+  /// [crate::clocks::SimulatedClock] which advances by `interval_ms` at every tick
+  Simulated,
+  /// [crate::clocks::WallClock] which advances according to the host's wall-clock time
+  Wall,
+}
+
+/// A struct which stores the parameters of the timer which executes the simulation
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimerConfig {
+  /// the interval (milliseconds) of every tick
+  pub interval_ms: u64,
+  /// the zeromq port number for the drawing engine
+  pub port: u64,
+  /// the kind of the clock which provides the simulation time
+  pub clock: ClockKind,
+}
+
+impl TimerConfig {
+  /// Get the interval of every tick as seconds
+  pub fn interval_secs(&self) -> f64 {
+    self.interval_ms as f64 / 1000.0
+  }
+}
+
+impl Default for TimerConfig {
+  fn default() -> TimerConfig {
+    TimerConfig {
+      interval_ms: timers::INTERVAL_MS,
+      port: timers::PORT,
+      clock: ClockKind::Simulated,
+    }
+  }
+}
+
+/// A struct which stores the parameters of the Monte Carlo experiment
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MonteCarloConfig {
+  /// the number of trials (the simulation is executed only once when it is `1`)
+  pub trials: usize,
+  /// the number of threads which execute the trials in parallel (the number of available cores is used when it is `null`)
+  pub jobs: Option<usize>,
+  /// the maximum error (m) of the estimated position, beyond which the trial is counted as lost
+  pub lost_position_error: f64,
+}
+
+impl Default for MonteCarloConfig {
+  fn default() -> MonteCarloConfig {
+    MonteCarloConfig {
+      trials: 1,
+      jobs: None,
+      lost_position_error: monte_carlo::LOST_POSITION_ERROR,
+    }
+  }
+}
+
+/// A struct which stores the initial condition and environment of the simulation
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScenarioConfig {
+  /// the initial pose of the robot
+  pub initial_pose: Pose,
+  /// the landmark points which will be observed from robot (used when `landmarks_file` is not given)
+  pub landmarks: Vec<Point>,
+  /// the map file (JSON, CSV or YAML) of the landmarks which will be observed from robot (see [crate::map])
+  pub landmarks_file: Option<PathBuf>,
+  /// the obstacles which the robot should not collide with (see [crate::obstacles])
+  pub obstacles: Vec<Obstacle>,
+}
+
+impl ScenarioConfig {
+  /// Get the landmark map of this scenario
+  ///
+  /// ## Returns
+  /// The map loaded from `landmarks_file` when it is given, otherwise the map of `landmarks`
+  ///
+  /// ## Errors
+  /// When the map file cannot be loaded or the landmarks are invalid, this function returns Error
+  pub fn load_map(&self) -> Result<LandmarkMap, Box<dyn Error>> {
+    match &self.landmarks_file {
+      Some(path) => LandmarkMap::load(path),
+      None => Ok(LandmarkMap::from_points(&self.landmarks)?),
+    }
+  }
+
+  /// Get the obstacle map of this scenario
+  ///
+  /// ## Returns
+  /// The map of `obstacles`
+  ///
+  /// ## Errors
+  /// When the shape of an obstacle is invalid, this function returns Error
+  pub fn load_obstacles(&self) -> Result<ObstacleMap, String> {
+    ObstacleMap::new(self.obstacles.clone())
+  }
+}
+
+impl Default for ScenarioConfig {
+  fn default() -> ScenarioConfig {
+    ScenarioConfig {
+      initial_pose: crate::INITIAL_POSE,
+      landmarks: crate::LANDMARKS.to_vec(),
+      landmarks_file: None,
+      obstacles: Vec::new(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn default_config_is_valid() {
+    assert!(Config::default().validate().is_ok());
+  }
+
+  #[test]
+  fn validate_rejects_out_of_range_parameters() {
+    let mut config = Config::default();
+    config.timer.interval_ms = 0;
+    assert!(config.validate().is_err());
+
+    let mut config = Config::default();
+    config.noise.actual_alphas[3] = f64::NAN;
+    assert!(config.validate().is_err());
+
+    let mut config = Config::default();
+    config.dwa.v_resolution = 0.0;
+    assert!(config.validate().is_err());
+
+    let mut config = Config::default();
+    config.robot.max_lin_acc = 0.0;
+    assert!(config.validate().is_err());
+
+    let mut config = Config::default();
+    config.filter.pf.margin = -0.1;
+    assert!(config.validate().is_err());
+
+    let mut config = Config::default();
+    config.filter.gate_probability = Some(1.0);
+    assert!(config.validate().is_err());
+
+    let mut config = Config::default();
+    config.association.gate_probability = 0.0;
+    assert!(config.validate().is_err());
+
+    // alpha^2 * (3 + kappa) = 0, so that the sigma points collapse
+    let mut config = Config::default();
+    config.filter.ukf.kappa = -3.0;
+    assert!(config.validate().is_err());
+
+    let mut config = Config::default();
+    config.filter.fastslam.resample_threshold = 1.5;
+    assert!(config.validate().is_err());
+
+    let mut config = Config::default();
+    config.robot.min_omega = config.robot.max_omega + 0.1;
+    assert!(config.validate().is_err());
+  }
+
+  #[test]
+  fn omitted_dwa_gains_fall_back_to_their_defaults() {
+    let config: Config = serde_json::from_str(r#"{ "dwa": { "far": { "velocity": 0.3 }, "near": { "distance": 1.0 } } }"#).unwrap();
+    assert_eq!(config.dwa.far.velocity, 0.3);
+    assert_eq!(config.dwa.far.theta, dwa_wo_obstacle::FAR_THETA_GAIN);
+    assert_eq!(config.dwa.near.distance, 1.0);
+    assert_eq!(config.dwa.near.theta, dwa_wo_obstacle::NEAR_THETA_GAIN);
+    assert!(serde_json::from_str::<Config>(r#"{ "dwa": { "near": { "unknown": 1.0 } } }"#).is_err());
+  }
+
+  #[test]
+  fn omitted_pid_gains_fall_back_to_their_defaults() {
+    let config: Config = serde_json::from_str(r#"{ "planner": { "pid": { "linear": { "i": 0.1 }, "angular": { "p": 3.0 } } } }"#).unwrap();
+    assert_eq!(config.planner.pid.linear.i, 0.1);
+    assert_eq!(config.planner.pid.linear.p, pid::LINEAR_P);
+    assert_eq!(config.planner.pid.angular.p, 3.0);
+    assert_eq!(config.planner.pid.angular.d, pid::ANGULAR_D);
+  }
+}
@@ -30,12 +30,66 @@ impl Pose {
   }
 }
 
+/// A struct which defines a weighted particle (x, y, theta) of the particle filter
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct Particle {
+  pub x: f64,
+  pub y: f64,
+  pub theta: f64,
+  pub weight: f64,
+}
+
+/// A struct which defines a landmark estimated by SLAM
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct EstimatedLandmark {
+  pub id: u64,
+  pub x: f64,
+  pub y: f64,
+  /// the covariance matrix (2x2, row-major) of the estimated position
+  pub covariance: Vec<f64>,
+}
+
 /// A struct which defines an observed values (distance, angle) of a landmark
 #[derive(Clone)]
 #[derive(Debug)]
 #[derive(Serialize, Deserialize)]
 pub struct Observed {
+  pub id: u64,
   pub landmark: Point,
   pub distance: f64,
   pub angle: f64,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub signature: Option<f64>,
+}
+
+impl Observed {
+  /// Create the [Measurement] which is given to the filter
+  ///
+  /// ## Arguments
+  /// * `labeled` - whether the camera can identify the observed landmark. When it is `false`, the measurement does not have the landmark ID
+  pub fn to_measurement(&self, labeled: bool) -> Measurement {
+    Measurement {
+      id: if labeled { Some(self.id) } else { None },
+      distance: self.distance,
+      angle: self.angle,
+      signature: self.signature,
+    }
+  }
+}
+
+/// A struct which defines a measurement (distance, angle) given to the filter
+///
+/// Unlike [Observed], the position of the landmark is not included, and the landmark ID is known only when the camera can identify the landmark
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct Measurement {
+  pub id: Option<u64>,
+  pub distance: f64,
+  pub angle: f64,
+  pub signature: Option<f64>,
 }
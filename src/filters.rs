@@ -1,3 +1,245 @@
 //! The `filters` module provides submodule(s) to estimate a pose of a robot in its state-space model
 
 pub mod kalman_filter;
+pub mod unscented_kalman_filter;
+pub mod particle_filter;
+pub mod ekf_slam;
+pub mod fast_slam;
+pub mod data_association;
+
+use std::error::Error;
+use std::fmt;
+
+extern crate nalgebra as na;
+use rand::rngs::StdRng;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{Config, FilterKind};
+use crate::data::{Pose, Particle, Measurement, EstimatedLandmark};
+use crate::map::LandmarkMap;
+use crate::utils;
+
+/// Create a concrete Estimator specified by the filter configuration such as EKF, UKF, PF, EKF-SLAM and FastSLAM, and returns it as Estimator trait object
+///
+/// ## Arguments
+/// * `map` - the landmark map which is used to estimate the pose (SLAM estimators do not use it)
+/// * `rng` - the random number generator which is used by the estimator itself (e.g. sampling particles), independent of the simulated world
+/// * `config` - the configuration which has the initial pose of the robot, the kind of filter and its parameters
+///
+/// ## Returns
+/// This function returns a instanciated Estimator as an Estimator trait object
+///
+/// ## Errors
+/// When the estimator cannot be created from the landmark map (e.g. the particle filter with an empty map), this function returns Error
+pub fn create_estimator(map: LandmarkMap, rng: StdRng, config: &Config) -> Result<Box<dyn Estimator>, String> {
+  Ok(match config.filter.kind {
+    FilterKind::Ekf => Box::new(kalman_filter::EKF::new(map, config)),
+    FilterKind::Ukf => Box::new(unscented_kalman_filter::UKF::new(map, config)),
+    FilterKind::Pf => Box::new(particle_filter::PF::new(map, rng, config)?),
+    FilterKind::EkfSlam => Box::new(ekf_slam::EkfSlam::new(config)),
+    FilterKind::FastSlam1 | FilterKind::FastSlam2 => Box::new(fast_slam::FastSlam::new(rng, config)),
+  })
+}
+
+/// A trait which estimates a pose of a robot from its inputs and measurements
+///
+/// The estimator does not know the simulated world: the inputs and the measurements associated with the landmarks are given by
+/// [crate::simulation::Simulation] at every tick
+pub trait Estimator: Send + fmt::Debug {
+
+  /// Calculate the "predict step" by the input of this tick
+  ///
+  /// ## Arguments
+  /// * `input` - the input vector(linear velocity, angular velocity)
+  /// * `delta` - time delta
+  fn predict(&mut self, input: &na::Vector2<f64>, delta: f64);
+
+  /// Calculate the "update step" by the measurements of this tick
+  ///
+  /// ## Arguments
+  /// * `measurements` - the measurements whose `id` is the associated landmark ID
+  ///
+  /// ## Returns
+  /// The results of the measurements in the same order. When a measurement cannot be applied, its result is [UpdateError]
+  fn update(&mut self, measurements: &[Measurement]) -> Vec<Result<(), UpdateError>>;
+
+  /// Get the current estimated pose(x, y, theta)
+  fn state(&self) -> na::Vector3<f64>;
+
+  /// Get the current covariance matrix of the estimated pose
+  fn covariance(&self) -> na::Matrix3<f64>;
+
+  /// Reset the estimated pose and its covariance matrix, which is used to recover from the divergence
+  ///
+  /// ## Arguments
+  /// * `state` - the new estimated pose(x, y, theta)
+  /// * `covariance` - the new covariance matrix
+  fn reset(&mut self, state: &na::Vector3<f64>, covariance: &na::Matrix3<f64>);
+
+  /// Get the kalman gain of the latest "update step"
+  ///
+  /// ## Returns
+  /// The kalman gain, or the zero matrix when this estimator does not use the kalman gain
+  fn gain(&self) -> na::Matrix3x2<f64> {
+    na::Matrix3x2::zeros()
+  }
+
+  /// Get the normalized innovation squared of the measurements which are applied at the latest "update step"
+  ///
+  /// ## Returns
+  /// The normalized innovation squared of every applied measurement, or an empty vector when this estimator does not calculate it
+  fn nis(&self) -> Vec<f64> {
+    Vec::new()
+  }
+
+  /// Get the particles which represent the distribution of the estimated pose
+  ///
+  /// ## Returns
+  /// The weighted particles, or an empty vector when this estimator does not use particles
+  fn particles(&self) -> Vec<Particle> {
+    Vec::new()
+  }
+
+  /// Get the landmark map estimated by this estimator
+  ///
+  /// ## Returns
+  /// The estimated landmarks, or an empty vector when this estimator uses the given landmark map
+  fn landmarks(&self) -> Vec<EstimatedLandmark> {
+    Vec::new()
+  }
+
+  /// Get the trajectory of the robot estimated by this estimator
+  ///
+  /// ## Returns
+  /// The estimated poses from the start of the simulation, or an empty vector when this estimator does not estimate the trajectory
+  fn trajectory(&self) -> Vec<Pose> {
+    Vec::new()
+  }
+}
+
+/// The error which occurs when a measurement cannot be applied in the "update step"
+#[derive(Clone, Copy)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub enum UpdateError {
+  /// the measurement is not associated with any landmark known by the estimator
+  UnknownLandmark,
+  /// the normalized innovation squared of the measurement exceeds the gate
+  Outlier(f64),
+  /// the jacobian of the observation equation is not defined because the estimated pose sits on the landmark
+  SingularJacobian,
+  /// the innovation covariance matrix is not invertible
+  SingularInnovationCovariance,
+  /// the covariance matrix cannot be decomposed to generate the sigma points
+  SigmaPoints,
+  /// the weights of the particles cannot be normalized because all of them are zero or not finite
+  DegenerateWeights,
+}
+
+impl fmt::Display for UpdateError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      UpdateError::UnknownLandmark => write!(f, "the measurement is not associated with any known landmark"),
+      UpdateError::Outlier(nis) => write!(f, "the measurement is an outlier (normalized innovation squared: {})", nis),
+      UpdateError::SingularJacobian => write!(f, "the jacobian of the observation equation is not defined at the estimated pose"),
+      UpdateError::SingularInnovationCovariance => write!(f, "the innovation covariance matrix is singular"),
+      UpdateError::SigmaPoints => write!(f, "the covariance matrix cannot be decomposed to generate the sigma points"),
+      UpdateError::DegenerateWeights => write!(f, "the weights of the particles are degenerate"),
+    }
+  }
+}
+
+impl Error for UpdateError {}
+
+/// The kind of divergence of the filter which is detected at the end of every tick
+#[derive(Clone, Copy)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Divergence {
+  /// the estimated pose or the covariance matrix has NaN or infinity
+  NanState,
+  /// the covariance matrix is not positive semi-definite
+  NotPositiveSemiDefinite,
+  /// the trace of the covariance matrix exceeds the maximum
+  CovarianceBlowup,
+}
+
+impl fmt::Display for Divergence {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Divergence::NanState => write!(f, "the state has NaN or infinity"),
+      Divergence::NotPositiveSemiDefinite => write!(f, "the covariance matrix is not positive semi-definite"),
+      Divergence::CovarianceBlowup => write!(f, "the covariance matrix blows up"),
+    }
+  }
+}
+
+/// Detect the divergence of the filter
+///
+/// ## Arguments
+/// * `xhat` - the estimated pose(x, y, theta)
+/// * `p` - the covariance matrix
+/// * `max_covariance_trace` - the maximum trace of the covariance matrix
+///
+/// ## Returns
+/// The kind of divergence, or `None` when the filter is healthy
+pub(crate) fn detect_divergence(xhat: &na::Vector3<f64>, p: &na::Matrix3<f64>, max_covariance_trace: f64) -> Option<Divergence> {
+  if xhat.iter().chain(p.iter()).any(|v| !v.is_finite()) {
+    return Some(Divergence::NanState);
+  }
+  // the tolerance allows the rounding error of the covariance matrix which is nearly zero
+  if ((p + p.transpose()) / 2.0).symmetric_eigenvalues().min() < -1e-9 {
+    return Some(Divergence::NotPositiveSemiDefinite);
+  }
+  if p.trace() > max_covariance_trace {
+    return Some(Divergence::CovarianceBlowup);
+  }
+  None
+}
+
+/// Estimate the pose only from the measurements associated with the landmarks
+///
+/// The direction is the circular mean of the directions calculated from every pair of measurements,
+/// and the position is the mean of the positions calculated from every measurement with the direction
+///
+/// ## Arguments
+/// * `map` - the landmark map
+/// * `measurements` - the measurements at this tick
+/// * `ids` - the landmark IDs associated with the measurements
+/// * `reference_theta` - the direction which the estimated direction is unwrapped around, so that the direction stays continuous
+///
+/// ## Returns
+/// The estimated pose(x, y, theta), or `None` when less than two measurements are associated with different landmarks
+pub(crate) fn pose_from_measurements(map: &LandmarkMap, measurements: &[Measurement], ids: &[Option<u64>], reference_theta: f64)
+  -> Option<na::Vector3<f64>> {
+  let observations = measurements.iter()
+                                 .zip(ids.iter())
+                                 .filter_map(|(m, id)| id.and_then(|id| map.get(id)).map(|l| (l.point(), m.distance, m.angle)))
+                                 .filter(|(_, distance, angle)| distance.is_finite() && angle.is_finite())
+                                 .collect::<Vec<_>>();
+  let (mut sin, mut cos, mut pairs) = (0.0, 0.0, 0);
+  for (i, (li, di, ai)) in observations.iter().enumerate() {
+    for (lj, dj, aj) in observations.iter().skip(i + 1) {
+      let (dx, dy) = (lj.x - li.x, lj.y - li.y);
+      let (rx, ry) = (dj * aj.cos() - di * ai.cos(), dj * aj.sin() - di * ai.sin());
+      if dx.hypot(dy) < f64::EPSILON || rx.hypot(ry) < f64::EPSILON {
+        continue;
+      }
+      let theta = dy.atan2(dx) - ry.atan2(rx);
+      sin += theta.sin();
+      cos += theta.cos();
+      pairs += 1;
+    }
+  }
+  if pairs == 0 {
+    return None;
+  }
+  let theta = sin.atan2(cos);
+  let theta = reference_theta + utils::normalize_angle(theta - reference_theta);
+  let n = observations.len() as f64;
+  let x = observations.iter().map(|(l, d, a)| l.x - d * (theta + a).cos()).sum::<f64>() / n;
+  let y = observations.iter().map(|(l, d, a)| l.y - d * (theta + a).sin()).sum::<f64>() / n;
+  Some(na::Vector3::new(x, y, theta))
+}
@@ -0,0 +1,276 @@
+//! The `data_association` module associates the measurements which do not have the landmark ID with the landmarks of the map
+//!
+//! The following methods are provided
+//! * Nearest Neighbor: each measurement is associated individually with the landmark which has the smallest Mahalanobis distance within the gate
+//! * [Joint Compatibility Branch and Bound](https://doi.org/10.1109/70.976019): all measurements are associated jointly so that the largest set of pairings is jointly compatible
+
+use serde::{Deserialize, Serialize};
+
+extern crate nalgebra as na;
+
+use crate::config::{AssociationConfig, AssociationMethod};
+use crate::data::{Point, Measurement};
+use crate::map::{Landmark, LandmarkMap};
+use crate::models::camera;
+use crate::utils;
+
+/// The default value of the probability of the chi-square distribution which is used as the gate of the Mahalanobis distance
+pub const GATE_PROBABILITY: f64 = 0.99;
+/// The default value of the maximum difference between the measured signature and the signature of the landmark
+pub const SIGNATURE_TOLERANCE: f64 = 0.5;
+
+/// A struct which stores the statistics of the data association at a tick
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+#[derive(Serialize, Deserialize)]
+pub struct AssociationStats {
+  /// the number of measurements
+  pub measurements: usize,
+  /// the number of measurements associated with a landmark
+  pub associated: usize,
+  /// the number of measurements associated with the landmark actually observed
+  pub correct: usize,
+  /// the number of measurements associated with a landmark which is not actually observed
+  pub wrong: usize,
+}
+
+impl AssociationStats {
+  /// Create the statistics by comparing the associated landmark IDs with the actual landmark IDs
+  ///
+  /// ## Arguments
+  /// * `associated` - the landmark IDs associated with the measurements (`None` means that the measurement is not associated)
+  /// * `actual` - the landmark IDs actually observed
+  ///
+  /// ## Returns
+  /// The statistics of the data association
+  pub fn new(associated: &[Option<u64>], actual: &[u64]) -> AssociationStats {
+    let mut stats = AssociationStats { measurements: associated.len(), ..Default::default() };
+    for (id, actual) in associated.iter().zip(actual.iter()) {
+      match id {
+        Some(id) if id == actual => { stats.associated += 1; stats.correct += 1; },
+        Some(_) => { stats.associated += 1; stats.wrong += 1; },
+        None => (),
+      }
+    }
+    stats
+  }
+
+  /// Accumulate the given statistics into this statistics
+  pub fn accumulate(&mut self, other: &AssociationStats) {
+    self.measurements += other.measurements;
+    self.associated += other.associated;
+    self.correct += other.correct;
+    self.wrong += other.wrong;
+  }
+}
+
+/// Associate the measurements with the landmarks of the map
+///
+/// ## Arguments
+/// * `measurements` - the measurements at this tick
+/// * `map` - the landmark map
+/// * `xhat` - the predicted pose(x, y, theta)
+/// * `p` - the predicted covariance matrix
+/// * `noise` - the noise model of the camera which gives the covariance matrix of observation noise for every measurement
+/// * `config` - the parameters of the data association
+///
+/// ## Returns
+/// The landmark IDs associated with the measurements in the same order (`None` means that the measurement is not associated)
+pub fn associate(measurements: &[Measurement], map: &LandmarkMap, xhat: &na::Vector3<f64>, p: &na::Matrix3<f64>, noise: &camera::ObservationNoise,
+                 config: &AssociationConfig) -> Vec<Option<u64>> {
+  let landmarks = map.landmarks();
+  let indexes = match config.method {
+    AssociationMethod::Known => return measurements.iter().map(|m| m.id).collect(),
+    AssociationMethod::NearestNeighbor => nearest_neighbor(measurements, landmarks, xhat, p, noise, config),
+    AssociationMethod::Jcbb => Jcbb::new(measurements, landmarks, xhat, p, noise, config).search(),
+  };
+  indexes.iter().map(|idx| idx.map(|idx| landmarks[idx].id)).collect()
+}
+
+/// **\[private\]** Associate each measurement individually with the nearest landmark in the Mahalanobis distance
+///
+/// ## Returns
+/// The indexes of the landmarks associated with the measurements
+fn nearest_neighbor(measurements: &[Measurement], landmarks: &[Landmark], xhat: &na::Vector3<f64>, p: &na::Matrix3<f64>, noise: &camera::ObservationNoise,
+                    config: &AssociationConfig) -> Vec<Option<usize>> {
+  measurements.iter()
+              .map(|m| {
+                candidates(m, landmarks, xhat, p, noise, config).first().map(|(idx, _)| *idx)
+              })
+              .collect()
+}
+
+/// **\[private\]** Get the landmarks which are individually compatible with the measurement
+///
+/// ## Returns
+/// The tuples of (the index of landmark, the squared Mahalanobis distance) sorted by the distance
+fn candidates(measurement: &Measurement, landmarks: &[Landmark], xhat: &na::Vector3<f64>, p: &na::Matrix3<f64>, noise: &camera::ObservationNoise,
+              config: &AssociationConfig) -> Vec<(usize, f64)> {
+  let gate = utils::chi2_quantile(config.gate_probability, 2);
+  let mut candidates = landmarks.iter()
+                                .enumerate()
+                                .filter(|(_, landmark)| {
+                                  measurement.signature.map_or(true, |s| (s - landmark.signature()).abs() <= config.signature_tolerance)
+                                })
+                                .filter_map(|(idx, landmark)| {
+                                  let point = landmark.point();
+                                  let yhat = innovation(measurement, &point, xhat);
+                                  let h = camera::calc_h(&point, xhat)?;
+                                  let s = h * p * h.transpose() + noise.covariance(measurement.distance);
+                                  s.try_inverse().map(|s_inv| (idx, (yhat.transpose() * s_inv * yhat)[0]))
+                                })
+                                .filter(|(_, d2)| *d2 < gate)
+                                .collect::<Vec<_>>();
+  candidates.sort_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+  candidates
+}
+
+/// **\[private\]** Calculate the innovation between the measurement and the predicted observation of the landmark
+fn innovation(measurement: &Measurement, landmark: &Point, xhat: &na::Vector3<f64>) -> na::Vector2<f64> {
+  camera::innovation(&na::Vector2::new(measurement.distance, measurement.angle), landmark, xhat)
+}
+
+/// **\[private\]** A struct which searches the interpretation tree by the Joint Compatibility Branch and Bound
+struct Jcbb<'a> {
+  /// the measurements at this tick
+  measurements: &'a [Measurement],
+  /// the landmarks of the map
+  landmarks: &'a [Landmark],
+  /// the predicted pose(x, y, theta)
+  xhat: &'a na::Vector3<f64>,
+  /// the predicted covariance matrix
+  p: &'a na::Matrix3<f64>,
+  /// the noise model of the camera which gives the covariance matrix of observation noise for every measurement
+  noise: &'a camera::ObservationNoise,
+  /// the probability of the chi-square distribution which is used as the gate
+  gate_probability: f64,
+  /// the indexes of the individually compatible landmarks of each measurement
+  candidates: Vec<Vec<usize>>,
+  /// the best hypothesis found so far
+  best: Vec<Option<usize>>,
+  /// the number of pairings of the best hypothesis
+  best_pairings: usize,
+}
+
+impl<'a> Jcbb<'a> {
+  /// Create a Jcbb instance
+  fn new(measurements: &'a [Measurement], landmarks: &'a [Landmark], xhat: &'a na::Vector3<f64>, p: &'a na::Matrix3<f64>, noise: &'a camera::ObservationNoise,
+         config: &AssociationConfig) -> Jcbb<'a> {
+    let candidates = measurements.iter()
+                                 .map(|m| candidates(m, landmarks, xhat, p, noise, config).iter().map(|(idx, _)| *idx).collect())
+                                 .collect();
+    Jcbb {
+      measurements, landmarks, xhat, p, noise,
+      gate_probability: config.gate_probability,
+      candidates,
+      best: vec![None; measurements.len()],
+      best_pairings: 0,
+    }
+  }
+
+  /// Search the hypothesis which has the largest number of jointly compatible pairings
+  ///
+  /// ## Returns
+  /// The indexes of the landmarks associated with the measurements
+  fn search(mut self) -> Vec<Option<usize>> {
+    let mut hypothesis = Vec::with_capacity(self.measurements.len());
+    self.branch(&mut hypothesis, 0);
+    self.best
+  }
+
+  /// Expand the interpretation tree from the given partial hypothesis
+  fn branch(&mut self, hypothesis: &mut Vec<Option<usize>>, pairings: usize) {
+    let i = hypothesis.len();
+    if i == self.measurements.len() {
+      if pairings > self.best_pairings {
+        self.best = hypothesis.clone();
+        self.best_pairings = pairings;
+      }
+      return;
+    }
+    // bound: this branch cannot exceed the best hypothesis even if all remaining measurements are paired
+    if pairings + self.measurements.len() - i <= self.best_pairings {
+      return;
+    }
+
+    for j in self.candidates[i].clone() {
+      if hypothesis.contains(&Some(j)) {
+        continue;
+      }
+      hypothesis.push(Some(j));
+      if self.is_jointly_compatible(hypothesis) {
+        self.branch(hypothesis, pairings + 1);
+      }
+      hypothesis.pop();
+    }
+
+    // the measurement i is not associated with any landmark (spurious measurement or unmapped landmark)
+    hypothesis.push(None);
+    self.branch(hypothesis, pairings);
+    hypothesis.pop();
+  }
+
+  /// Determine whether all pairings of the hypothesis are jointly compatible
+  fn is_jointly_compatible(&self, hypothesis: &[Option<usize>]) -> bool {
+    let pairings = hypothesis.iter()
+                             .enumerate()
+                             .filter_map(|(i, j)| j.map(|j| (i, j)))
+                             .collect::<Vec<_>>();
+    let n = pairings.len();
+    let mut yhat = na::DVector::zeros(2 * n);
+    let mut h = na::DMatrix::zeros(2 * n, 3);
+    let mut s = na::DMatrix::zeros(2 * n, 2 * n);
+    for (k, (i, j)) in pairings.iter().enumerate() {
+      let point = self.landmarks[*j].point();
+      let hj = match camera::calc_h(&point, self.xhat) {
+        Some(hj) => hj,
+        None => return false,
+      };
+      yhat.fixed_rows_mut::<2>(2 * k).copy_from(&innovation(&self.measurements[*i], &point, self.xhat));
+      h.fixed_slice_mut::<2, 3>(2 * k, 0).copy_from(&hj);
+      s.fixed_slice_mut::<2, 2>(2 * k, 2 * k).copy_from(&self.noise.covariance(self.measurements[*i].distance));
+    }
+    let s = &h * self.p * h.transpose() + s;
+    match s.cholesky() {
+      Some(cholesky) => yhat.dot(&cholesky.solve(&yhat)) < utils::chi2_quantile(self.gate_probability, 2 * n),
+      None => false,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::NoiseConfig;
+
+  /// The measurements of the landmarks (1, 0) and (1, 0.4) from the actual pose which is 0.3 m to the right of the predicted pose at the origin
+  fn shifted_measurements() -> (LandmarkMap, Vec<Measurement>) {
+    let map = LandmarkMap::from_points(&[Point { x: 1.0, y: 0.0 }, Point { x: 1.0, y: 0.4 }]).unwrap();
+    let actual = na::Vector3::new(0.0, -0.3, 0.0);
+    let measurements = map.landmarks()
+                          .iter()
+                          .map(|landmark| {
+                            let z = camera::observe(&landmark.point(), &actual);
+                            Measurement { id: None, distance: z[0], angle: z[1], signature: None }
+                          })
+                          .collect();
+    (map, measurements)
+  }
+
+  #[test]
+  fn jcbb_resolves_the_association_which_nearest_neighbor_gets_wrong() {
+    let (map, measurements) = shifted_measurements();
+    let xhat = na::Vector3::zeros();
+    let p = na::Matrix3::from_diagonal(&na::Vector3::new(0.01, 0.25, 0.0001));
+    let noise = camera::ObservationNoise::new(&NoiseConfig::default());
+
+    // each measurement is individually nearest to the landmark (1, 0.4), so that the first one is associated wrongly
+    let config = AssociationConfig { method: AssociationMethod::NearestNeighbor, ..Default::default() };
+    assert_eq!(associate(&measurements, &map, &xhat, &p, &noise, &config), vec![Some(1), Some(1)]);
+
+    // only the actual pairings are jointly compatible, since they are explained by the same shift of the pose
+    let config = AssociationConfig { method: AssociationMethod::Jcbb, ..Default::default() };
+    assert_eq!(associate(&measurements, &map, &xhat, &p, &noise, &config), vec![Some(0), Some(1)]);
+  }
+}
@@ -0,0 +1,279 @@
+//! The `ekf_slam` module estimates a pose of a robot and the positions of the landmarks simultaneously by using EKF-SLAM
+//!
+//! The state is augmented with the position(x, y) of every landmark which has been observed, so that no landmark map is required.
+//! A landmark is initialized from the range and bearing of its first measurement, and the correlation between the pose and
+//! the landmarks is kept in the full covariance matrix.
+
+use std::collections::HashMap;
+
+extern crate nalgebra as na;
+
+use crate::config::Config;
+use crate::data::{Point, Measurement, EstimatedLandmark};
+use crate::filters::{Estimator, UpdateError};
+use crate::models::{robot, camera};
+use crate::utils;
+
+/// **\[private\]** The dimension of the pose (x, y, theta) at the head of the state
+const POSE: usize = 3;
+
+/// A struct to estimate a pose of a robot and the landmark map by using EKF-SLAM
+#[derive(Debug)]
+pub struct EkfSlam {
+  /// **\[private\]** the current estimated state(x, y, theta, x_1, y_1, ..., x_n, y_n)
+  xhat: na::DVector<f64>,
+  /// **\[private\]** the current covariance matrix of the state
+  p: na::DMatrix<f64>,
+  /// **\[private\]** the landmark IDs in the order of the state
+  ids: Vec<u64>,
+  /// **\[private\]** the indexes of the landmarks in the state by their IDs
+  indexes: HashMap<u64, usize>,
+  /// **\[private\]** the kalman gain of the pose at the latest "update step"
+  k: na::Matrix3x2<f64>,
+  /// **\[private\]** the normalized innovation squared of the measurements applied at the latest "update step"
+  nis: Vec<f64>,
+  /// **\[private\]** the noise model of the robot's motion which gives the covariance matrix of process noise for every input
+  motion: robot::MotionNoise,
+  /// **\[private\]** the noise model of the camera which gives the covariance matrix of observation noise for every measurement
+  noise: camera::ObservationNoise,
+  /// **\[private\]** the threshold of the normalized innovation squared to reject the outliers (no measurement is rejected when it is `None`)
+  gate: Option<f64>,
+}
+
+impl EkfSlam {
+  /// Create an EkfSlam instance which has no landmark
+  ///
+  /// ## Arguments
+  /// * `config` - the configuration which has the initial pose of the robot and the noise model assumed by this filter
+  ///
+  /// ## Returns
+  /// An instance of EkfSlam
+  pub fn new(config: &Config) -> EkfSlam {
+    let initial_pose = &config.scenario.initial_pose;
+    let filter = &config.filter;
+    let xhat = na::DVector::from_column_slice(&[initial_pose.x, initial_pose.y, initial_pose.theta]);
+    let p = na::DMatrix::zeros(POSE, POSE);
+    let k = na::Matrix3x2::zeros();
+    let motion = robot::MotionNoise::assumed(config);
+    let noise = camera::ObservationNoise::assumed(config);
+    let gate = filter.gate_probability.map(|probability| utils::chi2_quantile(probability, 2));
+
+    EkfSlam { xhat, p, ids: Vec::new(), indexes: HashMap::new(), k, nis: Vec::new(), motion, noise, gate }
+  }
+
+  /// **\[private\]** Get the current estimated pose(x, y, theta) at the head of the state
+  fn pose(&self) -> na::Vector3<f64> {
+    self.xhat.fixed_rows::<POSE>(0).into_owned()
+  }
+
+  /// **\[private\]** Get the estimated position of the landmark at the given index of the state
+  fn landmark(&self, idx: usize) -> Point {
+    Point { x: self.xhat[idx], y: self.xhat[idx + 1] }
+  }
+
+  /// **\[private\]** Augment the state with a newly observed landmark which is initialized from the range and bearing of the measurement
+  ///
+  /// ## Arguments
+  /// * `id` - the landmark ID
+  /// * `z` - the measured vector(distance, angle)
+  fn augment(&mut self, id: u64, z: &na::Vector2<f64>) {
+    let pose = self.pose();
+    let (distance, direction) = (z[0], pose[2] + z[1]);
+    let (sin, cos) = direction.sin_cos();
+    // the jacobians of the inverse observation equation with respect to the pose and the measurement
+    let gx = na::Matrix2x3::new(1.0, 0.0, -distance * sin,
+                                0.0, 1.0,  distance * cos);
+    let gz = na::Matrix2::new(cos, -distance * sin,
+                              sin,  distance * cos);
+
+    let n = self.xhat.len();
+    let p_xr = self.p.columns(0, POSE).into_owned();
+    let p_lx = gx * p_xr.transpose();
+    let p_ll = gx * self.p.fixed_slice::<POSE, POSE>(0, 0) * gx.transpose() + gz * self.noise.covariance(distance) * gz.transpose();
+
+    let mut xhat = self.xhat.clone().resize_vertically(n + 2, 0.0);
+    xhat[n] = pose[0] + distance * cos;
+    xhat[n + 1] = pose[1] + distance * sin;
+    let mut p = self.p.clone().resize(n + 2, n + 2, 0.0);
+    p.slice_mut((n, 0), (2, n)).copy_from(&p_lx);
+    p.slice_mut((0, n), (n, 2)).copy_from(&p_lx.transpose());
+    p.fixed_slice_mut::<2, 2>(n, n).copy_from(&p_ll);
+
+    self.xhat = xhat;
+    self.p = p;
+    self.indexes.insert(id, n);
+    self.ids.push(id);
+  }
+
+  /// **\[private\]** Calculate the "update step" by a measurement of the landmark which is already in the state
+  ///
+  /// ## Arguments
+  /// * `idx` - the index of the landmark in the state
+  /// * `z` - the measured vector(distance, angle)
+  ///
+  /// ## Errors
+  /// When the measurement is an outlier, the jacobian is not defined or the innovation covariance matrix is singular, this function returns [UpdateError]
+  fn correct(&mut self, idx: usize, z: &na::Vector2<f64>) -> Result<(), UpdateError> {
+    let pose = self.pose();
+    let landmark = self.landmark(idx);
+    let yhat = camera::innovation(z, &landmark, &pose);
+    let hx = camera::calc_h(&landmark, &pose).ok_or(UpdateError::SingularJacobian)?;
+    // the observation depends on the landmark position with the opposite sign of the robot position
+    let mut h = na::DMatrix::zeros(2, self.xhat.len());
+    h.fixed_slice_mut::<2, POSE>(0, 0).copy_from(&hx);
+    h.fixed_slice_mut::<2, 2>(0, idx).copy_from(&(-hx.fixed_columns::<2>(0)));
+
+    let ph_t = &self.p * h.transpose();
+    let s = &h * &ph_t + self.noise.covariance(z[0]);
+    let s_inv = s.try_inverse().ok_or(UpdateError::SingularInnovationCovariance)?;
+    let nis = (yhat.transpose() * s_inv * yhat)[0];
+    if self.gate.is_some_and(|gate| nis > gate) {
+      return Err(UpdateError::Outlier(nis));
+    }
+    let k = ph_t * s_inv;
+    self.xhat += &k * yhat;
+    let n = self.xhat.len();
+    self.p = (na::DMatrix::identity(n, n) - &k * h) * &self.p;
+    self.k = k.fixed_rows::<POSE>(0).into_owned();
+    self.nis.push(nis);
+    Ok(())
+  }
+}
+
+/// The implementation for Estimator trait
+impl Estimator for EkfSlam {
+
+  /// Calculate the "predict step" by the input of this tick
+  ///
+  /// Only the pose and its correlation with the landmarks are changed, because the landmarks do not move
+  fn predict(&mut self, input: &na::Vector2<f64>, delta: f64) {
+    let pose = self.pose();
+    let f = robot::calc_f(&pose, input, delta);
+    self.xhat.fixed_rows_mut::<POSE>(0).copy_from(&robot::ideal_move(&pose, input, delta));
+
+    let q = self.motion.covariance(&pose, input, delta);
+    let n = self.xhat.len();
+    let p_rr = f * self.p.fixed_slice::<POSE, POSE>(0, 0) * f.transpose() + q;
+    let p_rl = f * self.p.slice((0, POSE), (POSE, n - POSE));
+    self.p.fixed_slice_mut::<POSE, POSE>(0, 0).copy_from(&p_rr);
+    self.p.slice_mut((0, POSE), (POSE, n - POSE)).copy_from(&p_rl);
+    self.p.slice_mut((POSE, 0), (n - POSE, POSE)).copy_from(&p_rl.transpose());
+  }
+
+  /// Calculate the "update step" by applying the measurements one by one
+  ///
+  /// The landmark which is observed for the first time is added to the state instead of updating the state
+  fn update(&mut self, measurements: &[Measurement]) -> Vec<Result<(), UpdateError>> {
+    self.k = na::Matrix3x2::zeros();
+    self.nis.clear();
+    measurements.iter()
+                .map(|measurement| {
+                  let id = measurement.id.ok_or(UpdateError::UnknownLandmark)?;
+                  let z = na::Vector2::new(measurement.distance, measurement.angle);
+                  match self.indexes.get(&id) {
+                    Some(idx) => self.correct(*idx, &z),
+                    None => {
+                      self.augment(id, &z);
+                      Ok(())
+                    },
+                  }
+                })
+                .collect()
+  }
+
+  /// Get the current estimated pose(x, y, theta)
+  fn state(&self) -> na::Vector3<f64> {
+    self.pose()
+  }
+
+  /// Get the current covariance matrix of the estimated pose
+  fn covariance(&self) -> na::Matrix3<f64> {
+    self.p.fixed_slice::<POSE, POSE>(0, 0).into_owned()
+  }
+
+  /// Reset the estimated pose and its covariance matrix
+  ///
+  /// The landmarks are kept, but their correlation with the pose is cleared
+  fn reset(&mut self, state: &na::Vector3<f64>, covariance: &na::Matrix3<f64>) {
+    let n = self.xhat.len();
+    self.xhat.fixed_rows_mut::<POSE>(0).copy_from(state);
+    self.p.fixed_slice_mut::<POSE, POSE>(0, 0).copy_from(covariance);
+    self.p.slice_mut((0, POSE), (POSE, n - POSE)).fill(0.0);
+    self.p.slice_mut((POSE, 0), (n - POSE, POSE)).fill(0.0);
+    self.k = na::Matrix3x2::zeros();
+  }
+
+  /// Get the kalman gain of the pose at the latest "update step"
+  fn gain(&self) -> na::Matrix3x2<f64> {
+    self.k
+  }
+
+  /// Get the normalized innovation squared of the measurements applied at the latest "update step"
+  fn nis(&self) -> Vec<f64> {
+    self.nis.clone()
+  }
+
+  /// Get the estimated landmark map and the covariance matrix of every landmark
+  fn landmarks(&self) -> Vec<EstimatedLandmark> {
+    self.ids.iter()
+            .map(|id| {
+              let idx = self.indexes[id];
+              EstimatedLandmark {
+                id: *id,
+                x: self.xhat[idx],
+                y: self.xhat[idx + 1],
+                covariance: self.p.fixed_slice::<2, 2>(idx, idx).transpose().as_slice().to_vec(),
+              }
+            })
+            .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// The measurement of the landmark from the pose
+  fn measurement(id: Option<u64>, landmark: &Point, pose: &na::Vector3<f64>) -> Measurement {
+    let z = camera::observe(landmark, pose);
+    Measurement { id, distance: z[0], angle: utils::normalize_angle(z[1]), signature: None }
+  }
+
+  #[test]
+  fn first_observation_augments_the_state_with_the_landmark() {
+    let mut slam = EkfSlam::new(&Config::default());
+    let pose = slam.state();
+    let landmark = Point { x: pose[0], y: pose[1] + 2.0 };
+    assert!(slam.update(&[measurement(Some(7), &landmark, &pose)]).iter().all(|result| result.is_ok()));
+    assert!(slam.nis().is_empty());
+
+    // the robot faces the landmark, so that the angle noise spreads along x by the distance and the distance noise along y
+    let r = slam.noise.covariance(2.0);
+    let estimated = &slam.landmarks()[0];
+    assert_eq!(estimated.id, 7);
+    assert!((estimated.x - landmark.x).abs() < 1e-9 && (estimated.y - landmark.y).abs() < 1e-9);
+    let expected = [4.0 * r[(1, 1)], 0.0, 0.0, r[(0, 0)]];
+    assert!(estimated.covariance.iter().zip(expected.iter()).all(|(c, e)| (c - e).abs() < 1e-12));
+  }
+
+  #[test]
+  fn observation_of_the_known_landmark_corrects_the_pose() {
+    let mut slam = EkfSlam::new(&Config::default());
+    let start = slam.state();
+    let landmark = Point { x: start[0] + 1.0, y: start[1] + 2.0 };
+    slam.update(&[measurement(Some(0), &landmark, &start)]);
+
+    let input = na::Vector2::new(0.5, 0.0);
+    slam.predict(&input, 0.5);
+    let predicted = slam.covariance().trace();
+    let actual = robot::ideal_move(&start, &input, 0.5);
+    let results = slam.update(&[measurement(Some(0), &landmark, &actual), measurement(None, &landmark, &actual)]);
+
+    assert!(results[0].is_ok());
+    assert!(matches!(results[1], Err(UpdateError::UnknownLandmark)));
+    assert_eq!(slam.nis().len(), 1);
+    assert!(slam.covariance().trace() < predicted);
+    assert!((slam.state() - actual).norm() < 1e-9);
+    assert_eq!(slam.landmarks().len(), 1);
+  }
+}
@@ -0,0 +1,488 @@
+//! The `fast_slam` module estimates a pose of a robot and the positions of the landmarks simultaneously by using FastSLAM
+//!
+//! FastSLAM (Rao-Blackwellized particle filter) represents the distribution of the robot's path by the particles,
+//! and every particle has its own landmark map whose landmarks are estimated by independent 2x2 EKFs.
+//! * FastSLAM 1.0 samples the pose of every particle from the motion model, and weights the particle by the likelihood of the measurements
+//! * FastSLAM 2.0 samples the pose from the proposal distribution which is improved by the measurements of the known landmarks,
+//!   so that less particles are required when the observation is more accurate than the motion
+//!
+//! The cost of the "update step" grows only with the number of particles and the number of measurements, not with the size of the map.
+//!
+//! The poses of the particles are recorded at every tick to trace back the trajectory, which costs the memory of the number of particles times the number of ticks.
+//! The history is limited to the latest `max_history` ticks when it is given, e.g. in the real-time mode which runs forever.
+
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+
+extern crate nalgebra as na;
+extern crate rand;
+use rand::Rng;
+use rand::rngs::StdRng;
+use rand_distr::{StandardNormal, Distribution};
+
+use crate::config::{Config, FilterKind};
+use crate::data::{Point, Pose, Particle, Measurement, EstimatedLandmark};
+use crate::filters::{Estimator, UpdateError};
+use crate::filters::particle_filter::PF;
+use crate::models::{robot, camera};
+use crate::utils;
+
+/// The default value of the number of particles
+pub const PARTICLES: usize = 100;
+/// The default value of the ratio of the effective sample size to the number of particles, below which the particles are resampled
+pub const RESAMPLE_THRESHOLD: f64 = 0.5;
+/// The maximum number of ticks of the history in the real-time mode when it is not configured
+pub const REALTIME_MAX_HISTORY: usize = 1000;
+
+/// **\[private\]** A struct which stores the EKF of a landmark in a particle
+#[derive(Clone)]
+#[derive(Debug)]
+struct LandmarkEkf {
+  /// the estimated position(x, y)
+  mean: na::Vector2<f64>,
+  /// the covariance matrix of the estimated position
+  covariance: na::Matrix2<f64>,
+}
+
+impl LandmarkEkf {
+  /// Get the estimated position as [Point]
+  fn point(&self) -> Point {
+    Point { x: self.mean[0], y: self.mean[1] }
+  }
+}
+
+/// **\[private\]** A struct which defines a particle which has a pose and its own landmark map
+#[derive(Clone)]
+#[derive(Debug)]
+struct SlamParticle {
+  /// the pose(x, y, theta)
+  pose: na::Vector3<f64>,
+  /// the landmarks by their IDs
+  landmarks: BTreeMap<u64, LandmarkEkf>,
+}
+
+/// A struct to estimate a pose of a robot and the landmark map by using FastSLAM
+#[derive(Debug)]
+pub struct FastSlam {
+  /// **\[private\]** the particles
+  particles: Vec<SlamParticle>,
+  /// **\[private\]** the normalized weights of the particles
+  weights: Vec<f64>,
+  /// **\[private\]** the index of the particle which had the largest weight at the latest "update step", which is kept through the resampling
+  best: usize,
+  /// **\[private\]** the normalized innovation squared of the measurements applied at the latest "update step"
+  nis: Vec<f64>,
+  /// **\[private\]** the current estimated pose(x, y, theta) which is the weighted mean of the particles
+  xhat: na::Vector3<f64>,
+  /// **\[private\]** the current covariance matrix of the poses of the particles
+  p: na::Matrix3<f64>,
+  /// **\[private\]** the noise model of the robot's motion which gives the covariance matrix of process noise for every input
+  motion: robot::MotionNoise,
+  /// **\[private\]** the covariance matrix of process noise at the latest "predict step"
+  q: na::Matrix3<f64>,
+  /// **\[private\]** the noise model of the camera which gives the covariance matrix of observation noise for every measurement
+  noise: camera::ObservationNoise,
+  /// **\[private\]** whether the pose is sampled from the proposal distribution improved by the measurements (FastSLAM 2.0)
+  improved_proposal: bool,
+  /// **\[private\]** whether the predicted poses have not been sampled yet (FastSLAM 2.0 samples them in the "update step")
+  pending: bool,
+  /// **\[private\]** the ratio of the effective sample size to the number of particles, below which the particles are resampled
+  resample_threshold: f64,
+  /// **\[private\]** the threshold of the normalized innovation squared to reject the outliers (no measurement is rejected when it is `None`)
+  gate: Option<f64>,
+  /// **\[private\]** the poses of the particles at every tick with the indexes of their ancestors at the previous tick
+  history: Vec<Vec<(na::Vector3<f64>, usize)>>,
+  /// **\[private\]** the maximum number of ticks of the history (the whole history is kept when it is `None`)
+  max_history: Option<usize>,
+  /// **\[private\]** the indexes of the ancestors of the particles at the latest tick of the history
+  ancestors: Vec<usize>,
+  /// **\[private\]** the random number generator which samples the particles
+  rng: StdRng,
+}
+
+impl FastSlam {
+  /// Create a FastSlam instance whose particles have no landmark
+  ///
+  /// ## Arguments
+  /// * `rng` - the random number generator which samples the particles
+  /// * `config` - the configuration which has the initial pose of the robot, the noise model assumed by this filter,
+  ///   the version of FastSLAM and the parameters of the particles
+  ///
+  /// ## Returns
+  /// An instance of FastSlam
+  pub fn new(rng: StdRng, config: &Config) -> FastSlam {
+    let initial_pose = &config.scenario.initial_pose;
+    let filter = &config.filter;
+    let n = filter.fastslam.particles.max(1);
+    let initial = na::Vector3::new(initial_pose.x, initial_pose.y, initial_pose.theta);
+    let particles = vec![SlamParticle { pose: initial, landmarks: BTreeMap::new() }; n];
+    let weights = vec![1.0 / n as f64; n];
+    let motion = robot::MotionNoise::assumed(config);
+    let noise = camera::ObservationNoise::assumed(config);
+    let gate = filter.gate_probability.map(|probability| utils::chi2_quantile(probability, 2));
+
+    FastSlam {
+      particles, weights,
+      best: 0,
+      nis: Vec::new(),
+      xhat: initial,
+      p: na::Matrix3::zeros(),
+      motion,
+      q: na::Matrix3::zeros(),
+      noise,
+      improved_proposal: filter.kind == FilterKind::FastSlam2,
+      pending: false,
+      resample_threshold: filter.fastslam.resample_threshold,
+      gate,
+      history: vec![(0..n).map(|i| (initial, i)).collect()],
+      max_history: filter.fastslam.max_history.map(|max_history| max_history.max(1)),
+      ancestors: (0..n).collect(),
+      rng,
+    }
+  }
+
+  /// **\[private\]** Get the index of the particle which has the largest weight
+  ///
+  /// The weights are uniform after the resampling, so that the best particle is recorded by [FastSlam::resample] instead
+  fn heaviest(&self) -> usize {
+    self.weights.iter()
+                .enumerate()
+                .fold((0, f64::NEG_INFINITY), |(best, max), (i, w)| if *w > max { (i, *w) } else { (best, max) })
+                .0
+  }
+
+  /// **\[private\]** Calculate the weighted mean and covariance matrix of the poses of the particles
+  fn estimate(&mut self) {
+    let poses = self.particles.iter().map(|particle| particle.pose).collect::<Vec<_>>();
+    let (xhat, p) = PF::estimate(&poses, &self.weights, self.xhat[2]);
+    self.xhat = xhat;
+    self.p = p;
+  }
+
+  /// **\[private\]** Calculate the normalized innovation squared of the measurement against the landmark estimated by a particle
+  ///
+  /// The innovation is evaluated at the weighted mean of the particles, and its covariance includes the spread of the particles
+  ///
+  /// ## Returns
+  /// The normalized innovation squared, or `None` when it cannot be calculated
+  fn calc_nis(&self, landmark: &LandmarkEkf, z: &na::Vector2<f64>) -> Option<f64> {
+    let point = landmark.point();
+    let hx = camera::calc_h(&point, &self.xhat)?;
+    let hl = -hx.fixed_columns::<2>(0);
+    let s = hx * self.p * hx.transpose() + hl * landmark.covariance * hl.transpose() + self.noise.covariance(z[0]);
+    let yhat = camera::innovation(z, &point, &self.xhat);
+    Some((yhat.transpose() * s.try_inverse()? * yhat)[0])
+  }
+
+  /// **\[private\]** Sample a vector from the gaussian distribution
+  ///
+  /// The covariance matrix which cannot be decomposed is approximated by its diagonal
+  fn sample(mean: &na::Vector3<f64>, covariance: &na::Matrix3<f64>, rng: &mut StdRng) -> na::Vector3<f64> {
+    let l = na::Cholesky::new(*covariance)
+                         .map(|cholesky| cholesky.l())
+                         .unwrap_or_else(|| na::Matrix3::from_diagonal(&covariance.diagonal().map(|v| v.abs().sqrt())));
+    mean + l * na::Vector3::from_fn(|_, _| StandardNormal.sample(rng))
+  }
+
+  /// **\[private\]** Calculate the logarithm of the probability density of the innovation
+  fn log_likelihood(yhat: &na::Vector2<f64>, s: &na::Matrix2<f64>, s_inv: &na::Matrix2<f64>) -> f64 {
+    -0.5 * (yhat.transpose() * s_inv * yhat)[0] - 0.5 * (2.0 * PI * s).determinant().ln()
+  }
+
+  /// **\[private\]** Apply the measurements to a particle
+  ///
+  /// The pose is sampled from the proposal distribution before the landmarks are updated when `q` is given (FastSLAM 2.0).
+  /// The landmark which is observed for the first time is initialized from the range and bearing of the measurement
+  ///
+  /// ## Arguments
+  /// * `particle` - the particle whose pose and landmarks are updated
+  /// * `observations` - the tuples of (the landmark ID, the measured vector(distance, angle))
+  /// * `noise` - the noise model of the camera which gives the covariance matrix of observation noise for every measurement
+  /// * `q` - the covariance matrix of process noise for FastSLAM 2.0, or `None` for FastSLAM 1.0
+  /// * `rng` - the random number generator
+  ///
+  /// ## Returns
+  /// The logarithm of the importance weight of the particle
+  fn observe(particle: &mut SlamParticle, observations: &[(u64, na::Vector2<f64>)], noise: &camera::ObservationNoise, q: Option<&na::Matrix3<f64>>,
+             rng: &mut StdRng) -> f64 {
+    let mut log_weight = 0.0;
+
+    if let Some(q) = q {
+      // the gaussian proposal distribution of the pose is refined by the measurements one by one in the same way as the EKF
+      let (mut mean, mut covariance) = (particle.pose, *q);
+      for (id, z) in observations.iter() {
+        let landmark = match particle.landmarks.get(id) {
+          Some(landmark) => landmark,
+          None => continue,
+        };
+        let point = landmark.point();
+        let hx = match camera::calc_h(&point, &mean) {
+          Some(hx) => hx,
+          None => continue,
+        };
+        let hl = -hx.fixed_columns::<2>(0);
+        let s = hx * covariance * hx.transpose() + hl * landmark.covariance * hl.transpose() + noise.covariance(z[0]);
+        let s_inv = match s.try_inverse() {
+          Some(s_inv) => s_inv,
+          None => continue,
+        };
+        let yhat = camera::innovation(z, &point, &mean);
+        log_weight += FastSlam::log_likelihood(&yhat, &s, &s_inv);
+        let k = covariance * hx.transpose() * s_inv;
+        mean += k * yhat;
+        covariance = (na::Matrix3::identity() - k * hx) * covariance;
+      }
+      particle.pose = FastSlam::sample(&mean, &covariance, rng);
+    }
+
+    let pose = particle.pose;
+    for (id, z) in observations.iter() {
+      match particle.landmarks.get_mut(id) {
+        Some(landmark) => {
+          let point = landmark.point();
+          let hl = match camera::calc_h(&point, &pose) {
+            Some(hx) => -hx.fixed_columns::<2>(0),
+            None => continue,
+          };
+          let s = hl * landmark.covariance * hl.transpose() + noise.covariance(z[0]);
+          let s_inv = match s.try_inverse() {
+            Some(s_inv) => s_inv,
+            None => continue,
+          };
+          let yhat = camera::innovation(z, &point, &pose);
+          if q.is_none() {
+            log_weight += FastSlam::log_likelihood(&yhat, &s, &s_inv);
+          }
+          let k = landmark.covariance * hl.transpose() * s_inv;
+          landmark.mean += k * yhat;
+          landmark.covariance = (na::Matrix2::identity() - k * hl) * landmark.covariance;
+        },
+        None => {
+          let (distance, direction) = (z[0], pose[2] + z[1]);
+          let (sin, cos) = direction.sin_cos();
+          // the jacobian of the inverse observation equation with respect to the measurement
+          let gz = na::Matrix2::new(cos, -distance * sin,
+                                    sin,  distance * cos);
+          particle.landmarks.insert(*id, LandmarkEkf {
+            mean: na::Vector2::new(pose[0] + distance * cos, pose[1] + distance * sin),
+            covariance: gz * noise.covariance(distance) * gz.transpose(),
+          });
+        },
+      }
+    }
+    log_weight
+  }
+
+  /// **\[private\]** Resample the particles by the systematic (low variance) resampling
+  ///
+  /// The best particle is moved to the first copy of the particle which had the largest weight
+  fn resample(&mut self) {
+    let n = self.particles.len();
+    let step = 1.0 / n as f64;
+    let mut u = self.rng.gen_range(0.0..step);
+    let mut cumulative = self.weights[0];
+    let mut i = 0;
+    let mut resampled = Vec::with_capacity(n);
+    let mut ancestors = Vec::with_capacity(n);
+    let mut best = None;
+    for _ in 0..n {
+      while u > cumulative && i < n - 1 {
+        i += 1;
+        cumulative += self.weights[i];
+      }
+      if i == self.best && best.is_none() {
+        best = Some(resampled.len());
+      }
+      resampled.push(self.particles[i].clone());
+      ancestors.push(self.ancestors[i]);
+      u += step;
+    }
+    self.particles = resampled;
+    self.ancestors = ancestors;
+    self.weights = vec![step; n];
+    // the largest weight is never less than 1/n, so that the best particle always survives the systematic resampling
+    self.best = best.unwrap_or(0);
+  }
+
+  /// **\[private\]** Sample the predicted poses from the motion model when they have not been sampled by the "update step"
+  fn sample_pending(&mut self) {
+    if self.pending {
+      for particle in self.particles.iter_mut() {
+        particle.pose = FastSlam::sample(&particle.pose, &self.q, &mut self.rng);
+      }
+      self.pending = false;
+    }
+  }
+}
+
+/// The implementation for Estimator trait
+impl Estimator for FastSlam {
+
+  /// Calculate the "predict step" by moving every particle
+  ///
+  /// FastSLAM 1.0 samples the pose from the motion model, while FastSLAM 2.0 samples it in the "update step"
+  fn predict(&mut self, input: &na::Vector2<f64>, delta: f64) {
+    self.sample_pending();
+    self.q = self.motion.covariance(&self.xhat, input, delta);
+    for particle in self.particles.iter_mut() {
+      particle.pose = if self.improved_proposal {
+        robot::ideal_move(&particle.pose, input, delta)
+      } else {
+        self.motion.sample(&particle.pose, input, delta, &mut self.rng)
+      };
+    }
+    self.pending = self.improved_proposal;
+    self.estimate();
+  }
+
+  /// Calculate the "update step" by updating the landmarks of every particle and weighting the particles, and resample the particles
+  ///
+  /// The measurements whose normalized innovation squared against the landmark of the best particle exceeds the gate are rejected as outliers
+  fn update(&mut self, measurements: &[Measurement]) -> Vec<Result<(), UpdateError>> {
+    let best = &self.particles[self.best];
+    let mut results = Vec::with_capacity(measurements.len());
+    let mut observations = Vec::new();
+    let mut applied_nis = Vec::new();
+    for measurement in measurements.iter() {
+      let id = match measurement.id {
+        Some(id) => id,
+        None => {
+          results.push(Err(UpdateError::UnknownLandmark));
+          continue;
+        },
+      };
+      let z = na::Vector2::new(measurement.distance, measurement.angle);
+      match best.landmarks.get(&id).and_then(|landmark| self.calc_nis(landmark, &z)) {
+        Some(nis) if self.gate.is_some_and(|gate| nis > gate) => results.push(Err(UpdateError::Outlier(nis))),
+        nis => {
+          results.push(Ok(()));
+          observations.push((id, z));
+          applied_nis.extend(nis);
+        },
+      }
+    }
+    self.nis = applied_nis;
+
+    let (noise, q, rng) = (&self.noise, if self.pending { Some(&self.q) } else { None }, &mut self.rng);
+    let log_weights = self.particles.iter_mut()
+                                    .map(|particle| FastSlam::observe(particle, &observations, noise, q, rng))
+                                    .collect::<Vec<_>>();
+    self.pending = false;
+
+    let max = log_weights.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let weights = self.weights.iter().zip(log_weights.iter()).map(|(w, l)| w * (l - max).exp()).collect::<Vec<_>>();
+    let total = weights.iter().sum::<f64>();
+    if max.is_finite() && total.is_finite() && total > 0.0 {
+      self.weights = weights.iter().map(|w| w / total).collect();
+    } else {
+      let n = self.particles.len();
+      self.weights = vec![1.0 / n as f64; n];
+      results = results.into_iter().map(|result| result.and(Err(UpdateError::DegenerateWeights))).collect();
+    }
+    self.best = self.heaviest();
+
+    let ess = 1.0 / self.weights.iter().map(|w| w * w).sum::<f64>();
+    if ess < self.resample_threshold * self.particles.len() as f64 {
+      self.resample();
+    }
+    self.history.push(self.particles.iter().zip(self.ancestors.iter()).map(|(particle, a)| (particle.pose, *a)).collect());
+    if let Some(max_history) = self.max_history {
+      let excess = self.history.len().saturating_sub(max_history);
+      self.history.drain(..excess);
+    }
+    self.ancestors = (0..self.particles.len()).collect();
+    self.estimate();
+    results
+  }
+
+  /// Get the current estimated pose(x, y, theta) which is the weighted mean of the particles
+  fn state(&self) -> na::Vector3<f64> {
+    self.xhat
+  }
+
+  /// Get the current covariance matrix of the poses of the particles
+  fn covariance(&self) -> na::Matrix3<f64> {
+    self.p
+  }
+
+  /// Scatter the poses of the particles around the given pose according to the given covariance matrix
+  ///
+  /// The landmark maps of the particles are kept
+  fn reset(&mut self, state: &na::Vector3<f64>, covariance: &na::Matrix3<f64>) {
+    self.pending = false;
+    let n = self.particles.len();
+    for particle in self.particles.iter_mut() {
+      particle.pose = FastSlam::sample(state, covariance, &mut self.rng);
+    }
+    self.weights = vec![1.0 / n as f64; n];
+    self.xhat = *state;
+    self.estimate();
+  }
+
+  /// Get the normalized innovation squared of the measurements applied at the latest "update step"
+  fn nis(&self) -> Vec<f64> {
+    self.nis.clone()
+  }
+
+  /// Get the particles which represent the distribution of the estimated pose
+  fn particles(&self) -> Vec<Particle> {
+    self.particles.iter()
+                  .zip(self.weights.iter())
+                  .map(|(particle, w)| Particle { x: particle.pose[0], y: particle.pose[1], theta: particle.pose[2], weight: *w })
+                  .collect()
+  }
+
+  /// Get the landmark map of the particle which has the largest weight
+  fn landmarks(&self) -> Vec<EstimatedLandmark> {
+    self.particles[self.best].landmarks
+                               .iter()
+                               .map(|(id, landmark)| EstimatedLandmark {
+                                 id: *id,
+                                 x: landmark.mean[0],
+                                 y: landmark.mean[1],
+                                 covariance: landmark.covariance.transpose().as_slice().to_vec(),
+                               })
+                               .collect()
+  }
+
+  /// Get the path of the particle which has the largest weight, traced back through its ancestors
+  ///
+  /// Only the latest `max_history` poses are returned when the history is limited
+  fn trajectory(&self) -> Vec<Pose> {
+    let mut idx = self.best;
+    let mut trajectory = self.history.iter()
+                                     .rev()
+                                     .map(|layer| {
+                                       let (pose, ancestor) = layer[idx];
+                                       idx = ancestor;
+                                       Pose::from_vector3(&pose)
+                                     })
+                                     .collect::<Vec<_>>();
+    trajectory.reverse();
+    trajectory
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rand::SeedableRng;
+
+  #[test]
+  fn landmarks_and_trajectory_come_from_the_highest_weight_particle_after_resampling() {
+    let mut config = Config::default();
+    config.filter.fastslam.particles = 4;
+    let mut slam = FastSlam::new(StdRng::seed_from_u64(0), &config);
+    for (i, particle) in slam.particles.iter_mut().enumerate() {
+      particle.pose[0] = i as f64;
+      particle.landmarks.insert(0, LandmarkEkf { mean: na::Vector2::new(i as f64, 0.0), covariance: na::Matrix2::identity() });
+    }
+    // the first particle always survives the resampling at the head, while the second one has the largest weight
+    slam.weights = vec![0.3, 0.7, 0.0, 0.0];
+    slam.update(&[]);
+
+    assert!(slam.weights.iter().all(|w| (w - 0.25).abs() < 1e-12));
+    assert_eq!(slam.landmarks()[0].x, 1.0);
+    assert_eq!(slam.trajectory().last().unwrap().x, 1.0);
+  }
+}
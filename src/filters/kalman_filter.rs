@@ -1,91 +1,61 @@
-//! The `kalman_filters` module estimates a pose of a simulated robot in its state-space model by using Kalman Filter
-
-use std::time::Instant;
+//! The `kalman_filters` module estimates a pose of a robot in its state-space model by using Kalman Filter
 
 extern crate nalgebra as na;
 
-use crate::agent::Agent;
-use crate::data::{Pose, Observed};
-use crate::planners::dwa_wo_obstacle;
+use crate::config::Config;
+use crate::data::{Point, Measurement};
+use crate::filters::{Estimator, UpdateError};
+use crate::map::LandmarkMap;
 use crate::models::{robot, camera};
+use crate::utils;
+
+/// The default value of the probability of the chi-square distribution which is used as the gate of the normalized innovation squared
+pub const GATE_PROBABILITY: f64 = 0.999;
 
-/// **\[private\]** The variance of process noise (assuming that each random variable in the state model is independent)
-const Q: f64 = 0.01;
-/// **\[private\]** The variance of observation noise (assuming that each random variable in the observation model is independent)
-const R: f64 = 0.02;
+/// **\[private\]** Tuple of (estimated pose(x, y, theta), covariance matrix, kalman gain, normalized innovation squared) calculated by the "update step"
+type Updated = (na::Vector3<f64>, na::Matrix3<f64>, na::Matrix3x2<f64>, f64);
 
-/// A struct to estimate a pose of a simulated robot by using Extended Kalman Filter
+/// A struct to estimate a pose of a robot by using Extended Kalman Filter
 #[derive(Debug)]
 pub struct EKF {
-  /// an agent instance of a robot to be estimated
-  pub agent: Box<dyn Agent>,
+  /// **\[private\]** the landmark map which is used to estimate the pose
+  map: LandmarkMap,
   /// **\[private\]** the current estimated pose(x, y, theta)
   xhat: na::Vector3<f64>,
   /// **\[private\]** the current covariance matrix
   p: na::Matrix3<f64>,
-  /// **\[private\]** the covariance matrix of process noise
-  q: na::Matrix3<f64>,
-  /// **\[private\]** the covariance matrix of observation noise
-  r: na::Matrix2<f64>,
-  /// **\[private\]** the current input vector(linear velocity, angular velocity)
-  input: na::Vector2<f64>,
-  /// **\[private\]** the time started simulating
-  start_t: Instant,
-  /// **\[private\]** the time started current tick
-  t: Instant,
+  /// **\[private\]** the kalman gain of the latest "update step"
+  k: na::Matrix3x2<f64>,
+  /// **\[private\]** the normalized innovation squared of the measurements applied at the latest "update step"
+  nis: Vec<f64>,
+  /// **\[private\]** the noise model of the robot's motion which gives the covariance matrix of process noise for every input
+  motion: robot::MotionNoise,
+  /// **\[private\]** the noise model of the camera which gives the covariance matrix of observation noise for every measurement
+  noise: camera::ObservationNoise,
+  /// **\[private\]** the threshold of the normalized innovation squared to reject the outliers (no measurement is rejected when it is `None`)
+  gate: Option<f64>,
 }
 
 impl EKF {
   /// Create an EKF instance
   ///
   /// ## Arguments
-  /// * `agent` - an agent instance of a robot to be estimated
-  /// * `initial_pose` - the initial pose for the robot
+  /// * `map` - the landmark map which is used to estimate the pose
+  /// * `config` - the configuration which has the initial pose of the robot and the noise model assumed by this filter
   ///
   /// ## Returns
   /// An instance of EKF
-  pub fn new(agent: Box<dyn Agent>, initial_pose: Pose) -> EKF {
+  pub fn new(map: LandmarkMap, config: &Config) -> EKF {
+    let initial_pose = &config.scenario.initial_pose;
+    let filter = &config.filter;
     let xhat = na::Vector3::new(initial_pose.x, initial_pose.y, initial_pose.theta);
     let p = na::Matrix3::zeros();
-    let q = Q * na::Matrix3::identity();
-    let r = R * na::Matrix2::identity();
-    let input = na::Vector2::new(0.0, 0.0);
-    let start_t = Instant::now();
-    let t = start_t.clone();
+    let k = na::Matrix3x2::zeros();
+    let motion = robot::MotionNoise::assumed(config);
+    let noise = camera::ObservationNoise::assumed(config);
+    let gate = filter.gate_probability.map(|probability| utils::chi2_quantile(probability, 2));
 
-    EKF { agent, xhat, p, q, r, input, start_t, t }
-  }
-
-  /// Estimate a pose of the robot at this tick
-  ///
-  /// ## Returns
-  /// Tuple of (the ideal pose of the robot, the estimated pose of the robot, the covariance matrix, the kalman gain)
-  pub fn step(&mut self) -> (Pose, Pose, Vec<f64>, Vec<f64>) {
-    let t = Instant::now();
-    let delta = (t - self.t).as_secs_f64();
-
-    let ideal = self.agent.get_ideal(&self.xhat, self.start_t.elapsed().as_secs_f64());
-    let input = dwa_wo_obstacle::get_input(&self.agent, &self.xhat, &ideal, &self.input, delta);
-    self.agent.noisy_move(&self.xhat, &input, delta);
-    let (mut xhat, mut p) = EKF::predict(&self.xhat, &self.p, &self.q, &input, delta);
-    let mut k: na::Matrix3x2<f64> = na::Matrix3x2::zeros();
-    for observed in self.agent.noisy_observe() {
-      let (updated_xhat, updated_p, updated_k) = EKF::update(&self.r, &xhat, &p, observed);
-      xhat = updated_xhat;
-      p = updated_p;
-      k = updated_k;
-    }
-
-    self.xhat = xhat;
-    self.p = p;
-    self.t = t;
-
-    (
-      Pose::from_vector3(&ideal),
-      Pose::from_vector3(&xhat),
-      p.transpose().as_slice().to_vec(),
-      k.transpose().as_slice().to_vec(),
-    )
+    EKF { map, xhat, p, k, nis: Vec::new(), motion, noise, gate }
   }
 
   /// **\[private\]** Calculate the "predict step"
@@ -109,22 +79,135 @@ impl EKF {
 
   /// **\[private\]** Calculate the "update step"
   ///
+  /// When the normalized innovation squared (the squared Mahalanobis distance of the innovation) exceeds the gate, the measurement is rejected as an outlier
+  ///
   /// ## Arguments
-  /// * `r` - the covariance matrix of observation noise
+  /// * `r` - the covariance matrix of observation noise of the measurement
   /// * `a_priori_x` - the predicted pose(x, y, theta)
   /// * `a_priori_p` - the predicted covariance matrix
-  /// * `observed` - observed landmark
+  /// * `landmark` - the position of the observed landmark in the map
+  /// * `measurement` - the measurement associated with the landmark
+  /// * `gate` - the threshold of the normalized innovation squared
   ///
   /// ## Returns
-  /// * Tuple of (estimated pose(x, y, theta), covariance matrix, kalman gain)
-  fn update(r: &na::Matrix2<f64>, a_priori_x: &na::Vector3<f64>, a_priori_p: &na::Matrix3<f64>, observed: &Observed)
-    -> (na::Vector3<f64>, na::Matrix3<f64>, na::Matrix3x2<f64>) {
-    let yhat = na::Vector2::new(observed.distance, observed.angle) - camera::observe(&observed.landmark, a_priori_x);
-    let h = camera::calc_h(&observed.landmark, a_priori_x);
+  /// * Tuple of (estimated pose(x, y, theta), covariance matrix, kalman gain, normalized innovation squared)
+  ///
+  /// ## Errors
+  /// When the measurement is an outlier, the jacobian is not defined or the innovation covariance matrix is singular, this function returns [UpdateError]
+  fn update(r: &na::Matrix2<f64>, a_priori_x: &na::Vector3<f64>, a_priori_p: &na::Matrix3<f64>, landmark: &Point, measurement: &Measurement,
+            gate: Option<f64>) -> Result<Updated, UpdateError> {
+    let yhat = camera::innovation(&na::Vector2::new(measurement.distance, measurement.angle), landmark, a_priori_x);
+    let h = camera::calc_h(landmark, a_priori_x).ok_or(UpdateError::SingularJacobian)?;
     let s = h * a_priori_p * h.transpose() + r;
-    let k = a_priori_p * h.transpose() * s.try_inverse().unwrap();
+    let s_inv = s.try_inverse().ok_or(UpdateError::SingularInnovationCovariance)?;
+    let nis = (yhat.transpose() * s_inv * yhat)[0];
+    if gate.is_some_and(|gate| nis > gate) {
+      return Err(UpdateError::Outlier(nis));
+    }
+    let k = a_priori_p * h.transpose() * s_inv;
     let xhat = a_priori_x + k * yhat;
     let p = (na::Matrix3::identity() - k * h) * a_priori_p;
-    (xhat, p, k)
+    Ok((xhat, p, k, nis))
+  }
+}
+
+/// The implementation for Estimator trait
+impl Estimator for EKF {
+
+  /// Calculate the "predict step" by the input of this tick
+  fn predict(&mut self, input: &na::Vector2<f64>, delta: f64) {
+    let q = self.motion.covariance(&self.xhat, input, delta);
+    let (xhat, p) = EKF::predict(&self.xhat, &self.p, &q, input, delta);
+    self.xhat = xhat;
+    self.p = p;
+  }
+
+  /// Calculate the "update step" by applying the measurements one by one
+  ///
+  /// The measurements which are rejected as outliers or cannot be applied because of the numerical failure are skipped
+  fn update(&mut self, measurements: &[Measurement]) -> Vec<Result<(), UpdateError>> {
+    self.k = na::Matrix3x2::zeros();
+    self.nis.clear();
+    measurements.iter()
+                .map(|measurement| {
+                  let landmark = match measurement.id.and_then(|id| self.map.get(id)) {
+                    Some(landmark) => landmark.point(),
+                    None => return Err(UpdateError::UnknownLandmark),
+                  };
+                  let (xhat, p, k, nis) = EKF::update(&self.noise.covariance(measurement.distance), &self.xhat, &self.p, &landmark, measurement, self.gate)?;
+                  self.xhat = xhat;
+                  self.p = p;
+                  self.k = k;
+                  self.nis.push(nis);
+                  Ok(())
+                })
+                .collect()
+  }
+
+  /// Get the current estimated pose(x, y, theta)
+  fn state(&self) -> na::Vector3<f64> {
+    self.xhat
+  }
+
+  /// Get the current covariance matrix of the estimated pose
+  fn covariance(&self) -> na::Matrix3<f64> {
+    self.p
+  }
+
+  /// Reset the estimated pose and its covariance matrix
+  fn reset(&mut self, state: &na::Vector3<f64>, covariance: &na::Matrix3<f64>) {
+    self.xhat = *state;
+    self.p = *covariance;
+    self.k = na::Matrix3x2::zeros();
+  }
+
+  /// Get the kalman gain of the latest "update step"
+  fn gain(&self) -> na::Matrix3x2<f64> {
+    self.k
+  }
+
+  /// Get the normalized innovation squared of the measurements applied at the latest "update step"
+  fn nis(&self) -> Vec<f64> {
+    self.nis.clone()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// The measurement of the landmark at (1, 0) from the origin whose angle is off by the given error
+  fn measurement(angle_error: f64) -> Measurement {
+    Measurement { id: Some(0), distance: 1.0, angle: angle_error, signature: None }
+  }
+
+  #[test]
+  fn update_gates_the_normalized_innovation_squared() {
+    let landmark = Point { x: 1.0, y: 0.0 };
+    let (x, p, r) = (na::Vector3::zeros(), 0.01 * na::Matrix3::identity(), 0.0004 * na::Matrix2::identity());
+    let gate = Some(utils::chi2_quantile(0.999, 2));
+
+    // the innovation covariance of the angle is 0.01 * (1 + 1) + 0.0004 = 0.0204, so that the gate 13.8155 is between the angle errors 0.5 and 0.6
+    let (_, _, _, nis) = EKF::update(&r, &x, &p, &landmark, &measurement(0.5), gate).unwrap();
+    assert!((nis - 0.25 / 0.0204).abs() < 1e-9);
+    match EKF::update(&r, &x, &p, &landmark, &measurement(0.6), gate) {
+      Err(UpdateError::Outlier(nis)) => assert!((nis - 0.36 / 0.0204).abs() < 1e-9),
+      other => panic!("expected an outlier, but got {:?}", other),
+    }
+    assert!(EKF::update(&r, &x, &p, &landmark, &measurement(0.6), None).is_ok());
+  }
+
+  #[test]
+  fn update_returns_the_numerical_failures_as_errors() {
+    let landmark = Point { x: 1.0, y: 0.0 };
+    let r = 0.0004 * na::Matrix2::identity();
+
+    // the jacobian is not defined when the estimated pose sits on the landmark
+    let on_landmark = na::Vector3::new(1.0, 0.0, 0.0);
+    assert_eq!(EKF::update(&r, &on_landmark, &na::Matrix3::identity(), &landmark, &measurement(0.0), None).unwrap_err(),
+               UpdateError::SingularJacobian);
+    // the innovation covariance is singular when neither the pose nor the measurement is uncertain
+    assert_eq!(EKF::update(&na::Matrix2::zeros(), &na::Vector3::zeros(), &na::Matrix3::zeros(), &landmark, &measurement(0.0), None).unwrap_err(),
+               UpdateError::SingularInnovationCovariance);
   }
 }
@@ -0,0 +1,404 @@
+//! The `particle_filter` module estimates a pose of a robot by using Particle Filter (Monte Carlo Localization)
+//!
+//! Each particle is moved by [robot::ideal_move] with the process noise, weighted by the likelihood of the measurements calculated by [camera::observe],
+//! and resampled by the systematic (low variance) resampling.
+//! The particles can be initialized over the whole map for the global localization, and random particles are injected
+//! according to the short-term and long-term averages of the likelihood (Augmented MCL), so that the filter recovers from the kidnapped robot problem.
+//! The random particles are sampled around the landmarks, so that the filter requires a non-empty landmark map
+
+use std::f64::consts::PI;
+
+extern crate nalgebra as na;
+extern crate rand;
+use rand::Rng;
+use rand::rngs::StdRng;
+use rand_distr::{StandardNormal, Distribution};
+
+use crate::config::{Config, PfConfig};
+use crate::data::{Point, Particle, Measurement};
+use crate::filters::{Estimator, UpdateError};
+use crate::map::LandmarkMap;
+use crate::models::{robot, camera};
+use crate::utils;
+
+/// The default value of the number of particles
+pub const PARTICLES: usize = 500;
+/// The default value of the ratio of the effective sample size to the number of particles, below which the particles are resampled
+pub const RESAMPLE_THRESHOLD: f64 = 0.5;
+/// The default value of the decay rate of the long-term average of the likelihood
+pub const ALPHA_SLOW: f64 = 0.001;
+/// The default value of the decay rate of the short-term average of the likelihood
+pub const ALPHA_FAST: f64 = 0.1;
+/// The default value of the margin (m) around the landmarks where the random particles are sampled
+pub const MARGIN: f64 = 1.0;
+
+/// A struct to estimate a pose of a robot by using Particle Filter
+#[derive(Debug)]
+pub struct PF {
+  /// **\[private\]** the landmark map which is used to estimate the pose
+  map: LandmarkMap,
+  /// **\[private\]** the current estimated pose(x, y, theta) which is the weighted mean of the particles
+  xhat: na::Vector3<f64>,
+  /// **\[private\]** the current covariance matrix of the particles
+  p: na::Matrix3<f64>,
+  /// **\[private\]** the particles(x, y, theta)
+  particles: Vec<na::Vector3<f64>>,
+  /// **\[private\]** the normalized weights of the particles
+  weights: Vec<f64>,
+  /// **\[private\]** the normalized innovation squared of the measurements applied at the latest "update step"
+  nis: Vec<f64>,
+  /// **\[private\]** the noise model of the robot's motion which gives the covariance matrix of process noise for every input
+  motion: robot::MotionNoise,
+  /// **\[private\]** the noise model of the camera which gives the covariance matrix of observation noise for every measurement
+  noise: camera::ObservationNoise,
+  /// **\[private\]** the parameters of the particles
+  config: PfConfig,
+  /// **\[private\]** the long-term average of the likelihood
+  w_slow: f64,
+  /// **\[private\]** the short-term average of the likelihood
+  w_fast: f64,
+  /// **\[private\]** the threshold of the normalized innovation squared to reject the outliers (no measurement is rejected when it is `None`)
+  gate: Option<f64>,
+  /// **\[private\]** the random number generator which samples the particles
+  rng: StdRng,
+}
+
+impl PF {
+  /// Create a PF instance
+  ///
+  /// ## Arguments
+  /// * `map` - the landmark map which is used to estimate the pose
+  /// * `rng` - the random number generator which samples the particles
+  /// * `config` - the configuration which has the initial pose of the robot, the noise model assumed by this filter and the parameters of the particles
+  ///
+  /// ## Returns
+  /// An instance of PF
+  ///
+  /// ## Errors
+  /// When the landmark map is empty, this function returns Error
+  pub fn new(map: LandmarkMap, mut rng: StdRng, config: &Config) -> Result<PF, String> {
+    if map.landmarks().is_empty() {
+      return Err("the particle filter requires a landmark map which has at least one landmark".to_string());
+    }
+    let initial_pose = &config.scenario.initial_pose;
+    let filter = &config.filter;
+    let n = filter.pf.particles.max(1);
+    let initial = na::Vector3::new(initial_pose.x, initial_pose.y, initial_pose.theta);
+    let particles = if filter.pf.global_localization {
+      (0..n).map(|_| PF::random_particle(&map, filter.pf.margin, initial[2], &mut rng)).collect()
+    } else {
+      vec![initial; n]
+    };
+    let weights = vec![1.0 / n as f64; n];
+    let (xhat, p) = PF::estimate(&particles, &weights, initial[2]);
+    let motion = robot::MotionNoise::assumed(config);
+    let noise = camera::ObservationNoise::assumed(config);
+    let gate = filter.gate_probability.map(|probability| utils::chi2_quantile(probability, 2));
+
+    Ok(PF {
+      map, xhat, p, particles, weights,
+      nis: Vec::new(),
+      motion, noise,
+      config: filter.pf.clone(),
+      w_slow: 0.0,
+      w_fast: 0.0,
+      gate, rng,
+    })
+  }
+
+  /// **\[private\]** Weight every particle with the likelihood of the measurements
+  ///
+  /// ## Arguments
+  /// * `observations` - the tuples of (the measured vector(distance, angle), the position of the landmark)
+  ///
+  /// ## Returns
+  /// The average likelihood per measurement of the particles before the weights are normalized, or `None` when the weights cannot be calculated
+  fn weigh(&mut self, observations: &[(na::Vector2<f64>, Point)]) -> Option<f64> {
+    // the covariance matrix of observation noise depends on the measured distance, but not on the particle
+    let r_invs = observations.iter()
+                             .map(|(z, _)| self.noise.covariance(z[0]).try_inverse())
+                             .collect::<Option<Vec<_>>>()?;
+    let log_likelihoods = self.particles.iter()
+                                        .map(|particle| {
+                                          observations.iter()
+                                                      .zip(r_invs.iter())
+                                                      .map(|((z, landmark), r_inv)| {
+                                                        let yhat = camera::innovation(z, landmark, particle);
+                                                        -0.5 * (yhat.transpose() * r_inv * yhat)[0]
+                                                      })
+                                                      .sum::<f64>()
+                                        })
+                                        .collect::<Vec<_>>();
+    let max = log_likelihoods.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    if !max.is_finite() {
+      return None;
+    }
+    // the likelihoods are scaled by exp(-max) to avoid the underflow, and the scale is restored for the average
+    let scaled = self.weights.iter().zip(log_likelihoods.iter()).map(|(w, l)| w * (l - max).exp()).collect::<Vec<_>>();
+    let total = scaled.iter().sum::<f64>();
+    if !(total.is_finite() && total > 0.0) {
+      return None;
+    }
+    self.weights = scaled.iter().map(|w| w / total).collect();
+    let log_normalizer = observations.iter().map(|(z, _)| ((2.0 * PI) * self.noise.covariance(z[0]).determinant().sqrt()).ln()).sum::<f64>();
+    let log_average = total.ln() + max - log_normalizer;
+    Some((log_average / observations.len() as f64).exp())
+  }
+
+  /// **\[private\]** Calculate the normalized innovation squared of the measurement against the weighted mean of the particles
+  ///
+  /// ## Arguments
+  /// * `z` - the measured vector(distance, angle)
+  /// * `landmark` - the position of the landmark associated with the measurement
+  ///
+  /// ## Returns
+  /// The normalized innovation squared, or `None` when it cannot be calculated
+  fn calc_nis(&self, z: &na::Vector2<f64>, landmark: &Point) -> Option<f64> {
+    let h = camera::calc_h(landmark, &self.xhat)?;
+    let s_inv = (h * self.p * h.transpose() + self.noise.covariance(z[0])).try_inverse()?;
+    let yhat = camera::innovation(z, landmark, &self.xhat);
+    Some((yhat.transpose() * s_inv * yhat)[0])
+  }
+
+  /// **\[private\]** Resample the particles by the systematic (low variance) resampling
+  ///
+  /// Each particle is replaced by a random particle with the probability `random_ratio`
+  fn resample(&mut self, random_ratio: f64) {
+    let n = self.particles.len();
+    let step = 1.0 / n as f64;
+    let mut u = self.rng.gen_range(0.0..step);
+    let mut cumulative = self.weights[0];
+    let mut i = 0;
+    let mut resampled = Vec::with_capacity(n);
+    for _ in 0..n {
+      while u > cumulative && i < n - 1 {
+        i += 1;
+        cumulative += self.weights[i];
+      }
+      if random_ratio > 0.0 && self.rng.gen::<f64>() < random_ratio {
+        resampled.push(PF::random_particle(&self.map, self.config.margin, self.xhat[2], &mut self.rng));
+      } else {
+        resampled.push(self.particles[i]);
+      }
+      u += step;
+    }
+    self.particles = resampled;
+    self.weights = vec![step; n];
+  }
+
+  /// **\[private\]** Sample a particle uniformly around the landmarks of the map
+  ///
+  /// ## Arguments
+  /// * `map` - the landmark map
+  /// * `margin` - the margin (m) around the landmarks
+  /// * `reference_theta` - the direction which the sampled direction is unwrapped around
+  /// * `rng` - the random number generator
+  fn random_particle(map: &LandmarkMap, margin: f64, reference_theta: f64, rng: &mut StdRng) -> na::Vector3<f64> {
+    let landmarks = map.landmarks();
+    let (min_x, max_x) = landmarks.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), l| (min.min(l.x), max.max(l.x)));
+    let (min_y, max_y) = landmarks.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), l| (min.min(l.y), max.max(l.y)));
+    na::Vector3::new(
+      rng.gen_range((min_x - margin)..=(max_x + margin)),
+      rng.gen_range((min_y - margin)..=(max_y + margin)),
+      reference_theta + rng.gen_range(-PI..PI),
+    )
+  }
+
+  /// Calculate the weighted mean and covariance matrix of the particles
+  ///
+  /// The directions are averaged as the differences from `reference_theta`, so that they are not broken around -pi and pi,
+  /// and the averaged direction is normalized in the same way as [robot::ideal_move]
+  ///
+  /// ## Returns
+  /// Tuple of (the weighted mean(x, y, theta), the weighted covariance matrix)
+  pub(crate) fn estimate(particles: &[na::Vector3<f64>], weights: &[f64], reference_theta: f64) -> (na::Vector3<f64>, na::Matrix3<f64>) {
+    let wrap = |theta: f64| utils::normalize_angle(theta - reference_theta);
+    let mut mean = na::Vector3::zeros();
+    for (particle, w) in particles.iter().zip(weights.iter()) {
+      mean += na::Vector3::new(particle[0], particle[1], wrap(particle[2])) * *w;
+    }
+    let mut p = na::Matrix3::zeros();
+    for (particle, w) in particles.iter().zip(weights.iter()) {
+      let mut d = particle - mean;
+      d[2] = wrap(particle[2]) - mean[2];
+      p += d * d.transpose() * *w;
+    }
+    mean[2] = utils::normalize_angle(mean[2] + reference_theta);
+    (mean, p)
+  }
+}
+
+/// The implementation for Estimator trait
+impl Estimator for PF {
+
+  /// Calculate the "predict step" by moving every particle with the process noise
+  fn predict(&mut self, input: &na::Vector2<f64>, delta: f64) {
+    for particle in self.particles.iter_mut() {
+      *particle = self.motion.sample(particle, input, delta, &mut self.rng);
+    }
+    let (xhat, p) = PF::estimate(&self.particles, &self.weights, self.xhat[2]);
+    self.xhat = xhat;
+    self.p = p;
+  }
+
+  /// Calculate the "update step" by weighting the particles with all measurements, and resample the particles
+  ///
+  /// The measurements whose normalized innovation squared against the weighted mean exceeds the gate are rejected as outliers.
+  /// When all measurements are rejected, the particles are still weighted by them, because the weighted mean itself may be lost
+  /// (e.g. the kidnapped robot) and the drop of the likelihood injects the random particles.
+  /// The particles are resampled when the effective sample size is small or random particles should be injected
+  fn update(&mut self, measurements: &[Measurement]) -> Vec<Result<(), UpdateError>> {
+    let mut results = Vec::with_capacity(measurements.len());
+    let mut observations = Vec::new();
+    let mut outliers = Vec::new();
+    self.nis.clear();
+    for measurement in measurements.iter() {
+      let landmark = match measurement.id.and_then(|id| self.map.get(id)) {
+        Some(landmark) => landmark.point(),
+        None => {
+          results.push(Err(UpdateError::UnknownLandmark));
+          continue;
+        },
+      };
+      let z = na::Vector2::new(measurement.distance, measurement.angle);
+      match self.calc_nis(&z, &landmark) {
+        Some(nis) if self.gate.is_some_and(|gate| nis > gate) => {
+          results.push(Err(UpdateError::Outlier(nis)));
+          outliers.push((z, landmark));
+        },
+        nis => {
+          results.push(Ok(()));
+          observations.push((z, landmark));
+          self.nis.extend(nis);
+        },
+      }
+    }
+    if observations.is_empty() {
+      if outliers.is_empty() {
+        return results;
+      }
+      observations = outliers;
+    }
+
+    match self.weigh(&observations) {
+      Some(w_avg) => {
+        if self.w_slow == 0.0 {
+          self.w_slow = w_avg;
+          self.w_fast = w_avg;
+        }
+        self.w_slow += self.config.alpha_slow * (w_avg - self.w_slow);
+        self.w_fast += self.config.alpha_fast * (w_avg - self.w_fast);
+      },
+      None => {
+        self.nis.clear();
+        return results.into_iter().map(|result| result.and(Err(UpdateError::DegenerateWeights))).collect();
+      },
+    }
+    // the pose is estimated before the resampling, so that the injected random particles do not move it until they are weighted
+    let (xhat, p) = PF::estimate(&self.particles, &self.weights, self.xhat[2]);
+    self.xhat = xhat;
+    self.p = p;
+    let random_ratio = if self.w_slow > 0.0 { (1.0 - self.w_fast / self.w_slow).max(0.0) } else { 0.0 };
+    let ess = 1.0 / self.weights.iter().map(|w| w * w).sum::<f64>();
+    if random_ratio > 0.0 || ess < self.config.resample_threshold * self.particles.len() as f64 {
+      self.resample(random_ratio);
+    }
+    results
+  }
+
+  /// Get the current estimated pose(x, y, theta) which is the weighted mean of the particles
+  fn state(&self) -> na::Vector3<f64> {
+    self.xhat
+  }
+
+  /// Get the current covariance matrix of the particles
+  fn covariance(&self) -> na::Matrix3<f64> {
+    self.p
+  }
+
+  /// Scatter the particles around the given pose according to the given covariance matrix
+  fn reset(&mut self, state: &na::Vector3<f64>, covariance: &na::Matrix3<f64>) {
+    // the covariance matrix which cannot be decomposed is approximated by its diagonal
+    let l = na::Cholesky::new(*covariance)
+                         .map(|cholesky| cholesky.l())
+                         .unwrap_or_else(|| na::Matrix3::from_diagonal(&covariance.diagonal().map(|v| v.abs().sqrt())));
+    let n = self.particles.len();
+    let rng = &mut self.rng;
+    for particle in self.particles.iter_mut() {
+      *particle = state + l * na::Vector3::from_fn(|_, _| StandardNormal.sample(rng));
+    }
+    self.weights = vec![1.0 / n as f64; n];
+    let (xhat, p) = PF::estimate(&self.particles, &self.weights, state[2]);
+    self.xhat = xhat;
+    self.p = p;
+  }
+
+  /// Get the normalized innovation squared of the measurements applied at the latest "update step"
+  fn nis(&self) -> Vec<f64> {
+    self.nis.clone()
+  }
+
+  /// Get the particles which represent the distribution of the estimated pose
+  fn particles(&self) -> Vec<Particle> {
+    self.particles.iter()
+                  .zip(self.weights.iter())
+                  .map(|(p, w)| Particle { x: p[0], y: p[1], theta: p[2], weight: *w })
+                  .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rand::SeedableRng;
+
+  /// The measurements of all landmarks from the pose without noise
+  fn observe_all(map: &LandmarkMap, pose: &na::Vector3<f64>) -> Vec<Measurement> {
+    map.landmarks()
+       .iter()
+       .map(|landmark| {
+         let z = camera::observe(&landmark.point(), pose);
+         Measurement { id: Some(landmark.id), distance: z[0], angle: utils::normalize_angle(z[1]), signature: None }
+       })
+       .collect()
+  }
+
+  #[test]
+  fn new_rejects_an_empty_map() {
+    let map: LandmarkMap = serde_json::from_str(r#"{ "landmarks": [] }"#).unwrap();
+    assert!(PF::new(map, StdRng::seed_from_u64(0), &Config::default()).is_err());
+  }
+
+  #[test]
+  fn kidnapped_robot_is_relocalized_by_the_random_particles() {
+    // the landmarks are not symmetric, so that the pose is identified uniquely
+    let points = [Point { x: 2.0, y: 1.5 }, Point { x: -1.5, y: 1.0 }, Point { x: -0.5, y: -2.0 }, Point { x: 1.0, y: -0.5 }];
+    let map = LandmarkMap::from_points(&points).unwrap();
+    let config = Config::default();
+    let mut pf = PF::new(map.clone(), StdRng::seed_from_u64(1), &config).unwrap();
+    let (input, delta) = (na::Vector2::new(0.5, 0.5), 0.1);
+    let initial = &config.scenario.initial_pose;
+    let mut actual = na::Vector3::new(initial.x, initial.y, initial.theta);
+    let error = |pf: &PF, actual: &na::Vector3<f64>| (pf.state().fixed_rows::<2>(0) - actual.fixed_rows::<2>(0)).norm();
+
+    for _ in 0..20 {
+      actual = robot::ideal_move(&actual, &input, delta);
+      pf.predict(&input, delta);
+      pf.update(&observe_all(&map, &actual));
+    }
+    assert!(error(&pf, &actual) < 0.1);
+
+    // the robot is carried and turned around without telling the filter, so that every measurement is rejected against the mean
+    actual = na::Vector3::new(actual[0] - 1.0, actual[1] + 0.5, utils::normalize_angle(actual[2] + PI));
+    pf.predict(&input, delta);
+    let results = pf.update(&observe_all(&map, &actual));
+    assert!(results.iter().all(|result| matches!(result, Err(UpdateError::Outlier(_)))));
+    for _ in 0..200 {
+      actual = robot::ideal_move(&actual, &input, delta);
+      pf.predict(&input, delta);
+      pf.update(&observe_all(&map, &actual));
+    }
+    assert!(error(&pf, &actual) < 0.1);
+    assert!(utils::normalize_angle(pf.state()[2] - actual[2]).abs() < 0.1);
+  }
+}
@@ -0,0 +1,317 @@
+//! The `unscented_kalman_filter` module estimates a pose of a robot in its state-space model by using Unscented Kalman Filter
+//!
+//! Instead of linearizing the models by their jacobians, the sigma points are propagated through [robot::ideal_move] and [camera::observe],
+//! so that the estimation is not degraded by the linearization error in tight turns
+
+extern crate nalgebra as na;
+
+use crate::config::{Config, UkfConfig};
+use crate::data::{Point, Measurement};
+use crate::filters::{Estimator, UpdateError};
+use crate::map::LandmarkMap;
+use crate::models::{robot, camera};
+use crate::utils;
+
+/// The default value of the spread of the sigma points around the mean
+pub const ALPHA: f64 = 1.0;
+/// The default value of the prior knowledge of the distribution (2 is optimal for gaussian)
+pub const BETA: f64 = 2.0;
+/// The default value of the secondary scaling parameter
+pub const KAPPA: f64 = 0.0;
+
+/// The dimension of the state (x, y, theta)
+pub(crate) const N: usize = 3;
+/// **\[private\]** The number of the sigma points
+const SIGMA_POINTS: usize = 2 * N + 1;
+
+/// **\[private\]** Tuple of (estimated pose(x, y, theta), covariance matrix, kalman gain, normalized innovation squared) calculated by the "update step"
+type Updated = (na::Vector3<f64>, na::Matrix3<f64>, na::Matrix3x2<f64>, f64);
+
+/// A struct to estimate a pose of a robot by using Unscented Kalman Filter
+#[derive(Debug)]
+pub struct UKF {
+  /// **\[private\]** the landmark map which is used to estimate the pose
+  map: LandmarkMap,
+  /// **\[private\]** the current estimated pose(x, y, theta)
+  xhat: na::Vector3<f64>,
+  /// **\[private\]** the current covariance matrix
+  p: na::Matrix3<f64>,
+  /// **\[private\]** the kalman gain of the latest "update step"
+  k: na::Matrix3x2<f64>,
+  /// **\[private\]** the normalized innovation squared of the measurements applied at the latest "update step"
+  nis: Vec<f64>,
+  /// **\[private\]** the noise model of the robot's motion which gives the covariance matrix of process noise for every input
+  motion: robot::MotionNoise,
+  /// **\[private\]** the noise model of the camera which gives the covariance matrix of observation noise for every measurement
+  noise: camera::ObservationNoise,
+  /// **\[private\]** the weights of the sigma points to calculate the mean and the covariance
+  weights: Weights,
+  /// **\[private\]** the threshold of the normalized innovation squared to reject the outliers (no measurement is rejected when it is `None`)
+  gate: Option<f64>,
+}
+
+impl UKF {
+  /// Create an UKF instance
+  ///
+  /// ## Arguments
+  /// * `map` - the landmark map which is used to estimate the pose
+  /// * `config` - the configuration which has the initial pose of the robot, the noise model assumed by this filter and the parameters of the sigma points
+  ///
+  /// ## Returns
+  /// An instance of UKF
+  pub fn new(map: LandmarkMap, config: &Config) -> UKF {
+    let initial_pose = &config.scenario.initial_pose;
+    let filter = &config.filter;
+    let xhat = na::Vector3::new(initial_pose.x, initial_pose.y, initial_pose.theta);
+    let p = na::Matrix3::zeros();
+    let k = na::Matrix3x2::zeros();
+    let motion = robot::MotionNoise::assumed(config);
+    let noise = camera::ObservationNoise::assumed(config);
+    let weights = Weights::new(&filter.ukf);
+    let gate = filter.gate_probability.map(|probability| utils::chi2_quantile(probability, 2));
+
+    UKF { map, xhat, p, k, nis: Vec::new(), motion, noise, weights, gate }
+  }
+
+  /// **\[private\]** Calculate the "predict step" by propagating the sigma points through the motion model
+  ///
+  /// ## Arguments
+  /// * `weights` - the weights of the sigma points
+  /// * `xhat` - the current estimated pose(x, y, theta)
+  /// * `p` - the current covariance matrix
+  /// * `q` - the covariance matrix of process noise
+  /// * `input` - the current input vector(linear velocity, angular velocity)
+  /// * `delta` - time delta
+  ///
+  /// ## Returns
+  /// Tuple of (predicted pose(x, y, theta), predicted covariance matrix)
+  ///
+  /// ## Errors
+  /// When the covariance matrix cannot be decomposed, this function returns [UpdateError::SigmaPoints]
+  fn predict(weights: &Weights, xhat: &na::Vector3<f64>, p: &na::Matrix3<f64>, q: &na::Matrix3<f64>, input: &na::Vector2<f64>, delta: f64)
+    -> Result<(na::Vector3<f64>, na::Matrix3<f64>), UpdateError> {
+    let sigma_points = weights.sigma_points(xhat, p)?;
+    let propagated = sigma_points.iter().map(|x| robot::ideal_move(x, input, delta)).collect::<Vec<_>>();
+    let a_priori_x = weights.mean(&propagated, 2);
+    let a_priori_p = weights.covariance(&propagated, &a_priori_x, 2, &propagated, &a_priori_x, 2) + q;
+    Ok((a_priori_x, a_priori_p))
+  }
+
+  /// **\[private\]** Calculate the "update step" by propagating the sigma points through the observation model
+  ///
+  /// When the normalized innovation squared exceeds the gate, the measurement is rejected as an outlier
+  ///
+  /// ## Arguments
+  /// * `weights` - the weights of the sigma points
+  /// * `r` - the covariance matrix of observation noise of the measurement
+  /// * `a_priori_x` - the predicted pose(x, y, theta)
+  /// * `a_priori_p` - the predicted covariance matrix
+  /// * `landmark` - the position of the observed landmark in the map
+  /// * `measurement` - the measurement associated with the landmark
+  /// * `gate` - the threshold of the normalized innovation squared
+  ///
+  /// ## Returns
+  /// * Tuple of (estimated pose(x, y, theta), covariance matrix, kalman gain, normalized innovation squared)
+  ///
+  /// ## Errors
+  /// When the measurement is an outlier, the covariance matrix cannot be decomposed or the innovation covariance matrix is singular, this function returns [UpdateError]
+  fn update(weights: &Weights, r: &na::Matrix2<f64>, a_priori_x: &na::Vector3<f64>, a_priori_p: &na::Matrix3<f64>, landmark: &Point,
+            measurement: &Measurement, gate: Option<f64>) -> Result<Updated, UpdateError> {
+    let sigma_points = weights.sigma_points(a_priori_x, a_priori_p)?;
+    let observed = sigma_points.iter().map(|x| camera::observe(landmark, x)).collect::<Vec<_>>();
+    let zhat = weights.mean(&observed, 1);
+    let s = weights.covariance(&observed, &zhat, 1, &observed, &zhat, 1) + r;
+    let pxz = weights.covariance(&sigma_points, a_priori_x, 2, &observed, &zhat, 1);
+    let s_inv = s.try_inverse().ok_or(UpdateError::SingularInnovationCovariance)?;
+    let mut yhat = na::Vector2::new(measurement.distance, measurement.angle) - zhat;
+    yhat[1] = utils::normalize_angle(yhat[1]);
+    let nis = (yhat.transpose() * s_inv * yhat)[0];
+    if gate.is_some_and(|gate| nis > gate) {
+      return Err(UpdateError::Outlier(nis));
+    }
+    let k = pxz * s_inv;
+    let xhat = a_priori_x + k * yhat;
+    let p = a_priori_p - k * s * k.transpose();
+    Ok((xhat, p, k, nis))
+  }
+}
+
+/// The implementation for Estimator trait
+impl Estimator for UKF {
+
+  /// Calculate the "predict step" by the input of this tick
+  ///
+  /// When the covariance matrix cannot be decomposed, the mean is propagated by the motion model and the process noise is added to the covariance matrix,
+  /// so that the broken covariance matrix is detected as the divergence
+  fn predict(&mut self, input: &na::Vector2<f64>, delta: f64) {
+    let q = self.motion.covariance(&self.xhat, input, delta);
+    let (xhat, p) = match UKF::predict(&self.weights, &self.xhat, &self.p, &q, input, delta) {
+      Ok((xhat, p)) => (xhat, p),
+      Err(_) => (robot::ideal_move(&self.xhat, input, delta), self.p + q),
+    };
+    self.xhat = xhat;
+    self.p = p;
+  }
+
+  /// Calculate the "update step" by applying the measurements one by one
+  ///
+  /// The measurements which are rejected as outliers or cannot be applied because of the numerical failure are skipped
+  fn update(&mut self, measurements: &[Measurement]) -> Vec<Result<(), UpdateError>> {
+    self.k = na::Matrix3x2::zeros();
+    self.nis.clear();
+    measurements.iter()
+                .map(|measurement| {
+                  let landmark = match measurement.id.and_then(|id| self.map.get(id)) {
+                    Some(landmark) => landmark.point(),
+                    None => return Err(UpdateError::UnknownLandmark),
+                  };
+                  let (xhat, p, k, nis) = UKF::update(&self.weights, &self.noise.covariance(measurement.distance), &self.xhat, &self.p, &landmark, measurement, self.gate)?;
+                  self.xhat = xhat;
+                  self.p = p;
+                  self.k = k;
+                  self.nis.push(nis);
+                  Ok(())
+                })
+                .collect()
+  }
+
+  /// Get the current estimated pose(x, y, theta)
+  fn state(&self) -> na::Vector3<f64> {
+    self.xhat
+  }
+
+  /// Get the current covariance matrix of the estimated pose
+  fn covariance(&self) -> na::Matrix3<f64> {
+    self.p
+  }
+
+  /// Reset the estimated pose and its covariance matrix
+  fn reset(&mut self, state: &na::Vector3<f64>, covariance: &na::Matrix3<f64>) {
+    self.xhat = *state;
+    self.p = *covariance;
+    self.k = na::Matrix3x2::zeros();
+  }
+
+  /// Get the kalman gain of the latest "update step"
+  fn gain(&self) -> na::Matrix3x2<f64> {
+    self.k
+  }
+
+  /// Get the normalized innovation squared of the measurements applied at the latest "update step"
+  fn nis(&self) -> Vec<f64> {
+    self.nis.clone()
+  }
+}
+
+/// **\[private\]** A struct which stores the weights of the scaled sigma points
+#[derive(Debug)]
+struct Weights {
+  /// the scaling parameter (alpha^2 * (n + kappa) - n)
+  lambda: f64,
+  /// the weights to calculate the mean
+  mean: [f64; SIGMA_POINTS],
+  /// the weights to calculate the covariance
+  covariance: [f64; SIGMA_POINTS],
+}
+
+impl Weights {
+  /// Create the weights from the parameters of the sigma points
+  fn new(config: &UkfConfig) -> Weights {
+    let n = N as f64;
+    let lambda = config.alpha.powi(2) * (n + config.kappa) - n;
+    let mut mean = [1.0 / (2.0 * (n + lambda)); SIGMA_POINTS];
+    let mut covariance = mean;
+    mean[0] = lambda / (n + lambda);
+    covariance[0] = mean[0] + 1.0 - config.alpha.powi(2) + config.beta;
+    Weights { lambda, mean, covariance }
+  }
+
+  /// Generate the sigma points around the mean
+  ///
+  /// The covariance matrix which is not positive definite (e.g. the initial zero matrix) is decomposed with a small jitter
+  fn sigma_points(&self, x: &na::Vector3<f64>, p: &na::Matrix3<f64>) -> Result<Vec<na::Vector3<f64>>, UpdateError> {
+    let scaled = (N as f64 + self.lambda) * (p + p.transpose()) / 2.0;
+    let l = na::Cholesky::new(scaled)
+                         .or_else(|| na::Cholesky::new(scaled + 1e-12 * na::Matrix3::identity()))
+                         .ok_or(UpdateError::SigmaPoints)?
+                         .l();
+    let mut points = vec![*x];
+    points.extend(l.column_iter().map(|c| x + c));
+    points.extend(l.column_iter().map(|c| x - c));
+    Ok(points)
+  }
+
+  /// Calculate the weighted mean of the points whose element at `angle` is an angle
+  ///
+  /// The angles are averaged as the differences from the first point, so that they are not broken around -pi and pi
+  fn mean<const D: usize>(&self, points: &[na::SVector<f64, D>], angle: usize) -> na::SVector<f64, D> {
+    let mut mean = na::SVector::<f64, D>::zeros();
+    for (w, point) in self.mean.iter().zip(points.iter()) {
+      mean += point * *w;
+    }
+    let reference = points[0][angle];
+    mean[angle] = reference + self.mean.iter().zip(points.iter()).map(|(w, point)| w * utils::normalize_angle(point[angle] - reference)).sum::<f64>();
+    mean
+  }
+
+  /// Calculate the weighted cross covariance of the points whose elements at `a_angle` and `b_angle` are angles
+  fn covariance<const A: usize, const B: usize>(&self, a: &[na::SVector<f64, A>], a_mean: &na::SVector<f64, A>, a_angle: usize,
+                                                b: &[na::SVector<f64, B>], b_mean: &na::SVector<f64, B>, b_angle: usize) -> na::SMatrix<f64, A, B> {
+    let mut covariance = na::SMatrix::<f64, A, B>::zeros();
+    for ((w, a), b) in self.covariance.iter().zip(a.iter()).zip(b.iter()) {
+      let mut da = a - a_mean;
+      da[a_angle] = utils::normalize_angle(da[a_angle]);
+      let mut db = b - b_mean;
+      db[b_angle] = utils::normalize_angle(db[b_angle]);
+      covariance += da * db.transpose() * *w;
+    }
+    covariance
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn weights_follow_the_scaling_parameter() {
+    // lambda = 0.5^2 * (3 + 0) - 3 = -2.25, so that n + lambda = 0.75
+    let weights = Weights::new(&UkfConfig { alpha: 0.5, beta: 2.0, kappa: 0.0 });
+    assert!((weights.lambda + 2.25).abs() < 1e-12);
+    assert!((weights.mean[0] + 3.0).abs() < 1e-12);
+    assert!(weights.mean[1..].iter().all(|w| (w - 2.0 / 3.0).abs() < 1e-12));
+    assert!((weights.covariance[0] + 0.25).abs() < 1e-12);
+    assert!((weights.mean.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+  }
+
+  #[test]
+  fn sigma_points_reproduce_the_mean_and_the_covariance() {
+    // the direction near pi makes some sigma points cross -pi and pi
+    let x = na::Vector3::new(1.0, -2.0, 3.1);
+    let p = na::Matrix3::new(0.04, 0.01, 0.002,
+                             0.01, 0.09, -0.003,
+                             0.002, -0.003, 0.01);
+    for config in [UkfConfig::default(), UkfConfig { alpha: 0.5, beta: 2.0, kappa: 1.0 }] {
+      let weights = Weights::new(&config);
+      let points = weights.sigma_points(&x, &p).unwrap().iter().map(|point| {
+                     let mut point = *point;
+                     point[2] = utils::normalize_angle(point[2]);
+                     point
+                   }).collect::<Vec<_>>();
+      assert_eq!(points.len(), SIGMA_POINTS);
+
+      let mean = weights.mean(&points, 2);
+      assert!((mean.fixed_rows::<2>(0) - x.fixed_rows::<2>(0)).norm() < 1e-9);
+      assert!(utils::normalize_angle(mean[2] - x[2]).abs() < 1e-9);
+      let covariance = weights.covariance(&points, &mean, 2, &points, &mean, 2);
+      assert!((covariance - p).norm() < 1e-9);
+    }
+  }
+
+  #[test]
+  fn sigma_points_of_the_zero_covariance_collapse_to_the_mean() {
+    let x = na::Vector3::new(0.5, 0.5, 0.0);
+    let points = Weights::new(&UkfConfig::default()).sigma_points(&x, &na::Matrix3::zeros()).unwrap();
+    assert!(points.iter().all(|point| (point - x).norm() < 1e-5));
+  }
+}
@@ -3,22 +3,32 @@
 //! A simulator of an autonomous mobile robot using Extended Kalman Filter and Dynamic Window Approach.
 
 pub mod agent;
+pub mod cli;
+pub mod clocks;
+pub mod config;
 pub mod filters;
 pub mod planners;
+pub mod pose_graph;
+pub mod simulation;
 pub mod models;
 pub mod timers;
 pub mod data;
+pub mod map;
+pub mod metrics;
+pub mod monte_carlo;
+pub mod obstacles;
 pub mod utils;
 
 use std::error::Error;
 use std::f64::consts::PI;
 
-use crate::data::{ Point, Pose};
+use crate::data::{Point, Pose};
+use crate::filters::fast_slam;
 
-/// **\[private\]** the initial pose of the robot
+/// **\[private\]** the default initial pose of the robot
 const INITIAL_POSE: Pose = Pose { x: 1.0, y: 0.0, theta: PI / 2.0 };
 
-/// **\[private\]** the array of landmark points which will be observed from robot
+/// **\[private\]** the default array of landmark points which will be observed from robot
 const LANDMARKS: [Point; 8]  = [
   Point {x: 1.1, y:  1.1}, Point {x: 0.0, y:  1.1}, Point {x: -1.1, y:  1.1},
   Point {x: 1.1, y:  0.0},                          Point {x: -1.1, y:  0.0},
@@ -28,17 +38,102 @@ const LANDMARKS: [Point; 8]  = [
 /// The entry point of this library crate.
 ///
 /// ## Arguments
-/// * `args` - a command line argument that means the Agent to be used. This crate can receive the following arguments:
-///     * circular
-///     * square
-///     * waypoints
+/// * `options` - the options of the `run` command parsed by [cli::parse]. The options which are given override the values of the scenario configuration
 ///
 /// ## Returns
 /// This function returns nothing when the process finishs successfully, but returns Error trait object when the process raises errors.
-pub fn run(args: std::env::Args) -> Result<(), Box<dyn Error>> {
-  let agt = agent::create_agent(args, LANDMARKS.to_vec())?;
-  let ekf = filters::kalman_filter::EKF::new(agt, INITIAL_POSE);
-  timers::start(ekf)?;
+pub fn run(options: cli::RunOptions) -> Result<(), Box<dyn Error>> {
+  let mut config = match &options.config {
+    Some(path) => config::Config::load(path)?,
+    None => config::Config::default(),
+  };
+  if let Some(port) = options.port {
+    config.timer.port = port;
+  }
+  if let Some(interval_ms) = options.interval_ms {
+    config.timer.interval_ms = interval_ms;
+  }
+  if let Some(initial_pose) = options.initial_pose {
+    config.scenario.initial_pose = initial_pose;
+  }
+  if let Some(kind) = options.filter {
+    config.filter.kind = kind;
+  }
+  if let Some(kind) = options.planner {
+    config.planner.kind = kind;
+  }
+  if let Some(path) = options.landmarks_file {
+    config.scenario.landmarks_file = Some(path);
+  }
+  if let Some(trials) = options.trials {
+    config.monte_carlo.trials = trials;
+  }
+  if let Some(jobs) = options.jobs {
+    config.monte_carlo.jobs = Some(jobs);
+  }
+  // the options may override the validated configuration with the values out of range
+  config.validate()?;
+
+  if config.filter.kind.is_slam() && config.association.method != config::AssociationMethod::Known {
+    return Err("the SLAM filters require the known data association (association.method = \"known\")".into());
+  }
+
+  let seed = options.seed.or(config.seed).unwrap_or_else(rand::random::<u64>);
+  eprintln!("seed = {}", seed);
+  let steps = match (options.steps, options.duration) {
+    (Some(steps), _) => Some(steps),
+    (None, Some(duration)) => Some((duration / config.timer.interval_secs()).ceil() as u64),
+    (None, None) => None,
+  };
+  if steps.is_some() && config.timer.clock != config::ClockKind::Simulated {
+    // the tight loop would integrate the motion over the wall-clock deltas of almost zero, which are not reproducible either
+    return Err("the headless batch mode and the Monte Carlo experiment require the simulated clock (timer.clock = \"simulated\")".into());
+  }
+
+  let map = config.scenario.load_map()?;
+  if config.monte_carlo.trials > 1 || options.output.is_some() {
+    let steps = steps.ok_or("the Monte Carlo experiment requires the headless batch mode (--steps or --duration)")?;
+    let report = monte_carlo::run(&options.agent, &map, &config, seed, steps);
+    match &options.output {
+      Some(path) if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("csv")) => std::fs::write(path, report.to_csv())?,
+      Some(path) => std::fs::write(path, serde_json::to_string_pretty(&report)?)?,
+      None => println!("{}", serde_json::to_string(&report)?),
+    }
+    return Ok(());
+  }
+  if steps.is_none() {
+    // the real-time mode runs forever, so that the history of FastSLAM and the pose graph are limited unless they are configured
+    config.filter.fastslam.max_history.get_or_insert(fast_slam::REALTIME_MAX_HISTORY);
+    config.graph.max_poses.get_or_insert(pose_graph::REALTIME_MAX_POSES);
+  }
+  let simulation = simulation::create_simulation(&options.agent, &map, &config, seed)?;
+
+  match steps {
+    Some(steps) => {
+      let summary = timers::run_batch(simulation, steps);
+      println!("{}", serde_json::to_string(&summary)?);
+    },
+    None => timers::start(simulation, &config.timer)?,
+  }
 
   Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn batch_mode_rejects_the_wall_clock() {
+    let path = std::env::temp_dir().join(format!("robot_simulator_rust_wall_clock_{}.json", std::process::id()));
+    std::fs::write(&path, r#"{ "timer": { "clock": "wall" } }"#).unwrap();
+    let options = |steps: Option<u64>, duration: Option<f64>| cli::RunOptions {
+      agent: "circular".to_string(), config: Some(path.clone()), steps, duration, ..cli::RunOptions::default()
+    };
+    let steps = run(options(Some(1), None));
+    let duration = run(options(None, Some(1.0)));
+    std::fs::remove_file(&path).unwrap();
+    assert!(steps.unwrap_err().to_string().contains("simulated clock"));
+    assert!(duration.unwrap_err().to_string().contains("simulated clock"));
+  }
+}
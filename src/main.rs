@@ -7,10 +7,36 @@ extern crate robot_simulator_rust;
 use std::env;
 use std::process;
 
+use robot_simulator_rust::agent;
+use robot_simulator_rust::cli::{self, Command};
+
+/// The exit status when the simulation fails
+const EXIT_FAILURE: i32 = 1;
+/// The exit status when the command line arguments are invalid
+const EXIT_USAGE: i32 = 2;
+
 /// The entry point of this binary crate.
 fn main() {
-  if let Err(e) = robot_simulator_rust::run(env::args()) {
-    eprintln!("Problem parsing arguments: {}", e);
-    process::exit(1);
+  let command = match cli::parse(env::args().skip(1)) {
+    Ok(command) => command,
+    Err(e) => {
+      eprintln!("Problem parsing arguments: {}\nTry `--help` for more information.", e);
+      process::exit(EXIT_USAGE);
+    },
+  };
+
+  match command {
+    Command::Help => println!("{}", cli::USAGE),
+    Command::ListAgents => {
+      for name in agent::AGENT_NAMES.iter() {
+        println!("{}", name);
+      }
+    },
+    Command::Run(options) => {
+      if let Err(e) = robot_simulator_rust::run(*options) {
+        eprintln!("Problem running simulation: {}", e);
+        process::exit(EXIT_FAILURE);
+      }
+    },
   }
 }
@@ -0,0 +1,241 @@
+//! The `map` module provides the landmark map which can be loaded from a JSON, CSV or YAML file
+//!
+//! Each landmark has its position (x, y), an optional ID and optional metadata. When the ID is omitted, the index in the file is used as its ID.
+//!
+//! * JSON: `[{"id": 0, "x": 1.1, "y": 1.1, "color": "red"}, ...]`
+//! * YAML: a sequence of mappings which have the same fields as JSON
+//! * CSV: a header line which has `x`, `y` and optionally `id` columns, followed by one landmark per line.
+//!   Blank lines and lines starting with `#` are ignored, and quoted values are not supported
+//!
+//! The fields (columns) other than `id`, `x` and `y` are stored as the metadata of the landmark.
+
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json;
+use serde_yaml;
+
+use crate::data::Point;
+
+/// A struct which defines a landmark of the map
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct Landmark {
+  /// the unique ID of this landmark
+  pub id: u64,
+  /// the position (x) of this landmark
+  pub x: f64,
+  /// the position (y) of this landmark
+  pub y: f64,
+  /// the additional information of this landmark
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub metadata: BTreeMap<String, serde_json::Value>,
+}
+
+impl Landmark {
+  /// Get the position of this landmark as [Point]
+  pub fn point(&self) -> Point {
+    Point { x: self.x, y: self.y }
+  }
+
+  /// Get the signature (e.g. color or shape) of this landmark which can be measured by the camera
+  ///
+  /// ## Returns
+  /// The numeric `signature` metadata (a number, or a string of number written in CSV) when it exists, otherwise the ID of this landmark
+  pub fn signature(&self) -> f64 {
+    self.metadata.get("signature")
+                 .and_then(|s| s.as_f64().or_else(|| s.as_str().and_then(|s| s.parse::<f64>().ok())))
+                 .unwrap_or(self.id as f64)
+  }
+}
+
+/// A struct which stores the validated landmarks
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct LandmarkMap {
+  /// **\[private\]** the landmarks of this map
+  landmarks: Vec<Landmark>,
+}
+
+impl LandmarkMap {
+  /// Create a LandmarkMap instance from the given landmarks
+  ///
+  /// ## Arguments
+  /// * `landmarks` - the landmarks of this map
+  ///
+  /// ## Returns
+  /// An instance of LandmarkMap
+  ///
+  /// ## Errors
+  /// When no landmark is given, a position is not finite or an ID is duplicated, this function returns Error
+  pub fn new(landmarks: Vec<Landmark>) -> Result<LandmarkMap, String> {
+    if landmarks.is_empty() {
+      return Err("the map has no landmark".to_string());
+    }
+    let mut ids = HashSet::new();
+    for landmark in landmarks.iter() {
+      if !landmark.x.is_finite() || !landmark.y.is_finite() {
+        return Err(format!("the position of landmark {} is not finite: ({}, {})", landmark.id, landmark.x, landmark.y));
+      }
+      if !ids.insert(landmark.id) {
+        return Err(format!("the ID of landmark is duplicated: {}", landmark.id));
+      }
+    }
+    Ok(LandmarkMap { landmarks })
+  }
+
+  /// Create a LandmarkMap instance from the given points whose IDs are their indexes
+  ///
+  /// ## Arguments
+  /// * `points` - the positions of landmarks
+  ///
+  /// ## Returns
+  /// An instance of LandmarkMap
+  ///
+  /// ## Errors
+  /// When no point is given or a position is not finite, this function returns Error
+  pub fn from_points(points: &[Point]) -> Result<LandmarkMap, String> {
+    LandmarkMap::new(points.iter()
+                           .enumerate()
+                           .map(|(i, p)| Landmark { id: i as u64, x: p.x, y: p.y, metadata: BTreeMap::new() })
+                           .collect())
+  }
+
+  /// Load a LandmarkMap from the given file
+  ///
+  /// The format of the file is determined by its extension (`.json`, `.csv`, `.yaml` or `.yml`)
+  ///
+  /// ## Arguments
+  /// * `path` - the path of the map file
+  ///
+  /// ## Returns
+  /// An instance of LandmarkMap
+  ///
+  /// ## Errors
+  /// When the file cannot be read, the format is unknown, or the file is not a valid map, this function returns Error
+  pub fn load<P: AsRef<Path>>(path: P) -> Result<LandmarkMap, Box<dyn Error>> {
+    let path = path.as_ref();
+    let content = fs::read_to_string(path).map_err(|e| format!("cannot read map file {}: {}", path.display(), e))?;
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    let records = match extension.as_str() {
+      "json" => serde_json::from_str::<Vec<LandmarkRecord>>(&content).map_err(|e| e.to_string()),
+      "yaml" | "yml" => serde_yaml::from_str::<Vec<LandmarkRecord>>(&content).map_err(|e| e.to_string()),
+      "csv" => parse_csv(&content),
+      _ => Err(format!("unknown format (expected .json, .csv, .yaml or .yml): {}", extension)),
+    }.map_err(|e| format!("invalid map file {}: {}", path.display(), e))?;
+
+    let landmarks = records.into_iter()
+                           .enumerate()
+                           .map(|(i, r)| Landmark { id: r.id.unwrap_or(i as u64), x: r.x, y: r.y, metadata: r.metadata })
+                           .collect();
+    let map = LandmarkMap::new(landmarks).map_err(|e| format!("invalid map file {}: {}", path.display(), e))?;
+    Ok(map)
+  }
+
+  /// Get the landmarks of this map
+  pub fn landmarks(&self) -> &[Landmark] {
+    &self.landmarks
+  }
+
+  /// Get the landmark which has the given ID
+  ///
+  /// ## Arguments
+  /// * `id` - the ID of the landmark
+  ///
+  /// ## Returns
+  /// The landmark when it exists in this map, or `None`
+  pub fn get(&self, id: u64) -> Option<&Landmark> {
+    self.landmarks.iter().find(|landmark| landmark.id == id)
+  }
+}
+
+/// **\[private\]** A struct which defines a landmark written in a map file
+#[derive(Deserialize)]
+struct LandmarkRecord {
+  /// the optional ID of the landmark
+  id: Option<u64>,
+  /// the position (x) of the landmark
+  x: f64,
+  /// the position (y) of the landmark
+  y: f64,
+  /// the other fields of the landmark
+  #[serde(flatten)]
+  metadata: BTreeMap<String, serde_json::Value>,
+}
+
+/// **\[private\]** Parse the content of a CSV map file
+///
+/// ## Arguments
+/// * `content` - the content of the CSV file
+///
+/// ## Returns
+/// The landmarks written in the CSV file
+///
+/// ## Errors
+/// When the header does not have `x` and `y` columns, or a line cannot be parsed, this function returns Error
+fn parse_csv(content: &str) -> Result<Vec<LandmarkRecord>, String> {
+  let mut lines = content.lines()
+                         .enumerate()
+                         .map(|(i, line)| (i + 1, line.trim()))
+                         .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+  let header = match lines.next() {
+    Some((_, line)) => line.split(',').map(|column| column.trim().to_lowercase()).collect::<Vec<_>>(),
+    None => return Ok(Vec::new()),
+  };
+  let column = |name: &str| header.iter().position(|c| c == name);
+  let (x_idx, y_idx) = match (column("x"), column("y")) {
+    (Some(x_idx), Some(y_idx)) => (x_idx, y_idx),
+    _ => return Err("the header must have `x` and `y` columns".to_string()),
+  };
+  let id_idx = column("id");
+
+  lines.map(|(n, line)| {
+         let values = line.split(',').map(|value| value.trim()).collect::<Vec<_>>();
+         if values.len() != header.len() {
+           return Err(format!("line {}: expected {} columns but found {}", n, header.len(), values.len()));
+         }
+         let parse_f64 = |idx: usize| values[idx].parse::<f64>().map_err(|e| format!("line {}: invalid {}: {} ({})", n, header[idx], values[idx], e));
+         let id = match id_idx {
+           Some(idx) => Some(values[idx].parse::<u64>().map_err(|e| format!("line {}: invalid id: {} ({})", n, values[idx], e))?),
+           None => None,
+         };
+         let metadata = header.iter()
+                              .zip(values.iter())
+                              .enumerate()
+                              .filter(|(idx, _)| *idx != x_idx && *idx != y_idx && Some(*idx) != id_idx)
+                              .map(|(_, (name, value))| (name.clone(), serde_json::Value::String(value.to_string())))
+                              .collect();
+         Ok(LandmarkRecord { id, x: parse_f64(x_idx)?, y: parse_f64(y_idx)?, metadata })
+       })
+       .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_csv_reads_the_columns_by_the_header() {
+    let content = "# landmarks of the test\n Y, X, id, label\n\n1.5, -0.5, 7, door\n0.0,1.0,8,window\n";
+    let records = parse_csv(content).unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!((records[0].id, records[0].x, records[0].y), (Some(7), -0.5, 1.5));
+    assert_eq!(records[0].metadata.get("label"), Some(&serde_json::Value::String("door".to_string())));
+    assert_eq!((records[1].id, records[1].x, records[1].y), (Some(8), 1.0, 0.0));
+  }
+
+  #[test]
+  fn parse_csv_rejects_invalid_lines() {
+    assert!(parse_csv("id,label\n0,door\n").is_err());
+    assert_eq!(parse_csv("x,y\n1.0,2.0\n3.0\n").err(), Some("line 3: expected 2 columns but found 1".to_string()));
+    assert!(parse_csv("x,y\n1.0,north\n").is_err());
+    assert!(parse_csv("x,y,id\n1.0,2.0,-1\n").is_err());
+    assert!(parse_csv("# empty\n").unwrap().is_empty());
+  }
+}
@@ -0,0 +1,235 @@
+//! The `metrics` module evaluates the accuracy and the consistency of the estimator against the hidden actual pose
+//!
+//! The following metrics are calculated at every tick and aggregated over the simulation
+//! * the errors of the estimated position(x, y) and direction(theta), which are aggregated as the root mean squared errors
+//! * NEES (normalized estimation error squared): the squared Mahalanobis distance of the estimation error against the estimated covariance matrix
+//! * NIS (normalized innovation squared): the squared Mahalanobis distance of the innovation of every measurement against its innovation covariance matrix
+//!
+//! When the estimator is consistent, NEES follows the chi-square distribution with 3 degrees of freedom and NIS with 2 degrees of freedom,
+//! so that about 95% of them fall within the two-sided 95% bounds of the chi-square distribution.
+
+use serde::{Deserialize, Serialize};
+
+extern crate nalgebra as na;
+
+use crate::utils;
+
+/// The probability of the two-sided bounds of the chi-square distribution which NEES and NIS are checked against
+pub const BOUND_PROBABILITY: f64 = 0.95;
+
+/// **\[private\]** The degrees of freedom of NEES (x, y, theta)
+const NEES_DOF: usize = 3;
+/// **\[private\]** The degrees of freedom of NIS (distance, angle)
+const NIS_DOF: usize = 2;
+
+/// A struct which stores the metrics of the estimator at a tick
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+#[derive(Serialize, Deserialize)]
+pub struct StepMetrics {
+  /// the error of the estimated position(x, y) against the actual position
+  pub position_error: f64,
+  /// the error of the estimated direction(theta) against the actual direction (-pi <= error < pi)
+  pub theta_error: f64,
+  /// the normalized estimation error squared of the estimated pose (`None` when the covariance matrix is singular)
+  pub nees: Option<f64>,
+  /// the normalized innovation squared of every measurement which is applied or rejected as an outlier
+  pub nis: Vec<f64>,
+}
+
+impl StepMetrics {
+  /// Calculate the metrics of the estimated pose against the actual pose
+  ///
+  /// ## Arguments
+  /// * `actual` - the hidden actual pose(x, y, theta)
+  /// * `xhat` - the estimated pose(x, y, theta)
+  /// * `p` - the covariance matrix of the estimated pose
+  /// * `nis` - the normalized innovation squared of the measurements at this tick
+  ///
+  /// ## Returns
+  /// The metrics at this tick
+  pub fn new(actual: &na::Vector3<f64>, xhat: &na::Vector3<f64>, p: &na::Matrix3<f64>, nis: Vec<f64>) -> StepMetrics {
+    let d = actual[2] - xhat[2];
+    let error = na::Vector3::new(actual[0] - xhat[0], actual[1] - xhat[1], utils::normalize_angle(d));
+    let nees = p.try_inverse()
+                .map(|p_inv| (error.transpose() * p_inv * error)[0])
+                .filter(|nees| nees.is_finite() && *nees >= 0.0);
+    StepMetrics {
+      position_error: error.fixed_rows::<2>(0).norm(),
+      theta_error: error[2],
+      nees,
+      nis,
+    }
+  }
+}
+
+/// A struct which accumulates the metrics of every tick
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct Metrics {
+  /// **\[private\]** the number of accumulated ticks
+  steps: usize,
+  /// **\[private\]** the sum of the squared errors of the position
+  squared_position_error: f64,
+  /// **\[private\]** the sum of the squared errors of the direction
+  squared_theta_error: f64,
+  /// **\[private\]** the maximum error of the position
+  max_position_error: f64,
+  /// **\[private\]** the accumulated NEES
+  nees: Consistency,
+  /// **\[private\]** the accumulated NIS
+  nis: Consistency,
+}
+
+impl Metrics {
+  /// Create a Metrics instance which has no tick
+  pub fn new() -> Metrics {
+    Metrics {
+      steps: 0,
+      squared_position_error: 0.0,
+      squared_theta_error: 0.0,
+      max_position_error: 0.0,
+      nees: Consistency::new(NEES_DOF),
+      nis: Consistency::new(NIS_DOF),
+    }
+  }
+
+  /// Accumulate the metrics of a tick
+  pub fn add(&mut self, step: &StepMetrics) {
+    self.steps += 1;
+    self.squared_position_error += step.position_error.powi(2);
+    self.squared_theta_error += step.theta_error.powi(2);
+    self.max_position_error = self.max_position_error.max(step.position_error);
+    self.nees.add(step.nees.iter());
+    self.nis.add(step.nis.iter());
+  }
+
+  /// Get the aggregated metrics of all accumulated ticks
+  pub fn summary(&self) -> MetricsSummary {
+    let n = self.steps.max(1) as f64;
+    MetricsSummary {
+      position_rmse: (self.squared_position_error / n).sqrt(),
+      theta_rmse: (self.squared_theta_error / n).sqrt(),
+      max_position_error: self.max_position_error,
+      nees_mean: self.nees.mean(),
+      nees_within_bounds: self.nees.within_bounds(),
+      nis_mean: self.nis.mean(),
+      nis_within_bounds: self.nis.within_bounds(),
+      nis_count: self.nis.count,
+    }
+  }
+}
+
+impl Default for Metrics {
+  fn default() -> Metrics {
+    Metrics::new()
+  }
+}
+
+/// A struct which stores the aggregated metrics of the estimator
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct MetricsSummary {
+  /// the root mean squared error of the estimated position(x, y) against the actual position
+  pub position_rmse: f64,
+  /// the root mean squared error of the estimated direction(theta) against the actual direction
+  pub theta_rmse: f64,
+  /// the maximum error of the estimated position(x, y) against the actual position
+  pub max_position_error: f64,
+  /// the average NEES (3 for the consistent estimator, `None` when no NEES is calculated)
+  pub nees_mean: Option<f64>,
+  /// the fraction of the ticks whose NEES is within the 95% bounds of the chi-square distribution
+  pub nees_within_bounds: Option<f64>,
+  /// the average NIS (2 for the consistent estimator, `None` when no NIS is calculated)
+  pub nis_mean: Option<f64>,
+  /// the fraction of the measurements whose NIS is within the 95% bounds of the chi-square distribution
+  pub nis_within_bounds: Option<f64>,
+  /// the number of measurements whose NIS is calculated
+  pub nis_count: usize,
+}
+
+/// **\[private\]** A struct which accumulates the normalized squared errors which should follow the chi-square distribution
+#[derive(Clone)]
+#[derive(Debug)]
+struct Consistency {
+  /// the lower and upper bounds of the chi-square distribution
+  bounds: (f64, f64),
+  /// the number of accumulated values
+  count: usize,
+  /// the sum of the accumulated values
+  sum: f64,
+  /// the number of accumulated values within the bounds
+  within: usize,
+}
+
+impl Consistency {
+  /// Create a Consistency instance for the chi-square distribution with the given degrees of freedom
+  fn new(dof: usize) -> Consistency {
+    let tail = (1.0 - BOUND_PROBABILITY) / 2.0;
+    let bounds = (utils::chi2_quantile(tail, dof), utils::chi2_quantile(1.0 - tail, dof));
+    Consistency { bounds, count: 0, sum: 0.0, within: 0 }
+  }
+
+  /// Accumulate the values
+  fn add<'a>(&mut self, values: impl Iterator<Item = &'a f64>) {
+    for value in values {
+      self.count += 1;
+      self.sum += value;
+      if self.bounds.0 <= *value && *value <= self.bounds.1 {
+        self.within += 1;
+      }
+    }
+  }
+
+  /// Get the average of the accumulated values
+  fn mean(&self) -> Option<f64> {
+    if self.count == 0 { None } else { Some(self.sum / self.count as f64) }
+  }
+
+  /// Get the fraction of the accumulated values within the bounds
+  fn within_bounds(&self) -> Option<f64> {
+    if self.count == 0 { None } else { Some(self.within as f64 / self.count as f64) }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn step_metrics_calculate_nees_against_the_covariance() {
+    // the error of the direction is wrapped around -pi and pi
+    let actual = na::Vector3::new(1.2, 0.9, 3.1);
+    let xhat = na::Vector3::new(1.0, 1.0, -3.1);
+    let p = na::Matrix3::from_diagonal(&na::Vector3::new(0.04, 0.01, 0.25));
+    let step = StepMetrics::new(&actual, &xhat, &p, vec![1.0]);
+    let theta_error = 6.2 - 2.0 * std::f64::consts::PI;
+    assert!((step.position_error - 0.05_f64.sqrt()).abs() < 1e-12);
+    assert!((step.theta_error - theta_error).abs() < 1e-12);
+    assert!((step.nees.unwrap() - (1.0 + 1.0 + theta_error.powi(2) / 0.25)).abs() < 1e-9);
+
+    assert!(StepMetrics::new(&actual, &xhat, &na::Matrix3::zeros(), Vec::new()).nees.is_none());
+  }
+
+  #[test]
+  fn summary_checks_the_bounds_of_the_chi_square_distribution() {
+    // the two-sided 95% bounds are (0.216, 9.348) for 3 degrees of freedom and (0.0506, 7.378) for 2 degrees of freedom
+    let mut metrics = Metrics::new();
+    assert!(metrics.summary().nees_mean.is_none() && metrics.summary().nis_mean.is_none());
+    metrics.add(&StepMetrics { position_error: 0.3, theta_error: 0.1, nees: Some(0.1), nis: vec![0.04, 1.0] });
+    metrics.add(&StepMetrics { position_error: 0.4, theta_error: -0.1, nees: Some(3.0), nis: vec![7.0, 8.0] });
+    metrics.add(&StepMetrics { position_error: 0.0, theta_error: 0.0, nees: None, nis: Vec::new() });
+
+    let summary = metrics.summary();
+    assert!((summary.position_rmse - (0.25_f64 / 3.0).sqrt()).abs() < 1e-12);
+    assert!((summary.theta_rmse - (0.02_f64 / 3.0).sqrt()).abs() < 1e-12);
+    assert_eq!(summary.max_position_error, 0.4);
+    assert!((summary.nees_mean.unwrap() - 1.55).abs() < 1e-12);
+    assert_eq!(summary.nees_within_bounds, Some(0.5));
+    assert!((summary.nis_mean.unwrap() - 4.01).abs() < 1e-12);
+    assert_eq!(summary.nis_within_bounds, Some(0.5));
+    assert_eq!(summary.nis_count, 4);
+  }
+}
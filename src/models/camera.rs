@@ -1,8 +1,89 @@
-//! The `camera` module provides an observation equation of a camera and the jacobian of the observation equation
+//! The `camera` module provides an observation equation of a camera, the jacobian of the observation equation and the sensor model which determines the visible landmarks
+
+use std::f64::consts::PI;
 
 extern crate nalgebra as na;
 
+use crate::config::{Config, CameraConfig, NoiseConfig};
 use crate::data::Point;
+use crate::utils;
+
+/// The default value of the minimum distance to observe a landmark
+pub const MIN_RANGE: f64 = 0.0;
+/// The default value of the angular field of view (the whole angle centered on the robot's heading) of the camera
+pub const FOV: f64 = 2.0 * PI;
+/// The default value of the radius of a landmark which is used to determine whether a landmark is hidden behind other landmarks
+pub const LANDMARK_RADIUS: f64 = 0.05;
+
+/// A struct which defines the gaussian noise model of the camera's observation
+///
+/// The noise of the distance and the angle are independent, and the standard deviation of the distance grows linearly with the distance.
+/// The same model is used to simulate the noisy observation and to give the covariance matrix of observation noise to the filters
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct ObservationNoise {
+  /// the standard deviation of the distance at zero distance
+  pub dist_sd: f64,
+  /// the increase of the standard deviation of the distance per meter
+  pub dist_sd_per_meter: f64,
+  /// the standard deviation of the angle
+  pub angle_sd: f64,
+}
+
+impl ObservationNoise {
+  /// Create the noise model which is used to simulate the camera
+  ///
+  /// ## Arguments
+  /// * `noise` - the parameters of the noise which is added to the simulated robot and camera
+  ///
+  /// ## Returns
+  /// The noise model of the camera
+  pub fn new(noise: &NoiseConfig) -> ObservationNoise {
+    ObservationNoise {
+      dist_sd: noise.observed_dist_sd,
+      dist_sd_per_meter: noise.observed_dist_sd_per_meter,
+      angle_sd: noise.observed_angle_sd,
+    }
+  }
+
+  /// Create the noise model which is assumed by the filters
+  ///
+  /// When `filter.r` is given, its variance is assumed for both distance and angle. Otherwise the noise model of the simulated camera is assumed
+  ///
+  /// ## Arguments
+  /// * `config` - the configuration which has the noise model of the camera and the noise model assumed by the filter
+  ///
+  /// ## Returns
+  /// The noise model assumed by the filters
+  pub fn assumed(config: &Config) -> ObservationNoise {
+    match config.filter.r {
+      Some(r) => ObservationNoise { dist_sd: r.sqrt(), dist_sd_per_meter: 0.0, angle_sd: r.sqrt() },
+      None => ObservationNoise::new(&config.noise),
+    }
+  }
+
+  /// Get the standard deviations of the observation
+  ///
+  /// ## Arguments
+  /// * `distance` - the distance to the landmark
+  ///
+  /// ## Returns
+  /// The standard deviations(distance, angle)
+  pub fn sd(&self, distance: f64) -> na::Vector2<f64> {
+    na::Vector2::new(self.dist_sd + self.dist_sd_per_meter * distance.abs(), self.angle_sd)
+  }
+
+  /// Get the covariance matrix of observation noise
+  ///
+  /// ## Arguments
+  /// * `distance` - the distance to the landmark
+  ///
+  /// ## Returns
+  /// The diagonal covariance matrix of the observation(distance, angle)
+  pub fn covariance(&self, distance: f64) -> na::Matrix2<f64> {
+    na::Matrix2::from_diagonal(&self.sd(distance).map(|sd| sd * sd))
+  }
+}
 
 /// Calculate the observation equation of a camera
 ///
@@ -18,6 +99,23 @@ pub fn observe(landmark: &Point, current: &na::Vector3<f64>) -> na::Vector2<f64>
   na::Vector2::new(dist, angle)
 }
 
+/// Calculate the innovation between the measured vector and the observation equation
+///
+/// The angle of the innovation is wrapped between -pi and pi, so that the measurements near the back of the robot are compared correctly
+///
+/// ## Arguments
+/// * `measured` - the measured vector(distance, angle)
+/// * `landmark` - the observed landmark
+/// * `current` - the current pose of the simulated robot(x, y, theta)
+///
+/// ## Returns
+/// The innovation vector(distance, angle)
+pub fn innovation(measured: &na::Vector2<f64>, landmark: &Point, current: &na::Vector3<f64>) -> na::Vector2<f64> {
+  let mut yhat = measured - observe(landmark, current);
+  yhat[1] = utils::normalize_angle(yhat[1]);
+  yhat
+}
+
 /// Calculate the jacobian of the observation equation
 ///
 /// ## Arguments
@@ -25,10 +123,96 @@ pub fn observe(landmark: &Point, current: &na::Vector3<f64>) -> na::Vector2<f64>
 /// * `current` - the current pose of the simulated robot(x, y, theta)
 ///
 /// ## Returns
-/// The jacobian of the observation equation
-pub fn calc_h(landmark: &Point, current: &na::Vector3<f64>) -> na::Matrix2x3<f64> {
+/// The jacobian of the observation equation, or `None` when the pose sits on the landmark (the jacobian is not defined there)
+pub fn calc_h(landmark: &Point, current: &na::Vector3<f64>) -> Option<na::Matrix2x3<f64>> {
   let q = (landmark.x - current[0]).powf(2.0) + (landmark.y - current[1]).powf(2.0);
+  if q.is_nan() || q < f64::EPSILON {
+    return None;
+  }
+
+  Some(na::Matrix2x3::new((current[0] - landmark.x)/q.sqrt(), (current[1] - landmark.y)/q.sqrt(),  0.0,
+                     (landmark.y - current[1])/q,        (current[0] - landmark.x)/q,        -1.0))
+}
+
+
+/// Determine whether the landmark can be seen from the camera according to the sensor model
+///
+/// The landmark is visible when all of the following conditions are satisfied
+/// * the distance is between `min_range` and `max_range`
+/// * the angle from the robot's heading is within the half of `fov`
+/// * when `occlusion` is enabled, no other landmark (a disc of `landmark_radius`) stands on the line of sight
+///
+/// ## Arguments
+/// * `landmark` - the landmark to be observed
+/// * `others` - all landmarks in the environment which may hide the landmark (the landmark itself can be included)
+/// * `current` - the current pose of the simulated robot(x, y, theta)
+/// * `config` - the sensor model of the camera
+///
+/// ## Returns
+/// `true` when the landmark can be seen from the camera
+pub fn is_visible(landmark: &Point, others: &[Point], current: &na::Vector3<f64>, config: &CameraConfig) -> bool {
+  let observed = observe(landmark, current);
+  let (dist, angle) = (observed[0], utils::normalize_angle(observed[1]));
+
+  if dist < config.min_range || config.max_range.is_some_and(|max_range| dist > max_range) {
+    return false;
+  }
+  if angle.abs() > config.fov / 2.0 {
+    return false;
+  }
+  if config.occlusion {
+    let origin = current.fixed_rows::<2>(0).into_owned();
+    let direction = (na::Vector2::new(landmark.x, landmark.y) - origin) / dist;
+    return !others.iter().any(|other| {
+      let v = na::Vector2::new(other.x, other.y) - origin;
+      let along = v.dot(&direction);
+      let across = (v - direction * along).norm();
+      along > 0.0 && along < dist - config.landmark_radius && across < config.landmark_radius
+    });
+  }
+  true
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn point(x: f64, y: f64) -> Point {
+    Point { x, y }
+  }
+
+  #[test]
+  fn landmark_is_visible_only_within_the_range() {
+    let config = CameraConfig { min_range: 0.5, max_range: Some(2.0), ..CameraConfig::default() };
+    let current = na::Vector3::new(1.0, 1.0, 0.0);
+    assert!(!is_visible(&point(1.3, 1.0), &[], &current, &config));
+    assert!(is_visible(&point(2.0, 1.0), &[], &current, &config));
+    assert!(is_visible(&point(1.0, 3.0), &[], &current, &config));
+    assert!(!is_visible(&point(3.5, 1.0), &[], &current, &config));
+    assert!(is_visible(&point(100.0, 1.0), &[], &current, &CameraConfig::default()));
+  }
+
+  #[test]
+  fn landmark_is_visible_only_within_the_field_of_view() {
+    let config = CameraConfig { fov: PI / 2.0, ..CameraConfig::default() };
+    let current = na::Vector3::new(0.0, 0.0, PI / 2.0);
+    assert!(is_visible(&point(0.0, 1.0), &[], &current, &config));
+    assert!(is_visible(&point(0.9, 1.0), &[], &current, &config));
+    assert!(!is_visible(&point(1.1, 1.0), &[], &current, &config));
+    assert!(!is_visible(&point(0.0, -1.0), &[], &current, &config));
+    // the angle is wrapped, so that the heading near pi sees the landmarks on both sides of the negative x axis
+    let current = na::Vector3::new(0.0, 0.0, PI - 0.1);
+    assert!(is_visible(&point(-1.0, -0.2), &[], &current, &config));
+  }
 
-  na::Matrix2x3::new((current[0] - landmark.x)/q.sqrt(), (current[1] - landmark.y)/q.sqrt(),  0.0,
-                     (landmark.y - current[1])/q,        (current[0] - landmark.x)/q,        -1.0)
+  #[test]
+  fn landmark_behind_another_landmark_is_occluded() {
+    let config = CameraConfig { occlusion: true, landmark_radius: 0.1, ..CameraConfig::default() };
+    let current = na::Vector3::new(0.0, 0.0, 0.0);
+    let landmarks = [point(1.0, 0.0), point(2.0, 0.05), point(2.0, 0.5)];
+    assert!(is_visible(&landmarks[0], &landmarks, &current, &config));
+    assert!(!is_visible(&landmarks[1], &landmarks, &current, &config));
+    assert!(is_visible(&landmarks[2], &landmarks, &current, &config));
+    assert!(is_visible(&landmarks[1], &landmarks, &current, &CameraConfig { occlusion: false, ..config }));
+  }
 }
@@ -1,7 +1,10 @@
-//! The `robot` module provides an state equation of a simulated robot and the jacobian of the state equation
+//! The `robot` module provides an state equation of a simulated robot, the jacobians of the state equation and the noise model of the motion
 
 extern crate nalgebra as na;
+use rand::rngs::StdRng;
+use rand_distr::{Normal, Distribution};
 
+use crate::config::{Config, NoiseConfig};
 use crate::utils;
 
 /// The default value of maximum linear acceleration
@@ -16,6 +19,8 @@ pub const MIN_V: f64 = -0.2;
 pub const MAX_OMEGA: f64 = 1.5;
 /// The default value of minimum angular velocity
 pub const MIN_OMEGA: f64 = -1.5;
+/// The default value of the radius of the robot which is used to detect the collision with the obstacles
+pub const RADIUS: f64 = 0.1;
 
 /// Calculate the state equation of a simulated robot
 ///
@@ -48,7 +53,136 @@ pub fn ideal_move(current: &na::Vector3<f64>, input: &na::Vector2<f64>, delta: f
 pub fn calc_f(current: &na::Vector3<f64>, input: &na::Vector2<f64>, delta: f64) -> na::Matrix3<f64> {
   let a = current[2] + input[1] * delta / 2.0;
 
-  na::Matrix3::new(1.0, 0.0, -1.0 * a.sin() * delta * input[0],
-                   0.0, 1.0,  1.0 * a.cos() * delta * input[0],
+  na::Matrix3::new(1.0, 0.0, -a.sin() * delta * input[0],
+                   0.0, 1.0,  a.cos() * delta * input[0],
                    0.0, 0.0,  1.0)
 }
+
+/// Calculate the jacobian of the state equation with respect to the input
+///
+/// ## Arguments
+/// * `current` - the current pose of the simulated robot(x, y, theta)
+/// * `input` - the input vector(linear velocity, angular velocity)
+/// * `delta` - time delta to next tick
+///
+/// ## Returns
+/// The jacobian of the state equation with respect to the input(linear velocity, angular velocity)
+pub fn calc_v(current: &na::Vector3<f64>, input: &na::Vector2<f64>, delta: f64) -> na::Matrix3x2<f64> {
+  let a = current[2] + input[1] * delta / 2.0;
+
+  na::Matrix3x2::new(a.cos() * delta, -a.sin() * delta * delta * input[0] / 2.0,
+                     a.sin() * delta,  a.cos() * delta * delta * input[0] / 2.0,
+                     0.0,              delta)
+}
+
+/// A struct which defines the gaussian noise model of the robot's motion
+///
+/// The velocity motion model perturbs the linear and angular velocities and adds a final rotation, whose variances grow with
+/// the squared velocities by the alpha parameters: `a1 * v^2 + a2 * omega^2`, `a3 * v^2 + a4 * omega^2` and `a5 * v^2 + a6 * omega^2`.
+/// In addition, the independent noise of the position and the direction is added in the world frame.
+/// The same model is used to simulate the noisy motion and to give the covariance matrix of process noise to the filters
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct MotionNoise {
+  /// the alpha parameters(a1, ..., a6) of the velocity motion model
+  pub alphas: [f64; 6],
+  /// the standard deviation of the position (x, y) in the world frame
+  pub xy_sd: f64,
+  /// the standard deviation of the direction (theta) in the world frame
+  pub theta_sd: f64,
+}
+
+impl MotionNoise {
+  /// Create the noise model which is used to simulate the robot
+  ///
+  /// ## Arguments
+  /// * `noise` - the parameters of the noise which is added to the simulated robot and camera
+  ///
+  /// ## Returns
+  /// The noise model of the robot's motion
+  pub fn new(noise: &NoiseConfig) -> MotionNoise {
+    MotionNoise {
+      alphas: noise.actual_alphas,
+      xy_sd: noise.actual_xy_sd,
+      theta_sd: noise.actual_theta_sd,
+    }
+  }
+
+  /// Create the noise model which is assumed by the filters
+  ///
+  /// When `filter.q` is given, its variance is assumed for every element of the pose regardless of the input. Otherwise the noise model of the simulated robot is assumed
+  ///
+  /// ## Arguments
+  /// * `config` - the configuration which has the noise model of the robot and the noise model assumed by the filter
+  ///
+  /// ## Returns
+  /// The noise model assumed by the filters
+  pub fn assumed(config: &Config) -> MotionNoise {
+    match config.filter.q {
+      Some(q) => MotionNoise { alphas: [0.0; 6], xy_sd: q.sqrt(), theta_sd: q.sqrt() },
+      None => MotionNoise::new(&config.noise),
+    }
+  }
+
+  /// Get the standard deviations of the velocities and the final rotation
+  ///
+  /// ## Arguments
+  /// * `input` - the input vector(linear velocity, angular velocity)
+  ///
+  /// ## Returns
+  /// The standard deviations(linear velocity, angular velocity, final rotation)
+  pub fn input_sd(&self, input: &na::Vector2<f64>) -> na::Vector3<f64> {
+    let (v2, omega2) = (input[0] * input[0], input[1] * input[1]);
+    let a = &self.alphas;
+    na::Vector3::new(
+      (a[0] * v2 + a[1] * omega2).sqrt(),
+      (a[2] * v2 + a[3] * omega2).sqrt(),
+      (a[4] * v2 + a[5] * omega2).sqrt(),
+    )
+  }
+
+  /// Get the covariance matrix of process noise
+  ///
+  /// The variances of the velocities are projected to the pose through the jacobian of the state equation with respect to the input
+  ///
+  /// ## Arguments
+  /// * `current` - the current pose of the robot(x, y, theta)
+  /// * `input` - the input vector(linear velocity, angular velocity)
+  /// * `delta` - time delta to next tick
+  ///
+  /// ## Returns
+  /// The covariance matrix of the pose(x, y, theta) at the next tick
+  pub fn covariance(&self, current: &na::Vector3<f64>, input: &na::Vector2<f64>, delta: f64) -> na::Matrix3<f64> {
+    let variance = self.input_sd(input).map(|sd| sd * sd);
+    let v = calc_v(current, input, delta);
+    let m = na::Matrix2::new(variance[0], 0.0,
+                             0.0,         variance[1]);
+    let world = na::Vector3::new(self.xy_sd * self.xy_sd, self.xy_sd * self.xy_sd, self.theta_sd * self.theta_sd);
+    v * m * v.transpose() + na::Matrix3::from_diagonal(&(world + na::Vector3::new(0.0, 0.0, variance[2] * delta * delta)))
+  }
+
+  /// Sample the pose at the next tick from the noise model
+  ///
+  /// ## Arguments
+  /// * `current` - the current pose of the robot(x, y, theta)
+  /// * `input` - the input vector(linear velocity, angular velocity)
+  /// * `delta` - time delta to next tick
+  /// * `rng` - the random number generator which generates the gaussian noise
+  ///
+  /// ## Returns
+  /// The noisy pose(x, y, theta) at the next tick
+  pub fn sample(&self, current: &na::Vector3<f64>, input: &na::Vector2<f64>, delta: f64, rng: &mut StdRng) -> na::Vector3<f64> {
+    let sd = self.input_sd(input);
+    let noisy_input = na::Vector2::new(
+      Normal::new(input[0], sd[0]).unwrap().sample(rng),
+      Normal::new(input[1], sd[1]).unwrap().sample(rng),
+    );
+    let gamma = Normal::new(0.0, sd[2]).unwrap().sample(rng);
+    let moved = ideal_move(current, &noisy_input, delta);
+    na::Vector3::new(
+      Normal::new(moved[0], self.xy_sd).unwrap().sample(rng),
+      Normal::new(moved[1], self.xy_sd).unwrap().sample(rng),
+      utils::normalize_angle(Normal::new(moved[2] + gamma * delta, self.theta_sd).unwrap().sample(rng)),
+    )
+  }
+}
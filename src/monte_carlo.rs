@@ -0,0 +1,363 @@
+//! The `monte_carlo` module executes a scenario many times with different seeds, and aggregates the metrics of the trials
+//!
+//! The trial `i` (i = 0, 1, ...) is executed by [timers::run_batch] with the seed `seed + i`, so that the whole experiment is reproducible.
+//! The trials are distributed to the worker threads, and a trial which fails or panics is counted as an error instead of aborting the experiment.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::map::LandmarkMap;
+use crate::metrics::MetricsSummary;
+use crate::simulation;
+use crate::timers;
+
+/// The default value of the maximum error (m) of the estimated position, beyond which the trial is counted as lost
+pub const LOST_POSITION_ERROR: f64 = 0.5;
+
+/// Execute the trials of the scenario in parallel and aggregate their results
+///
+/// ## Arguments
+/// * `name` - the name of the Agent (see [crate::agent::create_agent])
+/// * `map` - the landmark map which will be observed from robot
+/// * `config` - the configuration of the scenario and the experiment
+/// * `seed` - the seed of the first trial
+/// * `steps` - the number of steps of every trial
+///
+/// ## Returns
+/// The report of the experiment
+pub fn run(name: &str, map: &LandmarkMap, config: &Config, seed: u64, steps: u64) -> Report {
+  let trials = config.monte_carlo.trials.max(1);
+  let jobs = config.monte_carlo
+                   .jobs
+                   .or_else(|| thread::available_parallelism().ok().map(|n| n.get()))
+                   .unwrap_or(1)
+                   .clamp(1, trials);
+  let next = AtomicUsize::new(0);
+  let (sender, receiver) = mpsc::channel();
+
+  thread::scope(|scope| {
+    for _ in 0..jobs {
+      let (next, sender) = (&next, sender.clone());
+      scope.spawn(move || loop {
+        let i = next.fetch_add(1, Ordering::SeqCst);
+        if i >= trials {
+          break;
+        }
+        let trial = run_trial(name, map, config, seed.wrapping_add(i as u64), steps);
+        if let Some(error) = &trial.error {
+          eprintln!("trial {} (seed = {}) failed: {}", i, trial.seed, error);
+        }
+        if sender.send((i, trial)).is_err() {
+          break;
+        }
+      });
+    }
+  });
+  drop(sender);
+
+  let mut results = receiver.iter().collect::<Vec<_>>();
+  results.sort_by_key(|(i, _)| *i);
+  let results = results.into_iter().map(|(_, trial)| trial).collect::<Vec<_>>();
+  Report::new(seed, steps, results)
+}
+
+/// **\[private\]** Execute a trial and evaluate it
+///
+/// ## Returns
+/// The result of the trial, which has the error message when the simulation cannot be created or panics
+fn run_trial(name: &str, map: &LandmarkMap, config: &Config, seed: u64, steps: u64) -> Trial {
+  let executed = panic::catch_unwind(AssertUnwindSafe(|| {
+    simulation::create_simulation(name, map, config, seed).map(|simulation| timers::run_batch(simulation, steps))
+  }));
+  let summary = match executed {
+    Ok(Ok(summary)) => summary,
+    Ok(Err(e)) => return Trial::failed(seed, e),
+    Err(cause) => {
+      let message = cause.downcast_ref::<&str>()
+                         .map(|s| s.to_string())
+                         .or_else(|| cause.downcast_ref::<String>().cloned())
+                         .unwrap_or_else(|| "unknown panic".to_string());
+      return Trial::failed(seed, format!("panicked: {}", message));
+    },
+  };
+  Trial {
+    seed,
+    lost: summary.metrics.max_position_error > config.monte_carlo.lost_position_error || summary.metrics.max_position_error.is_nan(),
+    metrics: Some(summary.metrics),
+    landmark_rmse: summary.landmark_rmse,
+    rejected: summary.rejected,
+    failures: summary.failures,
+    divergences: summary.divergences,
+    collisions: summary.collisions,
+    tracking_rmse: summary.tracking_rmse,
+    max_tracking_error: summary.max_tracking_error,
+    error: None,
+  }
+}
+
+/// A struct which stores the result of a trial
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct Trial {
+  /// the seed of the trial
+  pub seed: u64,
+  /// the metrics of the estimator aggregated over the steps (`None` when the trial failed with an error)
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub metrics: Option<MetricsSummary>,
+  /// the root mean squared error of the landmarks estimated by SLAM against the actual landmark map
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub landmark_rmse: Option<f64>,
+  /// the total number of measurements rejected as outliers
+  pub rejected: usize,
+  /// the total number of measurements which could not be applied because of the numerical failure
+  pub failures: usize,
+  /// the number of steps at which the filter diverged and was recovered
+  pub divergences: usize,
+  /// the number of steps at which the actual robot collided with the obstacles
+  pub collisions: usize,
+  /// the root mean squared distance between the actual position and the ideal position
+  pub tracking_rmse: f64,
+  /// the maximum distance between the actual position and the ideal position
+  pub max_tracking_error: f64,
+  /// whether the maximum error of the estimated position exceeded `monte_carlo.lost_position_error`
+  pub lost: bool,
+  /// the error message when the trial could not be completed
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub error: Option<String>,
+}
+
+impl Trial {
+  /// **\[private\]** Create the result of the trial which could not be completed
+  fn failed(seed: u64, error: String) -> Trial {
+    Trial { seed, metrics: None, landmark_rmse: None, rejected: 0, failures: 0, divergences: 0, collisions: 0,
+            tracking_rmse: 0.0, max_tracking_error: 0.0, lost: false, error: Some(error) }
+  }
+}
+
+/// A struct which stores the number of the trials and the measurements which failed
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct Failures {
+  /// the number of trials which could not be completed
+  pub errors: usize,
+  /// the number of trials in which the filter diverged at least once
+  pub diverged: usize,
+  /// the number of trials in which the robot was lost
+  pub lost: usize,
+  /// the number of trials in which the actual robot collided with the obstacles at least once
+  pub collided: usize,
+  /// the total number of measurements rejected as outliers over all trials
+  pub rejected: usize,
+  /// the total number of measurements which could not be applied over all trials
+  pub failures: usize,
+}
+
+/// A struct which stores the statistics of a metric over the trials
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct Statistics {
+  /// the name of the metric
+  pub metric: String,
+  /// the number of trials which have the metric
+  pub count: usize,
+  /// the average
+  pub mean: f64,
+  /// the sample standard deviation
+  pub sd: f64,
+  /// the minimum value
+  pub min: f64,
+  /// the 5th percentile
+  pub p5: f64,
+  /// the 25th percentile
+  pub p25: f64,
+  /// the median
+  pub median: f64,
+  /// the 75th percentile
+  pub p75: f64,
+  /// the 95th percentile
+  pub p95: f64,
+  /// the maximum value
+  pub max: f64,
+}
+
+impl Statistics {
+  /// **\[private\]** Calculate the statistics of the values
+  ///
+  /// ## Returns
+  /// The statistics, or `None` when no finite value is given
+  fn new(metric: &str, values: &[f64]) -> Option<Statistics> {
+    let mut sorted = values.iter().cloned().filter(|v| v.is_finite()).collect::<Vec<_>>();
+    if sorted.is_empty() {
+      return None;
+    }
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let n = sorted.len() as f64;
+    let mean = sorted.iter().sum::<f64>() / n;
+    let variance = if sorted.len() > 1 { sorted.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0) } else { 0.0 };
+    Some(Statistics {
+      metric: metric.to_string(),
+      count: sorted.len(),
+      mean,
+      sd: variance.sqrt(),
+      min: sorted[0],
+      p5: percentile(&sorted, 5.0),
+      p25: percentile(&sorted, 25.0),
+      median: percentile(&sorted, 50.0),
+      p75: percentile(&sorted, 75.0),
+      p95: percentile(&sorted, 95.0),
+      max: sorted[sorted.len() - 1],
+    })
+  }
+}
+
+/// **\[private\]** Get the percentile of the sorted values by the linear interpolation between the closest ranks
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+  let rank = p / 100.0 * (sorted.len() - 1) as f64;
+  let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+  sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+}
+
+/// A struct which stores the report of the Monte Carlo experiment
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct Report {
+  /// the number of trials
+  pub trials: usize,
+  /// the number of steps of every trial
+  pub steps: u64,
+  /// the seed of the first trial
+  pub seed: u64,
+  /// the number of the trials and the measurements which failed
+  pub failures: Failures,
+  /// the statistics of every metric over the trials
+  pub statistics: Vec<Statistics>,
+  /// the results of the trials in the order of their seeds
+  pub results: Vec<Trial>,
+}
+
+impl Report {
+  /// **\[private\]** Aggregate the results of the trials
+  fn new(seed: u64, steps: u64, results: Vec<Trial>) -> Report {
+    let completed = results.iter().filter(|trial| trial.error.is_none()).collect::<Vec<_>>();
+    let collect = |f: &dyn Fn(&Trial) -> Option<f64>| completed.iter().filter_map(|trial| f(trial)).collect::<Vec<_>>();
+    let indicator = |condition: bool| if condition { 1.0 } else { 0.0 };
+    let metrics: Vec<(&str, Vec<f64>)> = vec![
+      ("position_rmse", collect(&|t| t.metrics.as_ref().map(|m| m.position_rmse))),
+      ("theta_rmse", collect(&|t| t.metrics.as_ref().map(|m| m.theta_rmse))),
+      ("max_position_error", collect(&|t| t.metrics.as_ref().map(|m| m.max_position_error))),
+      ("nees_mean", collect(&|t| t.metrics.as_ref().and_then(|m| m.nees_mean))),
+      ("nees_within_bounds", collect(&|t| t.metrics.as_ref().and_then(|m| m.nees_within_bounds))),
+      ("nis_mean", collect(&|t| t.metrics.as_ref().and_then(|m| m.nis_mean))),
+      ("nis_within_bounds", collect(&|t| t.metrics.as_ref().and_then(|m| m.nis_within_bounds))),
+      ("landmark_rmse", collect(&|t| t.landmark_rmse)),
+      ("rejected", collect(&|t| Some(t.rejected as f64))),
+      ("failures", collect(&|t| Some(t.failures as f64))),
+      ("divergences", collect(&|t| Some(t.divergences as f64))),
+      ("collisions", collect(&|t| Some(t.collisions as f64))),
+      ("tracking_rmse", collect(&|t| Some(t.tracking_rmse))),
+      ("max_tracking_error", collect(&|t| Some(t.max_tracking_error))),
+      // the rates of the failed trials are the averages of the indicators
+      ("diverged", collect(&|t| Some(indicator(t.divergences > 0)))),
+      ("lost", collect(&|t| Some(indicator(t.lost)))),
+      ("collided", collect(&|t| Some(indicator(t.collisions > 0)))),
+      ("error", results.iter().map(|t| indicator(t.error.is_some())).collect()),
+    ];
+    let statistics = metrics.iter()
+                            .filter_map(|(metric, values)| Statistics::new(metric, values))
+                            .collect();
+    let failures = Failures {
+      errors: results.len() - completed.len(),
+      diverged: completed.iter().filter(|t| t.divergences > 0).count(),
+      lost: completed.iter().filter(|t| t.lost).count(),
+      collided: completed.iter().filter(|t| t.collisions > 0).count(),
+      rejected: completed.iter().map(|t| t.rejected).sum(),
+      failures: completed.iter().map(|t| t.failures).sum(),
+    };
+    Report { trials: results.len(), steps, seed, failures, statistics, results }
+  }
+
+  /// Format the statistics of every metric as CSV
+  ///
+  /// The rates of the failed trials are given by the `mean` of the indicators `diverged`, `lost`, `collided` and `error`
+  ///
+  /// ## Returns
+  /// The CSV which has a header line and a line per metric
+  pub fn to_csv(&self) -> String {
+    let mut csv = String::from("metric,count,mean,sd,min,p5,p25,median,p75,p95,max\n");
+    for s in self.statistics.iter() {
+      csv.push_str(&format!("{},{},{},{},{},{},{},{},{},{},{}\n",
+                            s.metric, s.count, s.mean, s.sd, s.min, s.p5, s.p25, s.median, s.p75, s.p95, s.max));
+    }
+    csv
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::PlannerKind;
+  use crate::data::Point;
+
+  fn map() -> LandmarkMap {
+    LandmarkMap::from_points(&[Point { x: 1.0, y: 0.0 }, Point { x: 0.0, y: 1.0 }, Point { x: -1.0, y: 0.5 }]).unwrap()
+  }
+
+  /// The configuration of the experiment, whose planner is pure pursuit so that the trials finish quickly
+  fn config(trials: usize) -> Config {
+    let mut config = Config::default();
+    config.planner.kind = PlannerKind::PurePursuit;
+    config.monte_carlo.trials = trials;
+    config
+  }
+
+  #[test]
+  fn trials_with_the_same_seed_give_the_same_report() {
+    let mut config = config(3);
+    config.monte_carlo.jobs = Some(2);
+    let first = run("circular", &map(), &config, 11, 50);
+    let second = run("circular", &map(), &config, 11, 50);
+
+    assert_eq!(first.failures.errors, 0);
+    assert_eq!(first.results.iter().map(|t| t.seed).collect::<Vec<_>>(), vec![11, 12, 13]);
+    assert_eq!(serde_json::to_string(&first).unwrap(), serde_json::to_string(&second).unwrap());
+  }
+
+  #[test]
+  fn percentiles_are_interpolated_and_ordered() {
+    let statistics = Statistics::new("metric", &[5.0, 1.0, f64::NAN, 3.0, 2.0, 4.0]).unwrap();
+    assert_eq!(statistics.count, 5);
+    assert_eq!(statistics.mean, 3.0);
+    assert!((statistics.p5 - 1.2).abs() < 1e-12);
+    assert_eq!((statistics.p25, statistics.median, statistics.p75), (2.0, 3.0, 4.0));
+    assert!((statistics.p95 - 4.8).abs() < 1e-12);
+    assert!(Statistics::new("metric", &[f64::NAN]).is_none());
+
+    for s in run("circular", &map(), &config(4), 3, 30).statistics.iter() {
+      let ordered = [s.min, s.p5, s.p25, s.median, s.p75, s.p95, s.max];
+      assert!(ordered.windows(2).all(|pair| pair[0] <= pair[1]), "{} is not ordered: {:?}", s.metric, ordered);
+    }
+  }
+
+  #[test]
+  fn panicking_trial_is_counted_as_an_error() {
+    let mut config = config(2);
+    // the observation noise cannot be sampled with the standard deviation NaN, which panics in the simulation
+    config.camera.signature_sd = Some(f64::NAN);
+    let report = run("circular", &map(), &config, 1, 10);
+
+    assert_eq!(report.trials, 2);
+    assert_eq!(report.failures.errors, 2);
+    assert!(report.results.iter().all(|t| t.metrics.is_none() && t.error.as_ref().is_some_and(|e| e.starts_with("panicked"))));
+    let error = report.statistics.iter().find(|s| s.metric == "error").unwrap();
+    assert_eq!(error.mean, 1.0);
+  }
+}
@@ -0,0 +1,293 @@
+//! The `obstacles` module provides the obstacles of the environment which the robot should not collide with
+//!
+//! The obstacles are given by `scenario.obstacles` of the configuration file, and each of them is one of the following shapes.
+//!
+//! * circle: `{"type": "circle", "x": 0.5, "y": 0.5, "radius": 0.1}`
+//! * polygon: `{"type": "polygon", "points": [{"x": 0.0, "y": 0.0}, {"x": 0.2, "y": 0.0}, {"x": 0.2, "y": 0.2}]}`
+//! * occupancy grid: `{"type": "grid", "origin": {"x": -2.0, "y": -2.0}, "resolution": 0.1, "cells": [[0.0, 1.0], [0.0, 0.0]]}`.
+//!   `cells[row][column]` is the occupancy probability of the square cell whose lower-left corner is
+//!   `(origin.x + column * resolution, origin.y + row * resolution)`, and the cell is occupied when the probability is `threshold` (0.5 by default) or more
+
+use serde::{Deserialize, Serialize};
+
+extern crate nalgebra as na;
+
+use crate::data::Point;
+
+/// The default value of the occupancy probability, at or above which the cell of the occupancy grid is occupied
+pub const OCCUPIED_THRESHOLD: f64 = 0.5;
+
+/// **\[private\]** The default value of `threshold` of the occupancy grid which is used by serde
+fn occupied_threshold() -> f64 {
+  OCCUPIED_THRESHOLD
+}
+
+/// The obstacle of the environment
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Obstacle {
+  /// a circle whose center is (x, y)
+  Circle { x: f64, y: f64, radius: f64 },
+  /// a simple polygon whose vertices are given in order
+  Polygon { points: Vec<Point> },
+  /// an occupancy grid whose lower-left corner is `origin`
+  Grid {
+    /// the lower-left corner of the grid
+    origin: Point,
+    /// the length of the side of a cell
+    resolution: f64,
+    /// the occupancy probabilities of the cells (`cells[row][column]`, the row `0` is at the bottom)
+    cells: Vec<Vec<f64>>,
+    /// the occupancy probability, at or above which the cell is occupied
+    #[serde(default = "occupied_threshold")]
+    threshold: f64,
+  },
+}
+
+impl Obstacle {
+  /// Get the distance from the given point to this obstacle
+  ///
+  /// The occupancy grid scans all of its cells, so that its cost is proportional to the number of the cells (rows times columns).
+  /// [ObstacleMap::clearance] looks up the index of the occupied cells instead
+  ///
+  /// ## Arguments
+  /// * `point` - the position (x, y)
+  ///
+  /// ## Returns
+  /// The distance to the boundary of this obstacle, `0` when the point is inside it, or infinity when the occupancy grid has no occupied cell
+  pub fn distance(&self, point: &na::Vector2<f64>) -> f64 {
+    match self {
+      Obstacle::Circle { x, y, radius } => ((point - na::Vector2::new(*x, *y)).norm() - radius).max(0.0),
+      Obstacle::Polygon { points } => {
+        let vertices = points.iter().map(|p| na::Vector2::new(p.x, p.y)).collect::<Vec<_>>();
+        if contains(&vertices, point) {
+          return 0.0;
+        }
+        (0..vertices.len()).map(|i| segment_distance(&vertices[i], &vertices[(i + 1) % vertices.len()], point))
+                           .fold(f64::INFINITY, f64::min)
+      },
+      Obstacle::Grid { origin, resolution, cells, threshold } => {
+        cells.iter()
+             .enumerate()
+             .flat_map(|(row, columns)| columns.iter().enumerate().filter(|(_, p)| **p >= *threshold).map(move |(column, _)| (row, column)))
+             .map(|(row, column)| {
+               let min = na::Vector2::new(origin.x + column as f64 * resolution, origin.y + row as f64 * resolution);
+               let dx = (min[0] - point[0]).max(point[0] - min[0] - resolution).max(0.0);
+               let dy = (min[1] - point[1]).max(point[1] - min[1] - resolution).max(0.0);
+               dx.hypot(dy)
+             })
+             .fold(f64::INFINITY, f64::min)
+      },
+    }
+  }
+
+  /// **\[private\]** Validate the shape of this obstacle
+  fn validate(&self) -> Result<(), String> {
+    let finite = |values: &[f64]| values.iter().all(|v| v.is_finite());
+    match self {
+      Obstacle::Circle { x, y, radius } if !finite(&[*x, *y, *radius]) || *radius <= 0.0 =>
+        Err(format!("the circle obstacle at ({}, {}) has an invalid radius: {}", x, y, radius)),
+      Obstacle::Polygon { points } if points.len() < 3 || !points.iter().all(|p| finite(&[p.x, p.y])) =>
+        Err("the polygon obstacle requires at least 3 finite points".to_string()),
+      Obstacle::Grid { origin, resolution, .. } if !finite(&[origin.x, origin.y, *resolution]) || *resolution <= 0.0 =>
+        Err(format!("the grid obstacle has an invalid resolution: {}", resolution)),
+      _ => Ok(()),
+    }
+  }
+}
+
+/// **\[private\]** Whether the point is inside the polygon by the ray casting
+fn contains(vertices: &[na::Vector2<f64>], point: &na::Vector2<f64>) -> bool {
+  let mut inside = false;
+  for i in 0..vertices.len() {
+    let (a, b) = (&vertices[i], &vertices[(i + 1) % vertices.len()]);
+    if (a[1] > point[1]) != (b[1] > point[1]) && point[0] < a[0] + (point[1] - a[1]) / (b[1] - a[1]) * (b[0] - a[0]) {
+      inside = !inside;
+    }
+  }
+  inside
+}
+
+/// **\[private\]** Get the distance from the point to the line segment between `a` and `b`
+fn segment_distance(a: &na::Vector2<f64>, b: &na::Vector2<f64>, point: &na::Vector2<f64>) -> f64 {
+  let ab = b - a;
+  let t = if ab.norm_squared() > 0.0 { ((point - a).dot(&ab) / ab.norm_squared()).clamp(0.0, 1.0) } else { 0.0 };
+  (point - (a + ab * t)).norm()
+}
+
+/// **\[private\]** A struct which indexes the occupied cells of an occupancy grid by their rows
+///
+/// The distance to the grid visits the rows in the order of their distances from the point until the row is farther than the nearest cell found so far,
+/// and finds the nearest occupied cell in a row by the binary search, so that it does not scan all cells
+#[derive(Clone)]
+#[derive(Debug)]
+struct GridIndex {
+  /// the lower-left corner of the grid
+  origin: na::Vector2<f64>,
+  /// the length of the side of a cell
+  resolution: f64,
+  /// the sorted columns of the occupied cells in every row
+  rows: Vec<Vec<usize>>,
+}
+
+impl GridIndex {
+  /// Create a GridIndex instance of the occupied cells of the occupancy grid
+  fn new(origin: &Point, resolution: f64, cells: &[Vec<f64>], threshold: f64) -> GridIndex {
+    let rows = cells.iter()
+                    .map(|columns| columns.iter().enumerate().filter(|(_, p)| **p >= threshold).map(|(column, _)| column).collect())
+                    .collect();
+    GridIndex { origin: na::Vector2::new(origin.x, origin.y), resolution, rows }
+  }
+
+  /// Get the distance from the point to the nearest edge of the interval `[min, min + resolution]`, or `0` when the point is within it
+  fn interval_distance(&self, min: f64, point: f64) -> f64 {
+    (min - point).max(point - min - self.resolution).max(0.0)
+  }
+
+  /// Get the distance from the point to the nearest occupied cell, or infinity when the grid has no occupied cell
+  fn distance(&self, point: &na::Vector2<f64>) -> f64 {
+    let dy = |row: usize| self.interval_distance(self.origin[1] + row as f64 * self.resolution, point[1]);
+    let mut best = f64::INFINITY;
+    if self.rows.is_empty() {
+      return best;
+    }
+    // the rows below and above the point are visited alternately in the order of their distances
+    let start = ((point[1] - self.origin[1]) / self.resolution).floor().clamp(0.0, (self.rows.len() - 1) as f64) as usize;
+    let (mut below, mut above) = (start as isize, start + 1);
+    loop {
+      let below_dy = if below >= 0 { dy(below as usize) } else { f64::INFINITY };
+      let above_dy = if above < self.rows.len() { dy(above) } else { f64::INFINITY };
+      let row = if below_dy <= above_dy { below as usize } else { above };
+      if below_dy.min(above_dy) >= best {
+        return best;
+      }
+      best = best.min(self.row_distance(row, dy(row), point[0]));
+      if below_dy <= above_dy {
+        below -= 1;
+      } else {
+        above += 1;
+      }
+    }
+  }
+
+  /// Get the distance from the point to the nearest occupied cell in the row whose vertical distance from the point is `dy`
+  fn row_distance(&self, row: usize, dy: f64, x: f64) -> f64 {
+    let columns = &self.rows[row];
+    // the first occupied column whose right edge is not left of the point, and the column just before it are the candidates
+    let idx = columns.partition_point(|column| self.origin[0] + (column + 1) as f64 * self.resolution < x);
+    [idx.checked_sub(1), Some(idx)].iter()
+                                   .flatten()
+                                   .filter_map(|i| columns.get(*i))
+                                   .map(|column| self.interval_distance(self.origin[0] + *column as f64 * self.resolution, x).hypot(dy))
+                                   .fold(f64::INFINITY, f64::min)
+  }
+}
+
+/// A struct which stores the validated obstacles
+///
+/// The occupied cells of the occupancy grids are indexed when the map is created, so that the clearance does not scan all cells
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+#[derive(Serialize)]
+pub struct ObstacleMap {
+  /// **\[private\]** the obstacles of this map
+  obstacles: Vec<Obstacle>,
+  /// **\[private\]** the indexes of the occupancy grids in the order of the obstacles (`None` for the other shapes)
+  #[serde(skip)]
+  grids: Vec<Option<GridIndex>>,
+}
+
+impl ObstacleMap {
+  /// Create an ObstacleMap instance from the given obstacles
+  ///
+  /// ## Arguments
+  /// * `obstacles` - the obstacles of this map (the map may have no obstacle)
+  ///
+  /// ## Returns
+  /// An instance of ObstacleMap
+  ///
+  /// ## Errors
+  /// When the shape of an obstacle is invalid, this function returns Error
+  pub fn new(obstacles: Vec<Obstacle>) -> Result<ObstacleMap, String> {
+    for obstacle in obstacles.iter() {
+      obstacle.validate()?;
+    }
+    let grids = obstacles.iter()
+                         .map(|obstacle| match obstacle {
+                           Obstacle::Grid { origin, resolution, cells, threshold } => Some(GridIndex::new(origin, *resolution, cells, *threshold)),
+                           _ => None,
+                         })
+                         .collect();
+    Ok(ObstacleMap { obstacles, grids })
+  }
+
+  /// Whether this map has no obstacle
+  pub fn is_empty(&self) -> bool {
+    self.obstacles.is_empty()
+  }
+
+  /// Get the obstacles of this map
+  pub fn obstacles(&self) -> &[Obstacle] {
+    &self.obstacles
+  }
+
+  /// Get the clearance of the given point
+  ///
+  /// The distance to an occupancy grid is looked up from its index, whose cost grows with the number of rows within the clearance
+  /// and the logarithm of the number of columns
+  ///
+  /// ## Arguments
+  /// * `point` - the position (x, y)
+  ///
+  /// ## Returns
+  /// The distance to the nearest obstacle, `0` when the point is inside an obstacle, or infinity when this map has no obstacle
+  pub fn clearance(&self, point: &na::Vector2<f64>) -> f64 {
+    self.obstacles.iter()
+                  .zip(self.grids.iter())
+                  .map(|(obstacle, grid)| match grid {
+                    Some(grid) => grid.distance(point),
+                    None => obstacle.distance(point),
+                  })
+                  .fold(f64::INFINITY, f64::min)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn clearance_of_the_grid_matches_the_scan_of_all_cells() {
+    // a sparse grid whose occupied cells are scattered by a simple linear congruential sequence
+    let mut seed = 12345_u64;
+    let cells = (0..40).map(|_| (0..30).map(|_| {
+                                         seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                                         if seed >> 60 == 0 { 0.9 } else { 0.1 }
+                                       })
+                                       .collect::<Vec<_>>())
+                       .collect::<Vec<_>>();
+    let grid = Obstacle::Grid { origin: Point { x: -1.5, y: -2.0 }, resolution: 0.1, cells, threshold: OCCUPIED_THRESHOLD };
+    let map = ObstacleMap::new(vec![grid.clone()]).unwrap();
+
+    // the points inside, on the edges of and outside the grid
+    for i in 0..50 {
+      for j in 0..60 {
+        let point = na::Vector2::new(-3.0 + i as f64 * 0.1 + 0.013, -4.0 + j as f64 * 0.1 + 0.007);
+        assert!((map.clearance(&point) - grid.distance(&point)).abs() < 1e-12, "{:?}", point);
+      }
+    }
+    let corner = na::Vector2::new(-1.5, -2.0);
+    assert!((map.clearance(&corner) - grid.distance(&corner)).abs() < 1e-12);
+  }
+
+  #[test]
+  fn clearance_of_an_empty_grid_is_infinite() {
+    let empty = Obstacle::Grid { origin: Point { x: 0.0, y: 0.0 }, resolution: 0.1, cells: vec![vec![0.0; 3]; 2], threshold: OCCUPIED_THRESHOLD };
+    let circle = Obstacle::Circle { x: 1.0, y: 0.0, radius: 0.5 };
+    assert!(ObstacleMap::new(vec![empty.clone()]).unwrap().clearance(&na::Vector2::zeros()).is_infinite());
+    assert!((ObstacleMap::new(vec![empty, circle]).unwrap().clearance(&na::Vector2::new(-1.0, 0.0)) - 1.5).abs() < 1e-12);
+  }
+}
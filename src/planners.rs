@@ -1,3 +1,110 @@
 //! The `planners` module provides submodule(s) to plan a local path for the simulated robot
+//!
+//! Every planner implements the [Planner] trait, so that the planners can be compared on the same agents and routes
 
 pub mod dwa_wo_obstacle;
+pub mod dwa_with_obstacle;
+pub mod dwa;
+pub mod pure_pursuit;
+pub mod stanley;
+pub mod pid;
+pub mod mpc;
+
+use std::fmt;
+
+extern crate nalgebra as na;
+
+use crate::agent::Agent;
+use crate::config::{Config, PlannerKind};
+use crate::obstacles::ObstacleMap;
+
+/// Create a concrete Planner specified by the planner configuration such as DWA, pure pursuit, Stanley, PID and MPC, and returns it as Planner trait object
+///
+/// ## Arguments
+/// * `config` - the configuration which has the kind of planner and its parameters
+/// * `obstacles` - the obstacles which the planner avoids (only the Dynamic Window Approach planner avoids them)
+///
+/// ## Returns
+/// This function returns a instanciated Planner as a Planner trait object
+pub fn create_planner(config: &Config, obstacles: &ObstacleMap) -> Box<dyn Planner> {
+  match config.planner.kind {
+    PlannerKind::Dwa => Box::new(dwa::DWA::new(config.dwa.clone(), obstacles.clone())),
+    PlannerKind::PurePursuit => Box::new(pure_pursuit::PurePursuit::new(config.planner.pure_pursuit.clone())),
+    PlannerKind::Stanley => Box::new(stanley::Stanley::new(config.planner.stanley.clone())),
+    PlannerKind::Pid => Box::new(pid::PID::new(config.planner.pid.clone())),
+    PlannerKind::Mpc => Box::new(mpc::MPC::new(config.planner.mpc.clone())),
+  }
+}
+
+/// A trait which plans the input of the simulated robot toward the goal of next tick
+pub trait Planner: Send + fmt::Debug {
+
+  /// Get the input vector (linear velocity, angular velocity) of next tick
+  ///
+  /// ## Arguments
+  /// * `agent` - the agent instance of this simulated robot, which gives the limits of the velocities and the ideal poses
+  /// * `current` - the current pose(x, y, theta) of this simulated robot
+  /// * `destination` - the goal pose(x, y, theta) of next tick
+  /// * `current_input` - the current input vector(linear velocity, angular velocity) of this simulated robot
+  /// * `t` - elapsed time (sec) from the start of this simulation
+  /// * `delta` - time delta to next tick
+  ///
+  /// ## Returns
+  /// * The input vector(linear velocity, angular velocity) of next tick
+  fn get_input(&mut self, agent: &dyn Agent, current: &na::Vector3<f64>, destination: &na::Vector3<f64>,
+               current_input: &na::Vector2<f64>, t: f64, delta: f64) -> na::Vector2<f64>;
+}
+
+/// Clamp the input vector into the Dynamic Window, which is limited by the velocities and the accelarations of the simulated robot
+///
+/// ## Arguments
+/// * `agent` - the agent instance of this simulated robot
+/// * `current` - the current pose(x, y, theta) of this simulated robot
+/// * `current_input` - the current input vector(linear velocity, angular velocity) of this simulated robot
+/// * `input` - the input vector(linear velocity, angular velocity) to be clamped
+/// * `delta` - time delta to next tick
+///
+/// ## Returns
+/// The nearest input vector(linear velocity, angular velocity) in the Dynamic Window
+pub(crate) fn clamp_input(agent: &dyn Agent, current: &na::Vector3<f64>, current_input: &na::Vector2<f64>, input: &na::Vector2<f64>,
+                          delta: f64) -> na::Vector2<f64> {
+  let (max_lin_acc, max_ang_acc) = agent.get_max_accelarations(current);
+  let (max_v, min_v) = agent.get_linear_velocities(current);
+  let (max_omega, min_omega) = agent.get_angular_velocities(current);
+
+  // the accelarations take precedence over the velocities, since the robot cannot leave the velocities outside the limits in a tick
+  let v = input[0].min(max_v.min(current_input[0] + max_lin_acc * delta))
+                  .max(min_v.max(current_input[0] - max_lin_acc * delta).min(current_input[0] + max_lin_acc * delta));
+  let omega = input[1].min(max_omega.min(current_input[1] + max_ang_acc * delta))
+                      .max(min_omega.max(current_input[1] - max_ang_acc * delta).min(current_input[1] + max_ang_acc * delta));
+  na::Vector2::new(v, omega)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::agent;
+  use crate::data::Point;
+  use crate::map::LandmarkMap;
+
+  fn agent() -> Box<dyn Agent> {
+    let config = Config::default();
+    let map = LandmarkMap::from_points(&[Point { x: 0.0, y: 5.0 }]).unwrap();
+    agent::create_agent("circular", map, config.robot, config.noise, config.camera).unwrap()
+  }
+
+  #[test]
+  fn input_is_clamped_by_the_accelarations_and_the_velocities() {
+    let agent = agent();
+    let current = na::Vector3::new(0.0, 0.0, 0.0);
+    let clamp = |current_input: (f64, f64), input: (f64, f64), delta: f64| {
+      clamp_input(agent.as_ref(), &current, &na::Vector2::new(current_input.0, current_input.1), &na::Vector2::new(input.0, input.1), delta)
+    };
+    assert_eq!(clamp((1.0, 0.0), (5.0, -5.0), 0.1), na::Vector2::new(1.25, -0.25));
+    assert_eq!(clamp((1.95, 1.45), (5.0, 5.0), 0.1), na::Vector2::new(2.0, 1.5));
+    assert_eq!(clamp((0.5, 0.2), (0.6, 0.1), 0.1), na::Vector2::new(0.6, 0.1));
+    // the robot outside the velocity limits decelerates as fast as it can
+    assert_eq!(clamp((3.0, 0.0), (0.0, 0.0), 0.1), na::Vector2::new(2.75, 0.0));
+    assert_eq!(clamp((1.0, 0.5), (0.0, 0.0), 0.0), na::Vector2::new(1.0, 0.5));
+  }
+}
@@ -0,0 +1,46 @@
+//! The `dwa` module provides the Dynamic Window Approach planner as a [Planner]
+//!
+//! The planner avoids the obstacles by [crate::planners::dwa_with_obstacle] when the scenario has obstacles,
+//! otherwise it plans by [crate::planners::dwa_wo_obstacle]
+
+extern crate nalgebra as na;
+
+use crate::agent::Agent;
+use crate::config::DwaConfig;
+use crate::obstacles::ObstacleMap;
+use crate::planners::{Planner, dwa_wo_obstacle, dwa_with_obstacle};
+
+/// A struct to plan the input of a robot by using Dynamic Window Approach
+#[derive(Debug)]
+pub struct DWA {
+  /// **\[private\]** the parameters of this planner
+  config: DwaConfig,
+  /// **\[private\]** the obstacles which the robot should not collide with
+  obstacles: ObstacleMap,
+}
+
+impl DWA {
+  /// Create a DWA instance
+  ///
+  /// ## Arguments
+  /// * `config` - the parameters of this planner
+  /// * `obstacles` - the obstacles which the robot should not collide with
+  ///
+  /// ## Returns
+  /// An instance of DWA
+  pub fn new(config: DwaConfig, obstacles: ObstacleMap) -> DWA {
+    DWA { config, obstacles }
+  }
+}
+
+/// The implementation for Planner trait
+impl Planner for DWA {
+  fn get_input(&mut self, agent: &dyn Agent, current: &na::Vector3<f64>, destination: &na::Vector3<f64>,
+               current_input: &na::Vector2<f64>, _: f64, delta: f64) -> na::Vector2<f64> {
+    if self.obstacles.is_empty() {
+      dwa_wo_obstacle::get_input(agent, current, destination, current_input, delta, &self.config)
+    } else {
+      dwa_with_obstacle::get_input(agent, current, destination, current_input, delta, &self.config, &self.obstacles)
+    }
+  }
+}
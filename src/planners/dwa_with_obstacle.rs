@@ -0,0 +1,253 @@
+//! The `dwa_with_obstacle` module provides the `get_input` function which plans a local path in a "[Dynamic Window Approach](https://www.ri.cmu.edu/pub_files/pub1/fox_dieter_1997_1/fox_dieter_1997_1.pdf)" like way
+//! avoiding the obstacles
+//!
+//! Every candidate input of the Dynamic Window is rolled out over the prediction horizon in the same way as [crate::planners::dwa_wo_obstacle],
+//! and the candidate is discarded when the robot cannot stop before the collision with the obstacles (the braking distance exceeds the free distance along the rollout).
+//! The admissible candidates are evaluated by the same evaluation functions as [crate::planners::dwa_wo_obstacle] and the "clearance" evaluation function.
+//!
+//! The clearance is calculated at every pose of every rollout, so that the cost of a tick is the number of candidates times the number of poses of the rollout
+//! times the cost of [ObstacleMap::clearance], which looks up the index of the occupied cells built when the map is created
+
+extern crate nalgebra as na;
+extern crate itertools;
+
+use crate::agent::Agent;
+use crate::config::DwaConfig;
+use crate::obstacles::ObstacleMap;
+use crate::planners::dwa_wo_obstacle::{get_window, rollout, eval_heading, eval_velocity, eval_distance, eval_theta, eval_path};
+use crate::utils;
+
+/// The default value of the margin (m) which the planner keeps from the obstacles in addition to the radius of the robot
+pub const SAFETY_MARGIN: f64 = 0.05;
+/// The default value of the clearance (m), beyond which the "clearance" evaluation function does not distinguish the candidates
+pub const MAX_CLEARANCE: f64 = 0.5;
+/// The default value of the weight of the "clearance" evaluation function
+pub const CLEARANCE_GAIN: f64 = 0.5;
+
+/// Get the input vector (linear velocity, angular velocity) of next tick which avoids the obstacles
+///
+/// When no candidate is admissible, the candidate which decelerates most is chosen so that the robot stops as soon as possible
+///
+/// ## Arguments
+/// * `agent` - the agent instance of this simulated robot
+/// * `current` - the current pose(x, y, theta) of this simulated robot
+/// * `destination` - the goal pose(x, y, theta)
+/// * `current_input` - the current input vector(linear velocity, angular velocity) of this simulated robot
+/// * `delta` - time delta to next tick
+/// * `config` - the parameters of this planner
+/// * `obstacles` - the obstacles which the robot should not collide with
+///
+/// ## Returns
+/// * The input vector(linear velocity, angular velocity) of next tick
+pub fn get_input(agent: &dyn Agent, current: &na::Vector3<f64>, destination: &na::Vector3<f64>,
+                 current_input: &na::Vector2<f64>, delta: f64, config: &DwaConfig, obstacles: &ObstacleMap) -> na::Vector2<f64> {
+  let max_accelarations = agent.get_max_accelarations(current);
+  let linear_velocities = agent.get_linear_velocities(current);
+  let angular_velocities = agent.get_angular_velocities(current);
+  let robot_config = agent.get_robot_config();
+  let (max_v, radius) = (robot_config.max_v, robot_config.radius + config.safety_margin);
+
+  let (v_range, omega_range) = get_window(max_accelarations, linear_velocities, angular_velocities, current_input, delta, config);
+
+  let mut input_vec: Vec<na::Vector2<f64>> = Vec::new();
+  let mut heading_vec: Vec<f64> = Vec::new();
+  let mut velocity_vec: Vec<f64> = Vec::new();
+  let mut distance_vec: Vec<f64> = Vec::new();
+  let mut theta_vec: Vec<f64> = Vec::new();
+  let mut path_distance_vec: Vec<f64> = Vec::new();
+  let mut path_heading_vec: Vec<f64> = Vec::new();
+  let mut clearance_vec: Vec<f64> = Vec::new();
+  let mut slowest: Option<na::Vector2<f64>> = None;
+
+  for (v, omega) in itertools::iproduct!(v_range, omega_range) {
+    let input = na::Vector2::new(v, omega);
+    if slowest.map_or(true, |slowest| (v.abs(), omega.abs()) < (slowest[0].abs(), slowest[1].abs())) {
+      slowest = Some(input);
+    }
+
+    let trajectory = rollout(current, &input, delta, config);
+    let (clearance, free_distance) = check_collision(current, &trajectory, radius, obstacles);
+    if !is_admissible(&input, free_distance, max_accelarations.0) {
+      continue;
+    }
+    input_vec.push(input);
+
+    let last = &trajectory[trajectory.len() - 1];
+    heading_vec.push(eval_heading(last, destination));
+    velocity_vec.push(eval_velocity(&input, max_v));
+    distance_vec.push(eval_distance(last, destination));
+    theta_vec.push(eval_theta(last, destination));
+    path_distance_vec.push(eval_path(&trajectory, destination, eval_distance));
+    path_heading_vec.push(eval_path(&trajectory, destination, eval_heading));
+    clearance_vec.push(eval_clearance(clearance, config.max_clearance));
+  }
+
+  if input_vec.is_empty() {
+    return slowest.unwrap_or(*current_input);
+  }
+
+  let heading_vec = utils::normalize_min_max(heading_vec);
+  let velocity_vec = utils::normalize_min_max(velocity_vec);
+  let distance_vec = utils::normalize_min_max(distance_vec);
+  let theta_vec = utils::normalize_min_max(theta_vec);
+  let path_distance_vec = utils::normalize_min_max(path_distance_vec);
+  let path_heading_vec = utils::normalize_min_max(path_heading_vec);
+  let clearance_vec = utils::normalize_min_max(clearance_vec);
+
+  let gains = if (current.fixed_rows::<2>(0) - destination.fixed_rows::<2>(0)).norm_squared() < config.distance_squared_threshold {
+    &config.near
+  } else {
+    &config.far
+  };
+  let min_idx = itertools::izip!(&heading_vec, &velocity_vec, &distance_vec, &theta_vec, &path_distance_vec, &path_heading_vec, &clearance_vec)
+                .map(|(heading, velocity, distance, theta, path_distance, path_heading, clearance)|
+                  gains.error_angle * heading + gains.velocity * velocity + gains.distance * distance + gains.theta * theta
+                  + gains.path_distance * path_distance + gains.path_heading * path_heading + config.clearance_gain * clearance
+                )
+                .enumerate()
+                .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+                .map(|(idx, _)| idx)
+                .unwrap();
+
+  input_vec[min_idx]
+}
+
+/// **\[private\]** Check the collision with the obstacles along the rollout
+///
+/// A pose collides when its clearance is less than the radius of the robot (including the safety margin) and also less than the clearance of the current pose,
+/// so that the robot which is already too close to an obstacle can still move away from it
+///
+/// ## Arguments
+/// * `current` - the current pose(x, y, theta) of this simulated robot
+/// * `trajectory` - the poses of the rollout by the input vector being considered
+/// * `radius` - the radius of the robot including the safety margin
+/// * `obstacles` - the obstacles which the robot should not collide with
+///
+/// ## Returns
+/// Tuple of (the minimum clearance along the rollout, the distance which the robot travels before the collision (infinity when it does not collide))
+fn check_collision(current: &na::Vector3<f64>, trajectory: &[na::Vector3<f64>], radius: f64, obstacles: &ObstacleMap) -> (f64, f64) {
+  let limit = radius.min(obstacles.clearance(&current.fixed_rows::<2>(0).into_owned()));
+  let mut previous = current;
+  let mut travelled = 0.0;
+  let mut min_clearance = f64::INFINITY;
+  for pose in trajectory.iter() {
+    let clearance = obstacles.clearance(&pose.fixed_rows::<2>(0).into_owned());
+    min_clearance = min_clearance.min(clearance);
+    if clearance < limit {
+      return (min_clearance, travelled);
+    }
+    travelled += (pose.fixed_rows::<2>(0) - previous.fixed_rows::<2>(0)).norm();
+    previous = pose;
+  }
+  (min_clearance, f64::INFINITY)
+}
+
+/// **\[private\]** Whether the robot can stop before the collision by the maximum linear deceleration
+///
+/// ## Arguments
+/// * `input` - the input vector(linear velocity, angular velocity) being considered
+/// * `free_distance` - the distance which the robot travels before the collision
+/// * `max_lin_acc` - the maximum linear accelaration
+///
+/// ## Returns
+/// `true` when the braking distance does not exceed the free distance
+/// (the robot which cannot decelerate is admissible only when it stands still or does not collide)
+fn is_admissible(input: &na::Vector2<f64>, free_distance: f64, max_lin_acc: f64) -> bool {
+  if max_lin_acc <= 0.0 {
+    return input[0] == 0.0 || free_distance.is_infinite();
+  }
+  input[0].powi(2) / (2.0 * max_lin_acc) <= free_distance
+}
+
+/// **\[private\]** Evaluate how far the trajectory of the simulated robot keeps away from the obstacles
+///
+/// ## Arguments
+/// * `clearance` - the minimum clearance along the trajectory moved by the input vector being considered
+/// * `max_clearance` - the clearance beyond which the candidates are not distinguished
+///
+/// ## Returns
+/// The value subtracting the clearance (saturated at `max_clearance`) from `max_clearance`
+fn eval_clearance(clearance: f64, max_clearance: f64) -> f64 {
+  max_clearance - clearance.min(max_clearance)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::agent;
+  use crate::config::Config;
+  use crate::data::Point;
+  use crate::map::LandmarkMap;
+  use crate::obstacles::Obstacle;
+
+  fn agent() -> Box<dyn Agent> {
+    let config = Config::default();
+    let map = LandmarkMap::from_points(&[Point { x: 0.0, y: 5.0 }]).unwrap();
+    agent::create_agent("circular", map, config.robot, config.noise, config.camera).unwrap()
+  }
+
+  /// The parameters whose horizon of 0.5 sec makes the rollouts at about 1 m/s reach the obstacle ahead
+  fn dwa_config() -> DwaConfig {
+    DwaConfig { horizon: 0.5, v_resolution: 0.05, omega_resolution: 0.05, ..DwaConfig::default() }
+  }
+
+  fn obstacle_ahead(x: f64) -> ObstacleMap {
+    ObstacleMap::new(vec![Obstacle::Circle { x, y: 0.0, radius: 0.2 }]).unwrap()
+  }
+
+  #[test]
+  fn braking_distance_is_compared_with_the_free_distance() {
+    let input = na::Vector2::new(1.0, 0.0);
+    assert!(is_admissible(&input, 0.2, 2.5));
+    assert!(!is_admissible(&input, 0.19, 2.5));
+    assert!(is_admissible(&input, f64::INFINITY, 0.0));
+    assert!(!is_admissible(&input, 10.0, 0.0));
+    assert!(is_admissible(&na::Vector2::new(0.0, 0.5), 0.0, 0.0));
+  }
+
+  #[test]
+  fn free_distance_is_travelled_until_the_first_colliding_pose() {
+    let current = na::Vector3::new(0.0, 0.0, 0.0);
+    let trajectory = (1..=5).map(|i| na::Vector3::new(0.1 * i as f64, 0.0, 0.0)).collect::<Vec<_>>();
+    // the pose at 0.3 m is the first one whose clearance (0.1 m) is less than the radius (0.15 m)
+    let (clearance, free_distance) = check_collision(&current, &trajectory, 0.15, &obstacle_ahead(0.6));
+    assert!((free_distance - 0.2).abs() < 1e-12);
+    assert!((clearance - 0.1).abs() < 1e-12);
+
+    let (_, free_distance) = check_collision(&current, &trajectory, 0.15, &ObstacleMap::new(vec![]).unwrap());
+    assert!(free_distance.is_infinite());
+  }
+
+  #[test]
+  fn chosen_input_can_stop_before_the_obstacle_ahead() {
+    let agent = agent();
+    let (current, destination) = (na::Vector3::new(0.0, 0.0, 0.0), na::Vector3::new(3.0, 0.0, 0.0));
+    let current_input = na::Vector2::new(1.0, 0.0);
+    let (config, delta) = (dwa_config(), 0.1);
+    let radius = agent.get_robot_config().radius + config.safety_margin;
+    let max_lin_acc = agent.get_robot_config().max_lin_acc;
+
+    let free = get_input(agent.as_ref(), &current, &destination, &current_input, delta, &config, &ObstacleMap::new(vec![]).unwrap());
+    let obstacles = obstacle_ahead(0.6);
+    let input = get_input(agent.as_ref(), &current, &destination, &current_input, delta, &config, &obstacles);
+
+    let (_, free_distance) = check_collision(&current, &rollout(&current, &input, delta, &config), radius, &obstacles);
+    assert!(input[0].powi(2) / (2.0 * max_lin_acc) <= free_distance, "{} cannot stop within {}", input[0], free_distance);
+    assert!(input[0] < free[0], "the obstacle does not slow down the robot: {} >= {}", input[0], free[0]);
+  }
+
+  #[test]
+  fn robot_decelerates_most_when_every_candidate_collides() {
+    let agent = agent();
+    let (current, destination) = (na::Vector3::new(0.0, 0.0, 0.0), na::Vector3::new(3.0, 0.0, 0.0));
+    let current_input = na::Vector2::new(1.0, 0.0);
+    let (config, delta) = (dwa_config(), 0.1);
+
+    // every forward candidate collides at its first pose, so that none of them can stop before the collision
+    let input = get_input(agent.as_ref(), &current, &destination, &current_input, delta, &config, &obstacle_ahead(0.4));
+    let (v_range, _) = get_window(agent.get_max_accelarations(&current), agent.get_linear_velocities(&current),
+                                  agent.get_angular_velocities(&current), &current_input, delta, &config);
+    assert_eq!(input[0], v_range[0]);
+    assert!(input[1].abs() < 1e-9);
+  }
+}
@@ -1,88 +1,117 @@
 //! The `dwa_wo_obstacle` module provides the `get_input` function which plans a local path in a "[Dynamic Window Approach](https://www.ri.cmu.edu/pub_files/pub1/fox_dieter_1997_1/fox_dieter_1997_1.pdf)" like way
 //!
-//! <u>This module does not implement the obstacle avoidance feature</u>
+//! <u>This module does not implement the obstacle avoidance feature</u> (see [crate::planners::dwa_with_obstacle])
 
 extern crate nalgebra as na;
 extern crate itertools;
 
 use crate::agent::Agent;
+use crate::config::DwaConfig;
 use crate::models::robot;
 use crate::utils;
 
-/// **\[private\]** The linear velocity resolution to sampling the Dynamic Window
-const V_RESOLUTION: f64 = 0.01;
-/// **\[private\]** The angular velocity resolution to sampling the Dynamic Window
-const OMEGA_RESOLUTION: f64 = 0.01;
-
-/// **\[private\]** The weight of the "target heading" evaluation function used when the goal is "far away"
-const FAR_ERROR_ANGLE_GAIN: f64 = 1.0;
-/// **\[private\]** The weight of the "velocity" evaluation function used when the goal is "far away"
-const FAR_VELOCITY_GAIN: f64 = 0.5;
-/// **\[private\]** The weight of the "distance" evaluation function used when the goal is "far away"
-const FAR_DISTANCE_GAIN: f64 = 0.8;
-/// **\[private\]** The weight of the "theta" evaluation function used when the goal is "far away"
-const FAR_THETA_GAIN: f64 = 0.01;
-
-/// **\[private\]** The weight of the "target heading" evaluation function used when the goal is "near by"
-const NEAR_ERROR_ANGLE_GAIN: f64 = 1.0;
-/// **\[private\]** The weight of the "velocity" evaluation function used when the goal is "near by"
-const NEAR_VELOCITY_GAIN: f64 = 0.01;
-/// **\[private\]** The weight of the "distance" evaluation function used when the goal is "near by"
-const NEAR_DISTANCE_GAIN: f64 = 0.8;
-/// **\[private\]** The weight of the "theta" evaluation function used when the goal is "near by"
-const NEAR_THETA_GAIN: f64 = 0.8;
-
-/// **\[private\]** The threshold to determine whether the goal is "far away" or "near by"
-const DISTANCE_SQUARED_THRESHOLD: f64 = 0.01;
+/// The default value of the linear velocity resolution to sampling the Dynamic Window
+pub const V_RESOLUTION: f64 = 0.01;
+/// The default value of the angular velocity resolution to sampling the Dynamic Window
+pub const OMEGA_RESOLUTION: f64 = 0.01;
+
+/// The default value of the weight of the "target heading" evaluation function used when the goal is "far away"
+pub const FAR_ERROR_ANGLE_GAIN: f64 = 1.0;
+/// The default value of the weight of the "velocity" evaluation function used when the goal is "far away"
+pub const FAR_VELOCITY_GAIN: f64 = 0.5;
+/// The default value of the weight of the "distance" evaluation function used when the goal is "far away"
+pub const FAR_DISTANCE_GAIN: f64 = 0.8;
+/// The default value of the weight of the "theta" evaluation function used when the goal is "far away"
+pub const FAR_THETA_GAIN: f64 = 0.01;
+
+/// The default value of the weight of the "target heading" evaluation function used when the goal is "near by"
+pub const NEAR_ERROR_ANGLE_GAIN: f64 = 1.0;
+/// The default value of the weight of the "velocity" evaluation function used when the goal is "near by"
+pub const NEAR_VELOCITY_GAIN: f64 = 0.01;
+/// The default value of the weight of the "distance" evaluation function used when the goal is "near by"
+pub const NEAR_DISTANCE_GAIN: f64 = 0.8;
+/// The default value of the weight of the "theta" evaluation function used when the goal is "near by"
+pub const NEAR_THETA_GAIN: f64 = 0.8;
+
+/// The default value of the weight of the "path distance" evaluation function used when the goal is "far away"
+pub const FAR_PATH_DISTANCE_GAIN: f64 = 0.0;
+/// The default value of the weight of the "path heading" evaluation function used when the goal is "far away"
+pub const FAR_PATH_HEADING_GAIN: f64 = 0.0;
+/// The default value of the weight of the "path distance" evaluation function used when the goal is "near by"
+pub const NEAR_PATH_DISTANCE_GAIN: f64 = 0.0;
+/// The default value of the weight of the "path heading" evaluation function used when the goal is "near by"
+pub const NEAR_PATH_HEADING_GAIN: f64 = 0.0;
+
+/// The default value of the threshold to determine whether the goal is "far away" or "near by"
+pub const DISTANCE_SQUARED_THRESHOLD: f64 = 0.01;
+
+/// The default value of the prediction horizon (sec) over which every candidate input is rolled out
+pub const HORIZON: f64 = 0.2;
+/// The default value of the number of sub-steps of the rollout per time delta
+pub const SUBSTEPS: usize = 1;
 
 /// Get the input vector (linear velocity, angular velocity) of next tick based on the goal of next tick and the current pose and input vector of the simulated robot
 ///
+/// Every candidate input is rolled out over the prediction horizon, and it is evaluated by the final pose of the rollout
+/// and by the averages of the distance and heading toward the goal along the rollout (the path integrals divided by the horizon)
+///
 /// ## Arguments
 /// * `agent` - the agent instance of this simulated robot
 /// * `current` - the current pose(x, y, theta) of this simulated robot
 /// * `destination` - the goal pose(x, y, theta)
 /// * `current_input` - the current input vector(linear velocity, angular velocity) of this simulated robot
 /// * `delta` - time delta to next tick
+/// * `config` - the parameters of this planner
 ///
 /// ## Returns
 /// * The input vector(linear velocity, angular velocity) of next tick
-pub fn get_input(agent: &Box<dyn Agent>, current: &na::Vector3<f64>, destination: &na::Vector3<f64>,
-                 current_input: &na::Vector2<f64>, delta: f64) -> na::Vector2<f64> {
+pub fn get_input(agent: &dyn Agent, current: &na::Vector3<f64>, destination: &na::Vector3<f64>,
+                 current_input: &na::Vector2<f64>, delta: f64, config: &DwaConfig) -> na::Vector2<f64> {
   let max_accelarations = agent.get_max_accelarations(current);
   let linear_velocities = agent.get_linear_velocities(current);
   let angular_velocities = agent.get_angular_velocities(current);
+  let max_v = agent.get_robot_config().max_v;
 
-  let (v_range, omega_range) = get_window(max_accelarations, linear_velocities, angular_velocities, current_input, delta);
+  let (v_range, omega_range) = get_window(max_accelarations, linear_velocities, angular_velocities, current_input, delta, config);
 
   let mut input_vec: Vec<na::Vector2<f64>> = Vec::new();
   let mut heading_vec: Vec<f64> = Vec::new();
   let mut velocity_vec: Vec<f64> = Vec::new();
   let mut distance_vec: Vec<f64> = Vec::new();
   let mut theta_vec: Vec<f64> = Vec::new();
+  let mut path_distance_vec: Vec<f64> = Vec::new();
+  let mut path_heading_vec: Vec<f64> = Vec::new();
 
   for (v, omega) in itertools::iproduct!(v_range, omega_range) {
     let input = na::Vector2::new(v, omega);
     input_vec.push(input);
 
-    let next = robot::ideal_move(current, &input, delta);
-    heading_vec.push(eval_heading(&next, destination));
-    velocity_vec.push(eval_velocity(&input));
-    distance_vec.push(eval_distance(&next, destination));
-    theta_vec.push(eval_theta(&next, destination));
+    let trajectory = rollout(current, &input, delta, config);
+    let last = &trajectory[trajectory.len() - 1];
+    heading_vec.push(eval_heading(last, destination));
+    velocity_vec.push(eval_velocity(&input, max_v));
+    distance_vec.push(eval_distance(last, destination));
+    theta_vec.push(eval_theta(last, destination));
+    path_distance_vec.push(eval_path(&trajectory, destination, eval_distance));
+    path_heading_vec.push(eval_path(&trajectory, destination, eval_heading));
   }
 
   let heading_vec = utils::normalize_min_max(heading_vec);
   let velocity_vec = utils::normalize_min_max(velocity_vec);
   let distance_vec = utils::normalize_min_max(distance_vec);
   let theta_vec = utils::normalize_min_max(theta_vec);
+  let path_distance_vec = utils::normalize_min_max(path_distance_vec);
+  let path_heading_vec = utils::normalize_min_max(path_heading_vec);
 
-  let min_idx = itertools::izip!(&heading_vec, &velocity_vec, &distance_vec, &theta_vec)
-                .map(|(heading, velocity, distance, theta)|
-                  if (current.fixed_rows::<2>(0) - destination.fixed_rows::<2>(0)).norm_squared() < DISTANCE_SQUARED_THRESHOLD {
-                    NEAR_ERROR_ANGLE_GAIN * heading + NEAR_VELOCITY_GAIN * velocity + NEAR_DISTANCE_GAIN * distance + NEAR_THETA_GAIN * theta
-                  } else {
-                    FAR_ERROR_ANGLE_GAIN * heading + FAR_VELOCITY_GAIN * velocity + FAR_DISTANCE_GAIN * distance + FAR_THETA_GAIN * theta
-                  }
+  let gains = if (current.fixed_rows::<2>(0) - destination.fixed_rows::<2>(0)).norm_squared() < config.distance_squared_threshold {
+    &config.near
+  } else {
+    &config.far
+  };
+  let min_idx = itertools::izip!(&heading_vec, &velocity_vec, &distance_vec, &theta_vec, &path_distance_vec, &path_heading_vec)
+                .map(|(heading, velocity, distance, theta, path_distance, path_heading)|
+                  gains.error_angle * heading + gains.velocity * velocity + gains.distance * distance + gains.theta * theta
+                  + gains.path_distance * path_distance + gains.path_heading * path_heading
                 )
                 .enumerate()
                 .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
@@ -92,7 +121,33 @@ pub fn get_input(agent: &Box<dyn Agent>, current: &na::Vector3<f64>, destination
   input_vec[min_idx]
 }
 
-/// **\[private\]** Get the sampled values of linear and angular velocities in the next Dynamic Window
+/// Roll out the candidate input over the prediction horizon
+///
+/// The horizon is divided into `substeps` sub-steps per time delta (or into `substeps` sub-steps when the time delta is not positive),
+/// and the pose is moved by [robot::ideal_move] at every sub-step
+///
+/// ## Arguments
+/// * `current` - the current pose(x, y, theta) of this simulated robot
+/// * `input` - the input vector(linear velocity, angular velocity) being considered
+/// * `delta` - time delta to next tick
+/// * `config` - the parameters of this planner
+///
+/// ## Returns
+/// The poses(x, y, theta) at the end of every sub-step, which has at least one pose
+pub(crate) fn rollout(current: &na::Vector3<f64>, input: &na::Vector2<f64>, delta: f64, config: &DwaConfig) -> Vec<na::Vector3<f64>> {
+  let horizon = config.horizon.max(delta);
+  let deltas = if delta > 0.0 { (horizon / delta - 1e-9).ceil().max(1.0) as usize } else { 1 };
+  let steps = deltas * config.substeps.max(1);
+  let dt = horizon / steps as f64;
+  let mut pose = *current;
+  (0..steps).map(|_| {
+               pose = robot::ideal_move(&pose, input, dt);
+               pose
+             })
+             .collect()
+}
+
+/// Get the sampled values of linear and angular velocities in the next Dynamic Window
 ///
 /// ## Arguments
 /// * `max_accelarations` - the tuple of (maximum linear accelaration, maximum angular accelaration)
@@ -100,68 +155,129 @@ pub fn get_input(agent: &Box<dyn Agent>, current: &na::Vector3<f64>, destination
 /// * `angular_velocities` - the tuple of (maximum angular velocity, minimum angular velocity)
 /// * `current_input` - the current input vector(linear velocity, angular velocity) of this simulated robot
 /// * `delta` - time delta to next tick
+/// * `config` - the parameters of this planner
 ///
 /// ## Returns
 /// The tuple of sampled values (Vec of sampled linear velocities, Vec of sampled angular velocities)
-fn get_window(max_accelarations: (f64, f64), linear_velocities: (f64, f64), angular_velocities: (f64, f64), current_input: &na::Vector2<f64>, delta: f64) -> (Vec<f64>, Vec<f64>) {
+pub(crate) fn get_window(max_accelarations: (f64, f64), linear_velocities: (f64, f64), angular_velocities: (f64, f64), current_input: &na::Vector2<f64>, delta: f64,
+              config: &DwaConfig) -> (Vec<f64>, Vec<f64>) {
   let delta_v = max_accelarations.0 * delta;
   let delta_omega = max_accelarations.1 * delta;
 
-  let min_v = linear_velocities.1.max(current_input[0] - delta_v);
-  let max_v = linear_velocities.0.min(current_input[0] + delta_v);
-  let min_omega = angular_velocities.1.max(current_input[1] - delta_omega);
-  let max_omega = angular_velocities.0.min(current_input[1] + delta_omega);
+  // when the velocity limits get narrower than the reachable velocities (e.g. near a waypoint), the window collapses to the reachable bound
+  let min_v = linear_velocities.1.max(current_input[0] - delta_v).min(current_input[0] + delta_v);
+  let max_v = linear_velocities.0.min(current_input[0] + delta_v).max(current_input[0] - delta_v);
+  let min_omega = angular_velocities.1.max(current_input[1] - delta_omega).min(current_input[1] + delta_omega);
+  let max_omega = angular_velocities.0.min(current_input[1] + delta_omega).max(current_input[1] - delta_omega);
 
   (
-    utils::step_by_float(min_v, max_v, V_RESOLUTION),
-    utils::step_by_float(min_omega, max_omega, OMEGA_RESOLUTION),
+    utils::step_by_float(min_v, max_v, config.v_resolution),
+    utils::step_by_float(min_omega, max_omega, config.omega_resolution),
   )
 }
 
-/// **\[private\]** Evaluate whether the simulated robot are heading toward the goal
+/// Evaluate whether the simulated robot are heading toward the goal
 ///
 /// ## Arguments
-/// * `next` -　the ideal pose(x, y, theta) moved by the input vector being considered
+/// * `next` -　the final pose(x, y, theta) of the rollout by the input vector being considered
 /// * `destination` - the goal pose(x, y, theta)
 ///
 /// ## Returns
 /// The angle between the direction of the robot moved by the input vector considered and the position of the goal
-fn eval_heading(next: &na::Vector3<f64>, destination: &na::Vector3<f64>) -> f64 {
+pub(crate) fn eval_heading(next: &na::Vector3<f64>, destination: &na::Vector3<f64>) -> f64 {
   let angle = (destination[1] - next[1]).atan2(destination[0] - next[0]);
   utils::normalize_angle(angle - next[2]).abs()
 }
 
-/// **\[private\]** Evaluate the simulated robot's speed toward the goal
+/// Evaluate the simulated robot's speed toward the goal
 ///
 /// ## Arguments
 /// * `input` - the input vector(linear velocity, angular velocity) being considered
+/// * `max_v` - the maximum linear velocity of the simulated robot
 ///
 /// ## Returns
 /// The value subtracting input linear velocity from maximum linear velocity
-fn eval_velocity(input: &na::Vector2<f64>) -> f64 {
-  robot::MAX_V - input[0]
+pub(crate) fn eval_velocity(input: &na::Vector2<f64>, max_v: f64) -> f64 {
+  max_v - input[0]
 }
 
-/// **\[private\]** Evaluate the distance between the simulated robot and the goal
+/// Evaluate the distance between the simulated robot and the goal
 ///
 /// ## Arguments
-/// * `next` -　the ideal pose(x, y, theta) moved by the input vector being considered
+/// * `next` -　the final pose(x, y, theta) of the rollout by the input vector being considered
 /// * `destination` - the goal pose(x, y, theta)
 ///
 /// ## Returns
 /// The distance between the position of the robot moved by the input vector considered and the position of the goal
-fn eval_distance(next: &na::Vector3<f64>, destination: &na::Vector3<f64>) -> f64 {
+pub(crate) fn eval_distance(next: &na::Vector3<f64>, destination: &na::Vector3<f64>) -> f64 {
   (next.fixed_rows::<2>(0) - destination.fixed_rows::<2>(0)).norm_squared().sqrt()
 }
 
-/// **\[private\]** Evaluate whether the direction of the simulated robot and the direction of the goal match
+/// Evaluate the path integral of the given evaluation function along the rollout
 ///
 /// ## Arguments
-/// * `next` -　the ideal pose(x, y, theta) moved by the input vector being considered
+/// * `trajectory` - the poses of the rollout at the regular intervals
+/// * `destination` - the goal pose(x, y, theta)
+/// * `eval` - the evaluation function of a pose
+///
+/// ## Returns
+/// The average of the evaluation function over the poses of the rollout, which is the path integral divided by the horizon
+pub(crate) fn eval_path(trajectory: &[na::Vector3<f64>], destination: &na::Vector3<f64>, eval: fn(&na::Vector3<f64>, &na::Vector3<f64>) -> f64) -> f64 {
+  trajectory.iter().map(|pose| eval(pose, destination)).sum::<f64>() / trajectory.len() as f64
+}
+
+/// Evaluate whether the direction of the simulated robot and the direction of the goal match
+///
+/// ## Arguments
+/// * `next` -　the final pose(x, y, theta) of the rollout by the input vector being considered
 /// * `destination` - the goal pose(x, y, theta)
 ///
 /// ## Returns
 /// The absolute value between the direction of the robot moved by the input vector considered and the direction of the goal
-fn eval_theta(next: &na::Vector3<f64>, destination: &na::Vector3<f64>) -> f64 {
+pub(crate) fn eval_theta(next: &na::Vector3<f64>, destination: &na::Vector3<f64>) -> f64 {
   utils::normalize_angle(next[2] - destination[2]).abs()
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn rollout_moves_by_substeps_over_the_horizon() {
+    let current = na::Vector3::new(1.0, 2.0, 0.5);
+    let input = na::Vector2::new(0.8, 0.6);
+    let config = DwaConfig { horizon: 0.3, substeps: 4, ..DwaConfig::default() };
+    let trajectory = rollout(&current, &input, 0.1, &config);
+
+    // 3 time deltas divided into 4 sub-steps of 0.025 sec
+    assert_eq!(trajectory.len(), 12);
+    let mut pose = current;
+    for expected in trajectory.iter() {
+      pose = robot::ideal_move(&pose, &input, 0.025);
+      assert!((expected - pose).norm() < 1e-12);
+    }
+    assert!((trajectory[11][2] - (0.5 + 0.6 * 0.3)).abs() < 1e-12);
+  }
+
+  #[test]
+  fn rollout_covers_at_least_one_time_delta() {
+    let current = na::Vector3::new(0.0, 0.0, 0.0);
+    let input = na::Vector2::new(1.0, 0.0);
+    let config = DwaConfig { horizon: 0.05, substeps: 2, ..DwaConfig::default() };
+    let trajectory = rollout(&current, &input, 0.1, &config);
+    assert_eq!(trajectory.len(), 2);
+    assert!((trajectory[1][0] - 0.1).abs() < 1e-12);
+  }
+
+  #[test]
+  fn rollout_divides_the_horizon_by_substeps_when_the_time_delta_is_not_positive() {
+    let current = na::Vector3::new(0.0, 0.0, 0.0);
+    let input = na::Vector2::new(1.0, 0.0);
+    let config = DwaConfig { horizon: 0.2, substeps: 3, ..DwaConfig::default() };
+    for delta in [0.0, -0.1] {
+      let trajectory = rollout(&current, &input, delta, &config);
+      assert_eq!(trajectory.len(), 3);
+      assert!((trajectory[2][0] - 0.2).abs() < 1e-12);
+    }
+  }
+}
@@ -0,0 +1,239 @@
+//! The `mpc` module provides the "Model Predictive Control" planner
+//!
+//! The planner optimizes the sequence of the inputs over the prediction horizon, which moves the robot by [robot::ideal_move] at every time delta,
+//! so that the predicted poses track the reference trajectory, i.e. the ideal poses of the agent at the end of every time delta.
+//! Unlike the one-step Dynamic Window Approach, the robot anticipates the upcoming change of the reference trajectory such as the corners of the square route.
+//!
+//! Every input of the sequence is limited by the velocities and the accelarations which the agent returns for the predicted pose,
+//! where the accelarations are measured from the preceding input (the current input of the robot for the first one).
+//! Only the first input of the optimized sequence is applied, and the rest of the sequence is used as the initial guess of the optimization at next tick.
+//! The sequence is optimized by the projected gradient descent with the numerical gradient and the backtracking line search.
+//! When the time delta is not positive, the robot keeps the current input
+
+extern crate nalgebra as na;
+
+use crate::agent::Agent;
+use crate::config::MpcConfig;
+use crate::models::robot;
+use crate::planners::{self, Planner};
+use crate::utils;
+
+/// The default value of the prediction horizon (sec)
+pub const HORIZON: f64 = 1.0;
+/// The default value of the number of iterations of the gradient descent at every tick
+pub const ITERATIONS: usize = 20;
+/// The default value of the weight of the squared distance between the predicted position and the reference position
+pub const POSITION_WEIGHT: f64 = 1.0;
+/// The default value of the weight of the squared angle error between the predicted direction and the reference direction
+pub const THETA_WEIGHT: f64 = 0.01;
+/// The default value of the weight of the squared inputs
+pub const INPUT_WEIGHT: f64 = 0.001;
+/// The default value of the weight of the squared changes of the inputs between the consecutive time deltas
+pub const RATE_WEIGHT: f64 = 0.001;
+
+/// **\[private\]** The perturbation of the input to calculate the numerical gradient
+const EPSILON: f64 = 1e-6;
+/// **\[private\]** The initial step size of the backtracking line search
+const INITIAL_STEP: f64 = 1.0;
+/// **\[private\]** The maximum number of the halvings of the step size in the backtracking line search
+const MAX_HALVINGS: usize = 20;
+
+/// A struct to plan the input of a robot by using Model Predictive Control
+#[derive(Debug)]
+pub struct MPC {
+  /// **\[private\]** the parameters of this planner
+  config: MpcConfig,
+  /// **\[private\]** the optimized sequence of the inputs at the previous tick, whose first input has been applied
+  inputs: Vec<na::Vector2<f64>>,
+}
+
+impl MPC {
+  /// Create a MPC instance
+  ///
+  /// ## Arguments
+  /// * `config` - the parameters of this planner
+  ///
+  /// ## Returns
+  /// An instance of MPC
+  pub fn new(config: MpcConfig) -> MPC {
+    MPC { config, inputs: Vec::new() }
+  }
+}
+
+/// The implementation for Planner trait
+impl Planner for MPC {
+  fn get_input(&mut self, agent: &dyn Agent, current: &na::Vector3<f64>, destination: &na::Vector3<f64>,
+               current_input: &na::Vector2<f64>, t: f64, delta: f64) -> na::Vector2<f64> {
+    if delta <= 0.0 {
+      return *current_input;
+    }
+    let steps = (self.config.horizon.max(delta) / delta - 1e-9).ceil().max(1.0) as usize;
+    let references = (0..steps).map(|k| if k == 0 { *destination } else { agent.get_ideal(current, t + k as f64 * delta) })
+                               .collect::<Vec<_>>();
+    // the current input is the initial input of the accelaration limits
+    let initial = *current_input;
+
+    // the previous sequence shifted by a tick is the initial guess
+    let mut inputs = self.inputs.iter().skip(1).copied().collect::<Vec<_>>();
+    inputs.resize(steps, inputs.last().copied().unwrap_or(initial));
+    project(agent, current, &initial, &mut inputs, delta);
+
+    let cost = |inputs: &[na::Vector2<f64>]| eval_cost(current, &initial, inputs, &references, delta, &self.config);
+    let mut step = INITIAL_STEP;
+    let mut value = cost(&inputs);
+    for _ in 0..self.config.iterations {
+      let gradient = get_gradient(&inputs, value, &cost);
+      let mut improved = false;
+      for _ in 0..MAX_HALVINGS {
+        let mut candidate = inputs.iter().zip(gradient.iter()).map(|(input, g)| input - step * g).collect::<Vec<_>>();
+        project(agent, current, &initial, &mut candidate, delta);
+        let candidate_value = cost(&candidate);
+        if candidate_value < value {
+          inputs = candidate;
+          value = candidate_value;
+          improved = true;
+          break;
+        }
+        step /= 2.0;
+      }
+      if !improved {
+        break;
+      }
+      step *= 2.0;
+    }
+
+    self.inputs = inputs;
+    self.inputs[0]
+  }
+}
+
+/// **\[private\]** Project the sequence of the inputs onto the limits of the velocities and the accelarations
+///
+/// Every input is clamped into the Dynamic Window of the predicted pose where it is applied,
+/// which is limited by the velocities and the accelarations that the agent returns for the pose and by the preceding input
+///
+/// ## Arguments
+/// * `agent` - the agent instance of this simulated robot
+/// * `current` - the current pose(x, y, theta) of this simulated robot
+/// * `initial` - the input vector(linear velocity, angular velocity) which precedes the sequence
+/// * `inputs` - the sequence of the input vectors(linear velocity, angular velocity) to be projected
+/// * `delta` - time delta of every input
+fn project(agent: &dyn Agent, current: &na::Vector3<f64>, initial: &na::Vector2<f64>, inputs: &mut [na::Vector2<f64>], delta: f64) {
+  let mut pose = *current;
+  let mut previous = *initial;
+  for input in inputs.iter_mut() {
+    *input = planners::clamp_input(agent, &pose, &previous, input, delta);
+    pose = robot::ideal_move(&pose, input, delta);
+    previous = *input;
+  }
+}
+
+/// **\[private\]** Evaluate the sequence of the inputs by moving the robot from the current pose
+///
+/// ## Arguments
+/// * `current` - the current pose(x, y, theta) of this simulated robot
+/// * `initial` - the input vector(linear velocity, angular velocity) which precedes the sequence
+/// * `inputs` - the sequence of the input vectors(linear velocity, angular velocity) being considered
+/// * `references` - the reference poses(x, y, theta) at the end of every input
+/// * `delta` - time delta of every input
+/// * `config` - the parameters of this planner
+///
+/// ## Returns
+/// The sum of the weighted squared errors of the predicted poses against the reference poses, the weighted squared inputs and their weighted squared changes
+fn eval_cost(current: &na::Vector3<f64>, initial: &na::Vector2<f64>, inputs: &[na::Vector2<f64>], references: &[na::Vector3<f64>], delta: f64,
+             config: &MpcConfig) -> f64 {
+  let mut pose = *current;
+  let mut previous = initial;
+  inputs.iter()
+        .zip(references.iter())
+        .map(|(input, reference)| {
+          pose = robot::ideal_move(&pose, input, delta);
+          let change = input - previous;
+          previous = input;
+          config.position_weight * (pose.fixed_rows::<2>(0) - reference.fixed_rows::<2>(0)).norm_squared()
+          + config.theta_weight * utils::normalize_angle(pose[2] - reference[2]).powi(2)
+          + config.input_weight * input.norm_squared()
+          + config.rate_weight * change.norm_squared()
+        })
+        .sum()
+}
+
+/// **\[private\]** Get the numerical gradient of the cost by the forward difference
+///
+/// ## Arguments
+/// * `inputs` - the sequence of the input vectors(linear velocity, angular velocity)
+/// * `value` - the cost of `inputs`
+/// * `cost` - the cost function of the sequence of the inputs
+///
+/// ## Returns
+/// The partial derivatives of the cost by every element of the inputs
+fn get_gradient(inputs: &[na::Vector2<f64>], value: f64, cost: &dyn Fn(&[na::Vector2<f64>]) -> f64) -> Vec<na::Vector2<f64>> {
+  let mut perturbed = inputs.to_vec();
+  (0..inputs.len()).map(|k| {
+                     let mut gradient = na::Vector2::zeros();
+                     for i in 0..2 {
+                       perturbed[k][i] += EPSILON;
+                       gradient[i] = (cost(&perturbed) - value) / EPSILON;
+                       perturbed[k][i] = inputs[k][i];
+                     }
+                     gradient
+                   })
+                   .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::agent;
+  use crate::config::Config;
+  use crate::data::Point;
+  use crate::map::LandmarkMap;
+
+  fn agent() -> Box<dyn Agent> {
+    let config = Config::default();
+    let map = LandmarkMap::from_points(&[Point { x: 0.0, y: 5.0 }]).unwrap();
+    agent::create_agent("circular", map, config.robot, config.noise, config.camera).unwrap()
+  }
+
+  #[test]
+  fn robot_tracks_the_reference_trajectory_within_the_limits() {
+    let agent = agent();
+    let robot = agent.get_robot_config().clone();
+    let mut mpc = MPC::new(MpcConfig::default());
+    let delta = 0.1;
+    let (mut pose, mut input) = (agent.get_ideal(&na::Vector3::zeros(), 0.0), na::Vector2::new(0.0, 0.0));
+    let mut max_error: f64 = 0.0;
+    for i in 1..=100 {
+      let t = i as f64 * delta;
+      let destination = agent.get_ideal(&pose, t);
+      let next = mpc.get_input(agent.as_ref(), &pose, &destination, &input, t - delta, delta);
+
+      assert!(next[0] <= robot.max_v && next[0] >= robot.min_v && next[1] <= robot.max_omega && next[1] >= robot.min_omega);
+      assert!((next[0] - input[0]).abs() <= robot.max_lin_acc * delta + 1e-12);
+      assert!((next[1] - input[1]).abs() <= robot.max_ang_acc * delta + 1e-12);
+      let mut previous = input;
+      for planned in mpc.inputs.iter() {
+        assert!((planned[0] - previous[0]).abs() <= robot.max_lin_acc * delta + 1e-12);
+        previous = *planned;
+      }
+
+      input = next;
+      pose = robot::ideal_move(&pose, &input, delta);
+      if i > 20 {
+        max_error = max_error.max((pose - destination).fixed_rows::<2>(0).norm());
+      }
+    }
+    assert!(max_error < 0.05, "the robot does not track the reference: {}", max_error);
+  }
+
+  #[test]
+  fn robot_keeps_the_current_input_when_the_time_delta_is_not_positive() {
+    let agent = agent();
+    let mut mpc = MPC::new(MpcConfig::default());
+    let (current, input) = (na::Vector3::new(1.0, 0.0, 0.0), na::Vector2::new(0.3, -0.2));
+    for delta in [0.0, -0.1] {
+      assert_eq!(mpc.get_input(agent.as_ref(), &current, &na::Vector3::new(2.0, 1.0, 0.0), &input, 1.0, delta), input);
+    }
+    assert!(mpc.inputs.is_empty());
+  }
+}
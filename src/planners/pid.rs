@@ -0,0 +1,101 @@
+//! The `pid` module provides the planner which controls the distance and the heading toward the goal by PID controllers
+//!
+//! The linear velocity is controlled by the distance to the goal projected on the direction of the robot,
+//! and the angular velocity is controlled by the angle between the direction of the robot and the position of the goal.
+//! When the robot arrives at the goal, the angular velocity is controlled by the angle error against the direction of the goal instead
+
+extern crate nalgebra as na;
+
+use crate::agent::Agent;
+use crate::config::{PidConfig, PidGains};
+use crate::planners::{self, Planner};
+use crate::utils;
+
+/// The default value of the proportional gain of the linear velocity
+pub const LINEAR_P: f64 = 2.0;
+/// The default value of the integral gain of the linear velocity
+pub const LINEAR_I: f64 = 0.0;
+/// The default value of the derivative gain of the linear velocity
+pub const LINEAR_D: f64 = 0.0;
+/// The default value of the proportional gain of the angular velocity
+pub const ANGULAR_P: f64 = 2.0;
+/// The default value of the integral gain of the angular velocity
+pub const ANGULAR_I: f64 = 0.0;
+/// The default value of the derivative gain of the angular velocity
+pub const ANGULAR_D: f64 = 0.05;
+/// The default value of the maximum absolute value of the integrated errors, which prevents the windup of the integral terms
+pub const INTEGRAL_LIMIT: f64 = 1.0;
+/// The default value of the distance (m) to the goal, within which the heading is controlled toward the direction of the goal
+pub const ARRIVAL_DISTANCE: f64 = 0.02;
+
+/// A struct to plan the input of a robot by using the PID controllers of the distance and the heading
+#[derive(Debug)]
+pub struct PID {
+  /// **\[private\]** the parameters of this planner
+  config: PidConfig,
+  /// **\[private\]** the controller of the linear velocity
+  linear: Controller,
+  /// **\[private\]** the controller of the angular velocity
+  angular: Controller,
+}
+
+impl PID {
+  /// Create a PID instance
+  ///
+  /// ## Arguments
+  /// * `config` - the parameters of this planner
+  ///
+  /// ## Returns
+  /// An instance of PID
+  pub fn new(config: PidConfig) -> PID {
+    PID { config, linear: Controller::default(), angular: Controller::default() }
+  }
+}
+
+/// The implementation for Planner trait
+impl Planner for PID {
+  fn get_input(&mut self, agent: &dyn Agent, current: &na::Vector3<f64>, destination: &na::Vector3<f64>,
+               current_input: &na::Vector2<f64>, _: f64, delta: f64) -> na::Vector2<f64> {
+    let diff = destination.fixed_rows::<2>(0) - current.fixed_rows::<2>(0);
+    let alpha = utils::normalize_angle(diff[1].atan2(diff[0]) - current[2]);
+    let (distance_error, heading_error) = if diff.norm() < self.config.arrival_distance {
+      (0.0, utils::normalize_angle(destination[2] - current[2]))
+    } else {
+      (diff.norm() * alpha.cos(), alpha)
+    };
+    let input = na::Vector2::new(
+      self.linear.control(distance_error, delta, &self.config.linear, self.config.integral_limit),
+      self.angular.control(heading_error, delta, &self.config.angular, self.config.integral_limit),
+    );
+    planners::clamp_input(agent, current, current_input, &input, delta)
+  }
+}
+
+/// **\[private\]** A struct which stores the state of a PID controller
+#[derive(Debug)]
+#[derive(Default)]
+struct Controller {
+  /// **\[private\]** the integrated error
+  integral: f64,
+  /// **\[private\]** the error at the previous tick (`None` at the first tick)
+  previous: Option<f64>,
+}
+
+impl Controller {
+  /// **\[private\]** Get the output of this controller for the error at this tick
+  ///
+  /// ## Arguments
+  /// * `error` - the error at this tick
+  /// * `delta` - time delta to next tick
+  /// * `gains` - the proportional, integral and derivative gains
+  /// * `integral_limit` - the maximum absolute value of the integrated error
+  ///
+  /// ## Returns
+  /// The sum of the proportional, integral and derivative terms
+  fn control(&mut self, error: f64, delta: f64, gains: &PidGains, integral_limit: f64) -> f64 {
+    self.integral = (self.integral + error * delta).min(integral_limit).max(-integral_limit);
+    let derivative = self.previous.map_or(0.0, |previous| (error - previous) / delta);
+    self.previous = Some(error);
+    gains.p * error + gains.i * self.integral + gains.d * derivative
+  }
+}
@@ -0,0 +1,115 @@
+//! The `pure_pursuit` module provides the "[pure pursuit](https://www.ri.cmu.edu/pub_files/pub3/coulter_r_craig_1992_1/coulter_r_craig_1992_1.pdf)" planner
+//!
+//! The robot follows the arc which passes through the look-ahead point, which is the ideal pose of the agent `lookahead_time` seconds ahead.
+//! The linear velocity is proportional to the distance to the look-ahead point, and the robot turns in place when the look-ahead point is behind it.
+//! When the robot arrives at the goal, it turns in place to the direction of the goal
+
+extern crate nalgebra as na;
+
+use std::f64::consts::FRAC_PI_2;
+
+use crate::agent::Agent;
+use crate::config::PurePursuitConfig;
+use crate::planners::{self, Planner};
+use crate::utils;
+
+/// The default value of the time (sec) to look ahead the ideal pose of the agent
+pub const LOOKAHEAD_TIME: f64 = 0.2;
+/// The default value of the gain (1/sec) of the linear velocity against the distance to the look-ahead point
+pub const SPEED_GAIN: f64 = 2.0;
+/// The default value of the gain (1/sec) of the angular velocity against the angle error when the robot turns in place
+pub const HEADING_GAIN: f64 = 2.0;
+/// The default value of the distance (m) to the goal, within which the robot turns in place to the direction of the goal
+pub const ARRIVAL_DISTANCE: f64 = 0.02;
+
+/// A struct to plan the input of a robot by using the pure pursuit
+#[derive(Debug)]
+pub struct PurePursuit {
+  /// **\[private\]** the parameters of this planner
+  config: PurePursuitConfig,
+}
+
+impl PurePursuit {
+  /// Create a PurePursuit instance
+  ///
+  /// ## Arguments
+  /// * `config` - the parameters of this planner
+  ///
+  /// ## Returns
+  /// An instance of PurePursuit
+  pub fn new(config: PurePursuitConfig) -> PurePursuit {
+    PurePursuit { config }
+  }
+}
+
+/// The implementation for Planner trait
+impl Planner for PurePursuit {
+  fn get_input(&mut self, agent: &dyn Agent, current: &na::Vector3<f64>, destination: &na::Vector3<f64>,
+               current_input: &na::Vector2<f64>, t: f64, delta: f64) -> na::Vector2<f64> {
+    let distance = (destination.fixed_rows::<2>(0) - current.fixed_rows::<2>(0)).norm();
+    let input = if distance < self.config.arrival_distance {
+      na::Vector2::new(0.0, self.config.heading_gain * utils::normalize_angle(destination[2] - current[2]))
+    } else {
+      let target = if self.config.lookahead_time > 0.0 { agent.get_ideal(current, t + self.config.lookahead_time) } else { *destination };
+      let diff = target.fixed_rows::<2>(0) - current.fixed_rows::<2>(0);
+      let lookahead = diff.norm();
+      let alpha = utils::normalize_angle(diff[1].atan2(diff[0]) - current[2]);
+      if lookahead <= f64::EPSILON {
+        na::Vector2::new(0.0, 0.0)
+      } else if alpha.abs() > FRAC_PI_2 {
+        na::Vector2::new(0.0, self.config.heading_gain * alpha)
+      } else {
+        let v = self.config.speed_gain * lookahead * alpha.cos();
+        na::Vector2::new(v, 2.0 * v * alpha.sin() / lookahead)
+      }
+    };
+    planners::clamp_input(agent, current, current_input, &input, delta)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::agent;
+  use crate::config::Config;
+  use crate::data::Point;
+  use crate::map::LandmarkMap;
+
+  fn agent() -> Box<dyn Agent> {
+    let config = Config::default();
+    let map = LandmarkMap::from_points(&[Point { x: 0.0, y: 5.0 }]).unwrap();
+    agent::create_agent("circular", map, config.robot, config.noise, config.camera).unwrap()
+  }
+
+  /// The planner which pursues the goal itself instead of the look-ahead point of the agent
+  fn planner(speed_gain: f64) -> PurePursuit {
+    PurePursuit::new(PurePursuitConfig { lookahead_time: 0.0, speed_gain, ..PurePursuitConfig::default() })
+  }
+
+  #[test]
+  fn robot_follows_the_arc_through_the_goal() {
+    let agent = agent();
+    let (current, destination) = (na::Vector3::new(0.0, 0.0, 0.0), na::Vector3::new(1.0, 1.0, 0.0));
+    let input = planner(0.5).get_input(agent.as_ref(), &current, &destination, &na::Vector2::new(0.0, 0.0), 0.0, 1.0);
+
+    // the arc of the radius 1 m centered at (0, 1) passes through the goal
+    assert!((input[0] - 0.5).abs() < 1e-12);
+    assert!((input[1] - 0.5).abs() < 1e-12);
+  }
+
+  #[test]
+  fn robot_turns_in_place_when_the_goal_is_behind_it() {
+    let agent = agent();
+    let (current, destination) = (na::Vector3::new(0.0, 0.0, 0.0), na::Vector3::new(-1.0, 0.1, 0.0));
+    let input = planner(2.0).get_input(agent.as_ref(), &current, &destination, &na::Vector2::new(0.0, 0.0), 0.0, 1.0);
+    assert_eq!(input, na::Vector2::new(0.0, agent.get_robot_config().max_omega));
+  }
+
+  #[test]
+  fn robot_turns_to_the_goal_direction_after_arrival() {
+    let agent = agent();
+    let (current, destination) = (na::Vector3::new(1.0, 1.0, 0.0), na::Vector3::new(1.01, 1.0, -0.5));
+    let input = planner(2.0).get_input(agent.as_ref(), &current, &destination, &na::Vector2::new(0.0, 0.0), 0.0, 1.0);
+    assert_eq!(input, na::Vector2::new(0.0, -HEADING_GAIN * 0.5));
+  }
+}
@@ -0,0 +1,71 @@
+//! The `stanley` module provides the "[Stanley](http://robots.stanford.edu/papers/thrun.stanley05.pdf)" planner
+//!
+//! The reference path is the line which passes through the goal in the direction of the goal.
+//! The robot steers by the heading error against the reference path and the arctangent of the cross track error,
+//! and the linear velocity is proportional to the along track distance to the goal
+
+extern crate nalgebra as na;
+
+use crate::agent::Agent;
+use crate::config::StanleyConfig;
+use crate::planners::{self, Planner};
+use crate::utils;
+
+/// The default value of the gain of the cross track error
+pub const CROSS_TRACK_GAIN: f64 = 2.0;
+/// The default value of the softening linear velocity (m/sec) which keeps the steering finite at low speed
+pub const SOFTENING: f64 = 0.1;
+/// The default value of the gain (1/sec) of the angular velocity against the steering angle
+pub const HEADING_GAIN: f64 = 2.0;
+/// The default value of the gain (1/sec) of the linear velocity against the along track distance to the goal
+pub const SPEED_GAIN: f64 = 2.0;
+
+/// A struct to plan the input of a robot by using the Stanley controller
+#[derive(Debug)]
+pub struct Stanley {
+  /// **\[private\]** the parameters of this planner
+  config: StanleyConfig,
+}
+
+impl Stanley {
+  /// Create a Stanley instance
+  ///
+  /// ## Arguments
+  /// * `config` - the parameters of this planner
+  ///
+  /// ## Returns
+  /// An instance of Stanley
+  pub fn new(config: StanleyConfig) -> Stanley {
+    Stanley { config }
+  }
+}
+
+/// The implementation for Planner trait
+impl Planner for Stanley {
+  fn get_input(&mut self, agent: &dyn Agent, current: &na::Vector3<f64>, destination: &na::Vector3<f64>,
+               current_input: &na::Vector2<f64>, _: f64, delta: f64) -> na::Vector2<f64> {
+    let (along_track, cross_track) = get_track_errors(current, destination);
+    let v = self.config.speed_gain * along_track;
+    let heading_error = utils::normalize_angle(destination[2] - current[2]);
+    // the robot steers the other way to reduce the cross track error while it moves backward
+    let direction = if v < 0.0 { -1.0 } else { 1.0 };
+    let steering = heading_error + (-direction * self.config.cross_track_gain * cross_track).atan2(self.config.softening + v.abs());
+    let input = na::Vector2::new(v, self.config.heading_gain * utils::normalize_angle(steering));
+    planners::clamp_input(agent, current, current_input, &input, delta)
+  }
+}
+
+/// **\[private\]** Get the errors of the current pose against the reference path which passes through the goal in the direction of the goal
+///
+/// ## Arguments
+/// * `current` - the current pose(x, y, theta) of this simulated robot
+/// * `destination` - the goal pose(x, y, theta)
+///
+/// ## Returns
+/// Tuple of (the along track distance to the goal (positive when the robot is behind the goal),
+/// the cross track error (positive when the robot is on the left side of the reference path))
+fn get_track_errors(current: &na::Vector3<f64>, destination: &na::Vector3<f64>) -> (f64, f64) {
+  let diff = current.fixed_rows::<2>(0) - destination.fixed_rows::<2>(0);
+  let (sin, cos) = destination[2].sin_cos();
+  (-(cos * diff[0] + sin * diff[1]), -sin * diff[0] + cos * diff[1])
+}
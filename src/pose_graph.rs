@@ -0,0 +1,557 @@
+//! The `pose_graph` module provides the graph-based SLAM back-end which optimizes the whole trajectory of the robot and the landmarks
+//!
+//! The graph has a node for the pose of the robot at every tick and a node for every observed landmark.
+//! * an odometry edge connects the consecutive poses by the relative motion calculated from the commanded input by [robot::ideal_move]
+//! * an observation edge connects a pose and a landmark by the measured distance and angle calculated by [camera::observe]
+//!
+//! The graph is optimized by Gauss-Newton or Levenberg-Marquardt on SE(2) with the first pose fixed.
+//! When the robot revisits the landmarks, the observation edges close the loop and correct the drift of the odometry.
+//!
+//! The normal equation is solved efficiently by eliminating the poses: the poses form a block tridiagonal system
+//! because the odometry edges connect only the consecutive poses, and the Schur complement of the landmarks is small.
+//!
+//! The graph grows by a pose at every tick, and both the memory and the cost of an optimization grow with the number of poses times the number of landmarks.
+//! When `max_poses` is given (e.g. in the real-time mode which runs forever), the graph keeps only the latest poses as a sliding window:
+//! the oldest pose is dropped with its edges, and the next pose becomes the fixed first pose.
+
+use std::collections::HashMap;
+use std::ops::AddAssign;
+
+use serde::{Deserialize, Serialize};
+
+extern crate nalgebra as na;
+
+use crate::config::{Config, GraphConfig, OptimizationMethod};
+use crate::data::{Point, Pose, EstimatedLandmark};
+use crate::models::{robot, camera};
+use crate::utils;
+
+/// The default value of the maximum number of iterations of an optimization
+pub const MAX_ITERATIONS: usize = 20;
+/// The default value of the norm of the step, below which the optimization is regarded as converged
+pub const TOLERANCE: f64 = 1e-6;
+/// The maximum number of the poses in the real-time mode when it is not configured
+pub const REALTIME_MAX_POSES: usize = 1000;
+
+/// **\[private\]** The initial damping factor of Levenberg-Marquardt
+const INITIAL_DAMPING: f64 = 1e-4;
+/// **\[private\]** The variance added to the covariance matrix of the odometry edge, so that the edge of a stationary robot has a finite information
+const MIN_ODOMETRY_VARIANCE: f64 = 1e-8;
+
+/// A struct which stores the result of an optimization
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct Optimization {
+  /// the number of iterations
+  pub iterations: usize,
+  /// the weighted squared error of all edges before the optimization
+  pub initial_error: f64,
+  /// the weighted squared error of all edges after the optimization
+  pub final_error: f64,
+  /// whether the norm of the step fell below the tolerance
+  pub converged: bool,
+}
+
+/// **\[private\]** A struct which defines an observation edge between a pose and a landmark
+#[derive(Clone)]
+#[derive(Debug)]
+struct Observation {
+  /// the index of the pose
+  pose: usize,
+  /// the index of the landmark
+  landmark: usize,
+  /// the measured vector(distance, angle)
+  z: na::Vector2<f64>,
+  /// the information matrix of the edge
+  information: na::Matrix2<f64>,
+}
+
+/// **\[private\]** A struct which stores the normal equation of the graph
+///
+/// The poses except the first one are ordered before the landmarks, and the hessian is stored by its blocks
+struct NormalEquation {
+  /// the diagonal blocks of the poses
+  diagonal: Vec<na::Matrix3<f64>>,
+  /// the blocks between the consecutive poses (the block between the pose `i` and `i + 1` at `i`)
+  off_diagonal: Vec<na::Matrix3<f64>>,
+  /// the blocks between the poses and the landmarks
+  pose_landmark: na::DMatrix<f64>,
+  /// the block of the landmarks
+  landmark: na::DMatrix<f64>,
+  /// the gradient of the poses
+  pose_gradient: na::DVector<f64>,
+  /// the gradient of the landmarks
+  landmark_gradient: na::DVector<f64>,
+}
+
+/// A struct which records the pose graph and optimizes it
+#[derive(Debug)]
+pub struct PoseGraph {
+  /// **\[private\]** the poses(x, y, theta) of the nodes
+  poses: Vec<na::Vector3<f64>>,
+  /// **\[private\]** the positions(x, y) of the landmarks
+  landmarks: Vec<na::Vector2<f64>>,
+  /// **\[private\]** the landmark IDs in the order of the landmarks
+  ids: Vec<u64>,
+  /// **\[private\]** the indexes of the landmarks by their IDs
+  indexes: HashMap<u64, usize>,
+  /// **\[private\]** the relative motions(x, y, theta) between the consecutive poses in the frame of the former pose
+  odometry: Vec<na::Vector3<f64>>,
+  /// **\[private\]** the observation edges
+  observations: Vec<Observation>,
+  /// **\[private\]** the information matrices of the odometry edges
+  odometry_information: Vec<na::Matrix3<f64>>,
+  /// **\[private\]** the noise model of the robot's motion which gives the information matrix of every odometry edge
+  motion: robot::MotionNoise,
+  /// **\[private\]** the noise model of the camera which gives the information matrix of every observation edge
+  noise: camera::ObservationNoise,
+  /// **\[private\]** the covariance matrix of the landmarks at the latest optimization
+  landmark_covariance: Option<na::DMatrix<f64>>,
+  /// **\[private\]** the number of the poses dropped from the sliding window
+  dropped: usize,
+  /// **\[private\]** the parameters of the optimization
+  config: GraphConfig,
+}
+
+impl PoseGraph {
+  /// Create a PoseGraph instance which has only the initial pose
+  ///
+  /// ## Arguments
+  /// * `initial` - the initial pose(x, y, theta) which is fixed in the optimization
+  /// * `config` - the configuration which has the noise model assumed by the filter and the parameters of the optimization
+  ///
+  /// ## Returns
+  /// An instance of PoseGraph
+  pub fn new(initial: &na::Vector3<f64>, config: &Config) -> PoseGraph {
+    PoseGraph {
+      poses: vec![*initial],
+      landmarks: Vec::new(),
+      ids: Vec::new(),
+      indexes: HashMap::new(),
+      odometry: Vec::new(),
+      observations: Vec::new(),
+      odometry_information: Vec::new(),
+      motion: robot::MotionNoise::assumed(config),
+      noise: camera::ObservationNoise::assumed(config),
+      landmark_covariance: None,
+      dropped: 0,
+      config: config.graph.clone(),
+    }
+  }
+
+  /// Add a pose connected with the latest pose by the odometry edge
+  ///
+  /// The oldest pose is dropped when the graph has more than `max_poses` poses
+  ///
+  /// ## Arguments
+  /// * `input` - the commanded input vector(linear velocity, angular velocity)
+  /// * `delta` - time delta
+  /// * `guess` - the initial guess of the pose(x, y, theta), such as the pose estimated by the filter
+  pub fn add_pose(&mut self, input: &na::Vector2<f64>, delta: f64, guess: &na::Vector3<f64>) {
+    // the relative motion and its covariance matrix are calculated in the frame of the former pose
+    let origin = na::Vector3::zeros();
+    let covariance = self.motion.covariance(&origin, input, delta) + MIN_ODOMETRY_VARIANCE * na::Matrix3::identity();
+    self.odometry.push(robot::ideal_move(&origin, input, delta));
+    self.odometry_information.push(covariance.try_inverse().unwrap_or_else(na::Matrix3::identity));
+    self.poses.push(*guess);
+    if self.config.max_poses.is_some_and(|max_poses| self.poses.len() > max_poses.max(2)) {
+      self.drop_oldest();
+    }
+  }
+
+  /// **\[private\]** Drop the oldest pose with its edges, and the landmarks which are no longer observed from the remaining poses
+  fn drop_oldest(&mut self) {
+    self.poses.remove(0);
+    self.odometry.remove(0);
+    self.odometry_information.remove(0);
+    self.observations.retain(|o| o.pose > 0);
+    for o in self.observations.iter_mut() {
+      o.pose -= 1;
+    }
+    self.dropped += 1;
+
+    let mut observed = vec![false; self.landmarks.len()];
+    for o in self.observations.iter() {
+      observed[o.landmark] = true;
+    }
+    if observed.iter().all(|observed| *observed) {
+      return;
+    }
+    // the landmark which has no observation edge cannot be optimized, so that it is dropped and the remaining landmarks are re-indexed
+    let kept = (0..self.landmarks.len()).filter(|i| observed[*i]).collect::<Vec<_>>();
+    let mut remap = vec![0; self.landmarks.len()];
+    for (new, old) in kept.iter().enumerate() {
+      remap[*old] = new;
+    }
+    self.landmarks = kept.iter().map(|i| self.landmarks[*i]).collect();
+    self.ids = kept.iter().map(|i| self.ids[*i]).collect();
+    self.indexes = self.ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+    for o in self.observations.iter_mut() {
+      o.landmark = remap[o.landmark];
+    }
+    self.landmark_covariance = None;
+  }
+
+  /// Add an observation edge between the latest pose and the landmark
+  ///
+  /// The landmark which is observed for the first time is added to the graph and initialized from the range and bearing of the measurement
+  ///
+  /// ## Arguments
+  /// * `id` - the landmark ID
+  /// * `z` - the measured vector(distance, angle)
+  pub fn add_observation(&mut self, id: u64, z: &na::Vector2<f64>) {
+    let pose = self.poses.len() - 1;
+    let landmark = match self.indexes.get(&id) {
+      Some(landmark) => *landmark,
+      None => {
+        let current = self.poses[pose];
+        let direction = current[2] + z[1];
+        self.landmarks.push(na::Vector2::new(current[0] + z[0] * direction.cos(), current[1] + z[0] * direction.sin()));
+        self.ids.push(id);
+        self.indexes.insert(id, self.landmarks.len() - 1);
+        self.landmarks.len() - 1
+      },
+    };
+    let information = self.noise.covariance(z[0]).try_inverse().unwrap_or_else(na::Matrix2::identity);
+    self.observations.push(Observation { pose, landmark, z: *z, information });
+  }
+
+  /// Get the number of the poses
+  pub fn len(&self) -> usize {
+    self.poses.len()
+  }
+
+  /// Get the number of the poses added since the initial pose, including the poses dropped from the sliding window
+  pub fn ticks(&self) -> usize {
+    self.dropped + self.poses.len() - 1
+  }
+
+  /// Whether the graph has only the initial pose
+  pub fn is_empty(&self) -> bool {
+    self.odometry.is_empty()
+  }
+
+  /// Get the poses of the graph in the order of the ticks (only the latest `max_poses` poses when the graph is a sliding window)
+  pub fn trajectory(&self) -> Vec<Pose> {
+    self.poses.iter().map(Pose::from_vector3).collect()
+  }
+
+  /// Get the landmarks of the graph with their marginal covariance matrices at the latest optimization
+  ///
+  /// The covariance matrix is filled with zeros before the first optimization
+  pub fn landmarks(&self) -> Vec<EstimatedLandmark> {
+    self.ids.iter()
+            .enumerate()
+            .map(|(i, id)| {
+              let covariance = match &self.landmark_covariance {
+                Some(covariance) if covariance.nrows() >= 2 * (i + 1) => covariance.fixed_slice::<2, 2>(2 * i, 2 * i).transpose().as_slice().to_vec(),
+                _ => vec![0.0; 4],
+              };
+              EstimatedLandmark { id: *id, x: self.landmarks[i][0], y: self.landmarks[i][1], covariance }
+            })
+            .collect()
+  }
+
+  /// Optimize the poses and the landmarks of the graph
+  ///
+  /// ## Returns
+  /// The result of the optimization
+  pub fn optimize(&mut self) -> Optimization {
+    let initial_error = self.error(&self.poses, &self.landmarks);
+    let mut error = initial_error;
+    let mut damping = match self.config.method {
+      OptimizationMethod::GaussNewton => 0.0,
+      OptimizationMethod::LevenbergMarquardt => INITIAL_DAMPING,
+    };
+    let mut iterations = 0;
+    let mut converged = false;
+
+    while iterations < self.config.max_iterations {
+      iterations += 1;
+      let equation = self.linearize();
+      let (dp, dl, covariance) = match PoseGraph::solve(&equation, damping) {
+        Some(solution) => solution,
+        None => {
+          // the damped system is always solvable when the damping is large enough
+          if self.config.method == OptimizationMethod::GaussNewton {
+            break;
+          }
+          damping *= 10.0;
+          continue;
+        },
+      };
+      let (poses, landmarks) = self.apply(&dp, &dl);
+      let updated_error = self.error(&poses, &landmarks);
+      let step = (dp.norm_squared() + dl.norm_squared()).sqrt();
+
+      if self.config.method == OptimizationMethod::GaussNewton || updated_error < error {
+        self.poses = poses;
+        self.landmarks = landmarks;
+        self.landmark_covariance = Some(covariance);
+        error = updated_error;
+        damping /= 10.0;
+      } else {
+        damping *= 10.0;
+      }
+      if step < self.config.tolerance {
+        converged = true;
+        break;
+      }
+    }
+    if self.landmark_covariance.as_ref().map_or(true, |covariance| covariance.nrows() != 2 * self.landmarks.len()) {
+      self.landmark_covariance = PoseGraph::solve(&self.linearize(), 0.0).map(|(_, _, covariance)| covariance);
+    }
+
+    Optimization { iterations, initial_error, final_error: error, converged }
+  }
+
+  /// **\[private\]** Calculate the error of the odometry edge
+  ///
+  /// ## Returns
+  /// Tuple of (the error vector, the jacobian with respect to the former pose, the jacobian with respect to the latter pose)
+  fn odometry_error(from: &na::Vector3<f64>, to: &na::Vector3<f64>, odometry: &na::Vector3<f64>)
+    -> (na::Vector3<f64>, na::Matrix3<f64>, na::Matrix3<f64>) {
+    let (sin, cos) = from[2].sin_cos();
+    let (dx, dy) = (to[0] - from[0], to[1] - from[1]);
+    let dtheta = to[2] - from[2] - odometry[2];
+    let e = na::Vector3::new(
+       cos * dx + sin * dy - odometry[0],
+      -sin * dx + cos * dy - odometry[1],
+      utils::normalize_angle(dtheta),
+    );
+    let a = na::Matrix3::new(-cos, -sin, -sin * dx + cos * dy,
+                              sin, -cos, -cos * dx - sin * dy,
+                              0.0,  0.0, -1.0);
+    let b = na::Matrix3::new( cos, sin, 0.0,
+                             -sin, cos, 0.0,
+                              0.0, 0.0, 1.0);
+    (e, a, b)
+  }
+
+  /// **\[private\]** Calculate the error of the observation edge
+  ///
+  /// ## Returns
+  /// Tuple of (the error vector, the jacobian with respect to the pose, the jacobian with respect to the landmark),
+  /// or `None` when the pose sits on the landmark
+  fn observation_error(pose: &na::Vector3<f64>, landmark: &na::Vector2<f64>, z: &na::Vector2<f64>)
+    -> Option<(na::Vector2<f64>, na::Matrix2x3<f64>, na::Matrix2<f64>)> {
+    let point = Point { x: landmark[0], y: landmark[1] };
+    let jp = camera::calc_h(&point, pose)?;
+    let jl = -jp.fixed_columns::<2>(0);
+    Some((-camera::innovation(z, &point, pose), jp, jl))
+  }
+
+  /// **\[private\]** Calculate the weighted squared error of all edges
+  fn error(&self, poses: &[na::Vector3<f64>], landmarks: &[na::Vector2<f64>]) -> f64 {
+    let odometry = self.odometry.iter()
+                                .enumerate()
+                                .map(|(i, odometry)| {
+                                  let (e, _, _) = PoseGraph::odometry_error(&poses[i], &poses[i + 1], odometry);
+                                  (e.transpose() * self.odometry_information[i] * e)[0]
+                                })
+                                .sum::<f64>();
+    let observation = self.observations.iter()
+                                       .filter_map(|o| {
+                                         PoseGraph::observation_error(&poses[o.pose], &landmarks[o.landmark], &o.z)
+                                                   .map(|(e, _, _)| (e.transpose() * o.information * e)[0])
+                                       })
+                                       .sum::<f64>();
+    odometry + observation
+  }
+
+  /// **\[private\]** Build the normal equation of the graph linearized at the current poses and landmarks
+  ///
+  /// The first pose is fixed, so that its rows and columns are excluded
+  fn linearize(&self) -> NormalEquation {
+    let n = self.poses.len() - 1;
+    let m = 2 * self.landmarks.len();
+    let mut equation = NormalEquation {
+      diagonal: vec![na::Matrix3::zeros(); n],
+      off_diagonal: vec![na::Matrix3::zeros(); n.saturating_sub(1)],
+      pose_landmark: na::DMatrix::zeros(3 * n, m),
+      landmark: na::DMatrix::zeros(m, m),
+      pose_gradient: na::DVector::zeros(3 * n),
+      landmark_gradient: na::DVector::zeros(m),
+    };
+
+    for (i, odometry) in self.odometry.iter().enumerate() {
+      let (e, a, b) = PoseGraph::odometry_error(&self.poses[i], &self.poses[i + 1], odometry);
+      let omega = self.odometry_information[i];
+      // the variable of the pose `i + 1` is at `i` because the first pose is excluded
+      equation.diagonal[i] += b.transpose() * omega * b;
+      let gradient = b.transpose() * omega * e;
+      equation.pose_gradient.fixed_rows_mut::<3>(3 * i).add_assign(&gradient);
+      if i > 0 {
+        equation.diagonal[i - 1] += a.transpose() * omega * a;
+        equation.off_diagonal[i - 1] += a.transpose() * omega * b;
+        let gradient = a.transpose() * omega * e;
+        equation.pose_gradient.fixed_rows_mut::<3>(3 * (i - 1)).add_assign(&gradient);
+      }
+    }
+
+    for o in self.observations.iter() {
+      let (e, jp, jl) = match PoseGraph::observation_error(&self.poses[o.pose], &self.landmarks[o.landmark], &o.z) {
+        Some(linearized) => linearized,
+        None => continue,
+      };
+      let omega = o.information;
+      let l = 2 * o.landmark;
+      let hll = jl.transpose() * omega * jl;
+      let gl = jl.transpose() * omega * e;
+      equation.landmark.fixed_slice_mut::<2, 2>(l, l).add_assign(&hll);
+      equation.landmark_gradient.fixed_rows_mut::<2>(l).add_assign(&gl);
+      if o.pose > 0 {
+        let p = 3 * (o.pose - 1);
+        equation.diagonal[o.pose - 1] += jp.transpose() * omega * jp;
+        let hpl = jp.transpose() * omega * jl;
+        let gp = jp.transpose() * omega * e;
+        equation.pose_landmark.fixed_slice_mut::<3, 2>(p, l).add_assign(&hpl);
+        equation.pose_gradient.fixed_rows_mut::<3>(p).add_assign(&gp);
+      }
+    }
+    equation
+  }
+
+  /// **\[private\]** Solve the damped normal equation by eliminating the poses
+  ///
+  /// ## Returns
+  /// Tuple of (the step of the poses, the step of the landmarks, the covariance matrix of the landmarks),
+  /// or `None` when the normal equation is singular
+  fn solve(equation: &NormalEquation, damping: f64) -> Option<(na::DVector<f64>, na::DVector<f64>, na::DMatrix<f64>)> {
+    let m = equation.landmark.nrows();
+    let damped = |block: &na::Matrix3<f64>| block + damping * na::Matrix3::identity();
+    let diagonal = equation.diagonal.iter().map(damped).collect::<Vec<_>>();
+
+    // solve the poses for the gradient and every column of the pose-landmark blocks at once
+    let mut rhs = na::DMatrix::zeros(equation.pose_gradient.len(), m + 1);
+    rhs.column_mut(0).copy_from(&equation.pose_gradient);
+    rhs.columns_mut(1, m).copy_from(&equation.pose_landmark);
+    let solved = PoseGraph::solve_block_tridiagonal(&diagonal, &equation.off_diagonal, &rhs)?;
+    let (y, z) = (solved.column(0).into_owned(), solved.columns(1, m).into_owned());
+
+    let schur = &equation.landmark + damping * na::DMatrix::identity(m, m) - equation.pose_landmark.transpose() * &z;
+    let reduced = &equation.landmark_gradient - equation.pose_landmark.transpose() * &y;
+    let covariance = schur.try_inverse()?;
+    let dl = -(&covariance * reduced);
+    let dp = -(y + &z * &dl);
+    Some((dp, dl, covariance))
+  }
+
+  /// **\[private\]** Solve the symmetric block tridiagonal system by the block Thomas algorithm
+  ///
+  /// ## Arguments
+  /// * `diagonal` - the diagonal blocks
+  /// * `off_diagonal` - the upper off-diagonal blocks (the lower ones are their transposes)
+  /// * `rhs` - the right-hand sides
+  ///
+  /// ## Returns
+  /// The solution, or `None` when a diagonal block of the elimination is singular
+  fn solve_block_tridiagonal(diagonal: &[na::Matrix3<f64>], off_diagonal: &[na::Matrix3<f64>], rhs: &na::DMatrix<f64>) -> Option<na::DMatrix<f64>> {
+    let n = diagonal.len();
+    let k = rhs.ncols();
+    let mut c: Vec<na::Matrix3<f64>> = Vec::with_capacity(n);
+    let mut d = na::DMatrix::zeros(3 * n, k);
+    for i in 0..n {
+      let mut block = diagonal[i];
+      let mut b = rhs.rows(3 * i, 3).into_owned();
+      if i > 0 {
+        block -= off_diagonal[i - 1].transpose() * c[i - 1];
+        b -= off_diagonal[i - 1].transpose() * d.rows(3 * (i - 1), 3);
+      }
+      let inverse = block.try_inverse()?;
+      if i + 1 < n {
+        c.push(inverse * off_diagonal[i]);
+      }
+      d.rows_mut(3 * i, 3).copy_from(&(inverse * b));
+    }
+    for i in (0..n.saturating_sub(1)).rev() {
+      let next = d.rows(3 * (i + 1), 3).into_owned();
+      let x = d.rows(3 * i, 3) - c[i] * next;
+      d.rows_mut(3 * i, 3).copy_from(&x);
+    }
+    Some(d)
+  }
+
+  /// **\[private\]** Apply the step to the poses and the landmarks
+  ///
+  /// ## Returns
+  /// Tuple of (the updated poses, the updated landmarks)
+  fn apply(&self, dp: &na::DVector<f64>, dl: &na::DVector<f64>) -> (Vec<na::Vector3<f64>>, Vec<na::Vector2<f64>>) {
+    let poses = self.poses.iter()
+                          .enumerate()
+                          .map(|(i, pose)| {
+                            if i == 0 {
+                              return *pose;
+                            }
+                            let mut updated = pose + dp.fixed_rows::<3>(3 * (i - 1));
+                            updated[2] = utils::normalize_angle(updated[2]);
+                            updated
+                          })
+                          .collect();
+    let landmarks = self.landmarks.iter()
+                                  .enumerate()
+                                  .map(|(i, landmark)| landmark + dl.fixed_rows::<2>(2 * i))
+                                  .collect();
+    (poses, landmarks)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// The measurement of the landmark from the pose
+  fn observe(landmark: &Point, pose: &na::Vector3<f64>) -> na::Vector2<f64> {
+    let z = camera::observe(landmark, pose);
+    na::Vector2::new(z[0], utils::normalize_angle(z[1]))
+  }
+
+  #[test]
+  fn loop_closure_corrects_the_drift_of_the_odometry() {
+    let landmark = Point { x: 0.0, y: 2.0 };
+    let (input, delta, ticks) = (na::Vector2::new(1.0, 1.0), 0.1, 63);
+    let mut actual = na::Vector3::zeros();
+    let mut guess = na::Vector3::zeros();
+    let mut graph = PoseGraph::new(&actual, &Config::default());
+    graph.add_observation(0, &observe(&landmark, &actual));
+
+    // the robot goes around the circle of radius 1 and comes back near the start, while the guess drifts by the biased angular velocity
+    for _ in 0..ticks {
+      actual = robot::ideal_move(&actual, &input, delta);
+      guess = robot::ideal_move(&guess, &na::Vector2::new(input[0], 1.1 * input[1]), delta);
+      graph.add_pose(&input, delta, &guess);
+    }
+    graph.add_observation(0, &observe(&landmark, &actual));
+    let drift = (guess.fixed_rows::<2>(0) - actual.fixed_rows::<2>(0)).norm();
+
+    let optimization = graph.optimize();
+    assert!(optimization.final_error < optimization.initial_error);
+    let trajectory = graph.trajectory();
+    let last = &trajectory[ticks];
+    assert!((na::Vector2::new(last.x, last.y) - actual.fixed_rows::<2>(0)).norm() < 0.1 * drift);
+    let estimated = &graph.landmarks()[0];
+    assert!((estimated.x - landmark.x).hypot(estimated.y - landmark.y) < 1e-3);
+  }
+
+  #[test]
+  fn sliding_window_drops_the_oldest_poses_and_landmarks() {
+    let mut config = Config::default();
+    config.graph.max_poses = Some(5);
+    let input = na::Vector2::new(1.0, 0.0);
+    let mut pose = na::Vector3::zeros();
+    let mut graph = PoseGraph::new(&pose, &config);
+    graph.add_observation(0, &observe(&Point { x: 0.0, y: 1.0 }, &pose));
+    for _ in 0..10 {
+      pose = robot::ideal_move(&pose, &input, 0.1);
+      graph.add_pose(&input, 0.1, &pose);
+      graph.add_observation(1, &observe(&Point { x: 2.0, y: 1.0 }, &pose));
+    }
+
+    assert_eq!(graph.len(), 5);
+    assert_eq!(graph.ticks(), 10);
+    assert!((graph.trajectory()[0].x - 0.6).abs() < 1e-9);
+    assert_eq!(graph.landmarks().iter().map(|l| l.id).collect::<Vec<_>>(), vec![1]);
+    assert!(graph.optimize().final_error < 1e-12);
+  }
+}
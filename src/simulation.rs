@@ -0,0 +1,398 @@
+//! The `simulation` module drives a simulated robot and an estimator of its pose at every tick
+//!
+//! [Simulation] owns the simulated world (the agent which moves and observes with the noise, the clock and the random number generator),
+//! the planner which decides the input, and the [Estimator] which estimates the pose only from the input and the measurements.
+
+extern crate nalgebra as na;
+extern crate rand;
+use std::collections::BTreeMap;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+
+use crate::agent::{self, Agent};
+use crate::clocks::{self, Clock};
+use crate::config::{Config, AssociationConfig, AssociationMethod, RecoveryMethod};
+use crate::data::{Pose, Particle, Measurement, EstimatedLandmark};
+use crate::filters::{self, Estimator, UpdateError, Divergence};
+use crate::filters::data_association::{self, AssociationStats};
+use crate::map::{Landmark, LandmarkMap};
+use crate::metrics::StepMetrics;
+use crate::models::camera;
+use crate::obstacles::ObstacleMap;
+use crate::planners::{self, Planner};
+use crate::pose_graph::{PoseGraph, Optimization};
+
+/// The default value of the maximum trace of the covariance matrix, beyond which the estimator is regarded as diverged
+pub const MAX_COVARIANCE_TRACE: f64 = 100.0;
+/// The default value of the variance which is set to the diagonal of the covariance matrix when the estimator recovers from the divergence
+pub const RESET_VARIANCE: f64 = 0.1;
+
+/// Create a Simulation of the given Agent whose noise and particles are generated from the given seed
+///
+/// The same seed reproduces the same simulation
+///
+/// ## Arguments
+/// * `name` - the name of the Agent (see [agent::create_agent])
+/// * `map` - the landmark map which will be observed from robot
+/// * `config` - the configuration of the simulated robot, the estimator and the planner
+/// * `seed` - the seed of the random number generator
+///
+/// ## Returns
+/// An instance of Simulation
+///
+/// ## Errors
+/// When unknown Agent is given, an obstacle is invalid, the estimator cannot be created or the random number generator cannot be seeded, this function returns Error
+pub fn create_simulation(name: &str, map: &LandmarkMap, config: &Config, seed: u64) -> Result<Simulation, String> {
+  let agt = agent::create_agent(name, map.clone(), config.robot.clone(), config.noise.clone(), config.camera.clone())?;
+  let clock = clocks::create_clock(&config.timer);
+  let mut rng = StdRng::seed_from_u64(seed);
+  let estimator_rng = StdRng::from_rng(&mut rng).map_err(|e| e.to_string())?;
+  let estimator = filters::create_estimator(map.clone(), estimator_rng, config)?;
+  let obstacles = config.scenario.load_obstacles()?;
+  Ok(Simulation::new(agt, estimator, map.clone(), obstacles, clock, rng, config))
+}
+
+/// A struct which simulates a robot and estimates its pose at every tick
+#[derive(Debug)]
+pub struct Simulation {
+  /// **\[private\]** an agent instance of a robot to be estimated
+  agent: Box<dyn Agent>,
+  /// **\[private\]** the estimator such as EKF, UKF and PF
+  estimator: Box<dyn Estimator>,
+  /// **\[private\]** the landmark map which is used to associate the measurements
+  map: LandmarkMap,
+  /// **\[private\]** the obstacles which the planner avoids and the actual robot may collide with
+  obstacles: ObstacleMap,
+  /// **\[private\]** whether the actual robot collides with the obstacles at the latest tick
+  collision: bool,
+  /// **\[private\]** the noise model of the camera which is assumed to associate the measurements
+  noise: camera::ObservationNoise,
+  /// **\[private\]** the parameters of the data association between the measurements and the landmarks
+  association: AssociationConfig,
+  /// **\[private\]** the statistics of the data association at the latest tick
+  association_stats: AssociationStats,
+  /// **\[private\]** the number of measurements rejected as outliers at the latest tick
+  rejected: usize,
+  /// **\[private\]** the number of measurements which could not be applied because of the numerical failure at the latest tick
+  failures: usize,
+  /// **\[private\]** the maximum trace of the covariance matrix, beyond which the estimator is regarded as diverged
+  max_covariance_trace: f64,
+  /// **\[private\]** the way to recover from the divergence
+  recovery: RecoveryMethod,
+  /// **\[private\]** whether the estimator is SLAM, which re-initializes the pose from its own landmarks instead of the map
+  slam: bool,
+  /// **\[private\]** the variance which is set to the diagonal of the covariance matrix when the estimator recovers from the divergence
+  reset_variance: f64,
+  /// **\[private\]** the divergence detected at the latest tick
+  divergence: Option<Divergence>,
+  /// **\[private\]** the metrics of the estimator against the actual pose at the latest tick
+  metrics: StepMetrics,
+  /// **\[private\]** the current input vector(linear velocity, angular velocity)
+  input: na::Vector2<f64>,
+  /// **\[private\]** the clock which provides the simulation time
+  clock: Box<dyn Clock>,
+  /// **\[private\]** the random number generator which generates the noise of the simulated robot and camera
+  rng: StdRng,
+  /// **\[private\]** the planner which decides the input such as DWA, pure pursuit, Stanley, PID and MPC
+  planner: Box<dyn Planner>,
+  /// **\[private\]** the pose graph which is recorded when the graph SLAM back-end is enabled
+  graph: Option<PoseGraph>,
+  /// **\[private\]** the number of steps between the periodic optimizations of the pose graph
+  optimize_every: Option<u64>,
+  /// **\[private\]** the result of the optimization of the pose graph at the latest tick
+  optimization: Option<Optimization>,
+}
+
+impl Simulation {
+  /// Create a Simulation instance
+  ///
+  /// ## Arguments
+  /// * `agent` - an agent instance of a robot to be estimated
+  /// * `estimator` - the estimator such as EKF, UKF and PF
+  /// * `map` - the landmark map which is used to associate the measurements
+  /// * `obstacles` - the obstacles which the planner avoids
+  /// * `clock` - the clock which provides the simulation time
+  /// * `rng` - the random number generator which generates the noise of the simulated robot and camera
+  /// * `config` - the configuration which has the noise model assumed by the estimator, the divergence recovery,
+  ///   the parameters of the data association and the kind of the planner and its parameters
+  ///
+  /// ## Returns
+  /// An instance of Simulation
+  pub fn new(mut agent: Box<dyn Agent>, estimator: Box<dyn Estimator>, map: LandmarkMap, obstacles: ObstacleMap, clock: Box<dyn Clock>, rng: StdRng,
+             config: &Config) -> Simulation {
+    let filter = &config.filter;
+    let initial_pose = &config.scenario.initial_pose;
+    agent.set_actual(na::Vector3::new(initial_pose.x, initial_pose.y, initial_pose.theta));
+    let graph = if config.graph.enabled { Some(PoseGraph::new(&estimator.state(), config)) } else { None };
+    let planner = planners::create_planner(config, &obstacles);
+    Simulation {
+      agent, estimator, map, obstacles,
+      collision: false,
+      noise: camera::ObservationNoise::assumed(config),
+      association: config.association.clone(),
+      association_stats: AssociationStats::default(),
+      rejected: 0,
+      failures: 0,
+      max_covariance_trace: filter.max_covariance_trace,
+      recovery: filter.recovery,
+      slam: filter.kind.is_slam(),
+      reset_variance: filter.reset_variance,
+      divergence: None,
+      metrics: StepMetrics::default(),
+      input: na::Vector2::new(0.0, 0.0),
+      clock, rng,
+      planner,
+      graph,
+      optimize_every: config.graph.optimize_every.filter(|every| *every > 0),
+      optimization: None,
+    }
+  }
+
+  /// **\[private\]** Get the landmark map which the pose is re-initialized from when the estimator diverges
+  ///
+  /// SLAM estimators must not see the actual map, so their own estimated landmarks are used instead
+  ///
+  /// ## Returns
+  /// The landmark map, or `None` when the SLAM estimator has no valid landmark yet
+  fn recovery_map(&self) -> Option<LandmarkMap> {
+    if !self.slam {
+      return Some(self.map.clone());
+    }
+    let landmarks = self.estimator.landmarks().iter().map(|l| Landmark { id: l.id, x: l.x, y: l.y, metadata: BTreeMap::new() }).collect();
+    LandmarkMap::new(landmarks).ok()
+  }
+
+  /// Move the robot and estimate its pose at this tick
+  ///
+  /// The measurements which cannot be applied because of the numerical failure are skipped.
+  /// When the estimator diverges, the estimated pose and the covariance matrix are recovered according to the configured [RecoveryMethod]
+  ///
+  /// ## Returns
+  /// Tuple of (the ideal pose of the robot, the estimated pose of the robot, the covariance matrix, the kalman gain)
+  pub fn step(&mut self) -> (Pose, Pose, Vec<f64>, Vec<f64>) {
+    let (t, delta) = self.clock.tick();
+
+    let previous = self.estimator.state();
+    let ideal = self.agent.get_ideal(&previous, t);
+    let input = self.planner.get_input(self.agent.as_ref(), &previous, &ideal, &self.input, t, delta);
+    self.input = input;
+    self.agent.noisy_move(&input, delta, &mut self.rng);
+    let actual = self.agent.get_actual();
+    self.collision = self.obstacles.clearance(&actual.fixed_rows::<2>(0).into_owned()) < self.agent.get_robot_config().radius;
+    self.estimator.predict(&input, delta);
+
+    let observed = self.agent.noisy_observe(&mut self.rng);
+    let labeled = self.association.method == AssociationMethod::Known;
+    let measurements = observed.iter().map(|o| o.to_measurement(labeled)).collect::<Vec<_>>();
+    let ids = data_association::associate(&measurements, &self.map, &self.estimator.state(), &self.estimator.covariance(), &self.noise, &self.association);
+    let actual_ids = observed.iter().map(|o| o.id).collect::<Vec<_>>();
+    self.association_stats = AssociationStats::new(&ids, &actual_ids);
+
+    let associated = measurements.iter()
+                                 .zip(ids.iter())
+                                 .filter_map(|(measurement, id)| id.map(|id| Measurement { id: Some(id), ..measurement.clone() }))
+                                 .collect::<Vec<_>>();
+    let results = self.estimator.update(&associated);
+    self.rejected = results.iter().filter(|result| matches!(result, Err(UpdateError::Outlier(_)))).count();
+    self.failures = results.iter().filter(|result| matches!(result, Err(e) if !matches!(e, UpdateError::Outlier(_)))).count();
+
+    let (xhat, p) = (self.estimator.state(), self.estimator.covariance());
+    self.divergence = filters::detect_divergence(&xhat, &p, self.max_covariance_trace);
+    if let Some(divergence) = self.divergence {
+      eprintln!("the filter diverged at {} sec: {}", t, divergence);
+      let fallback = if xhat.iter().all(|v| v.is_finite()) { xhat } else { previous };
+      let pose = match self.recovery {
+        RecoveryMethod::ResetCovariance => fallback,
+        RecoveryMethod::Reinitialize => self.recovery_map()
+                                            .and_then(|map| filters::pose_from_measurements(&map, &measurements, &ids, fallback[2]))
+                                            .unwrap_or(fallback),
+      };
+      self.estimator.reset(&pose, &(self.reset_variance * na::Matrix3::identity()));
+    }
+
+    let xhat = self.estimator.state();
+    let mut nis = self.estimator.nis();
+    nis.extend(results.iter().filter_map(|result| match result {
+      Err(UpdateError::Outlier(nis)) => Some(*nis),
+      _ => None,
+    }));
+    self.metrics = StepMetrics::new(self.agent.get_actual(), &xhat, &self.estimator.covariance(), nis);
+    self.optimization = None;
+    if let Some(graph) = self.graph.as_mut() {
+      graph.add_pose(&input, delta, &xhat);
+      for (measurement, result) in associated.iter().zip(results.iter()) {
+        if let (Some(id), Ok(())) = (measurement.id, result) {
+          graph.add_observation(id, &na::Vector2::new(measurement.distance, measurement.angle));
+        }
+      }
+      if self.optimize_every.is_some_and(|every| graph.ticks() as u64 % every == 0) {
+        self.optimization = Some(graph.optimize());
+      }
+    }
+
+    (
+      Pose::from_vector3(&ideal),
+      Pose::from_vector3(&xhat),
+      self.estimator.covariance().transpose().as_slice().to_vec(),
+      self.estimator.gain().transpose().as_slice().to_vec(),
+    )
+  }
+
+  /// Get the agent instance of the robot to be estimated
+  pub fn get_agent(&self) -> &dyn Agent {
+    self.agent.as_ref()
+  }
+
+  /// Get the statistics of the data association at the latest tick
+  pub fn get_association_stats(&self) -> &AssociationStats {
+    &self.association_stats
+  }
+
+  /// Get the number of measurements rejected as outliers at the latest tick
+  pub fn get_rejected(&self) -> usize {
+    self.rejected
+  }
+
+  /// Get the number of measurements which could not be applied because of the numerical failure at the latest tick
+  pub fn get_failures(&self) -> usize {
+    self.failures
+  }
+
+  /// Get the divergence detected at the latest tick
+  pub fn get_divergence(&self) -> Option<Divergence> {
+    self.divergence
+  }
+
+  /// Get whether the actual robot collides with the obstacles at the latest tick
+  pub fn get_collision(&self) -> bool {
+    self.collision
+  }
+
+  /// Get the obstacles which the planner avoids
+  pub fn get_obstacles(&self) -> &ObstacleMap {
+    &self.obstacles
+  }
+
+  /// Get the metrics of the estimator against the actual pose at the latest tick
+  ///
+  /// The normalized innovation squared includes the measurements rejected as outliers
+  pub fn get_metrics(&self) -> &StepMetrics {
+    &self.metrics
+  }
+
+  /// Get the particles which represent the distribution of the estimated pose
+  ///
+  /// ## Returns
+  /// The weighted particles, or an empty vector when the estimator does not use particles
+  pub fn get_particles(&self) -> Vec<Particle> {
+    self.estimator.particles()
+  }
+
+  /// Get the landmark map estimated by the estimator
+  ///
+  /// ## Returns
+  /// The estimated landmarks, or an empty vector when the estimator uses the given landmark map
+  pub fn get_landmarks(&self) -> Vec<EstimatedLandmark> {
+    self.estimator.landmarks()
+  }
+
+  /// Get the trajectory of the robot estimated by the estimator
+  ///
+  /// ## Returns
+  /// The estimated poses from the start of the simulation, or an empty vector when the estimator does not estimate the trajectory
+  pub fn get_trajectory(&self) -> Vec<Pose> {
+    self.estimator.trajectory()
+  }
+
+  /// Optimize the pose graph recorded so far
+  ///
+  /// ## Returns
+  /// The result of the optimization, or `None` when the graph SLAM back-end is disabled
+  pub fn optimize_graph(&mut self) -> Option<Optimization> {
+    self.graph.as_mut().map(|graph| graph.optimize())
+  }
+
+  /// Get the pose graph which is recorded when the graph SLAM back-end is enabled
+  pub fn get_graph(&self) -> Option<&PoseGraph> {
+    self.graph.as_ref()
+  }
+
+  /// Get the result of the optimization of the pose graph at the latest tick
+  ///
+  /// ## Returns
+  /// The result of the optimization, or `None` when the pose graph was not optimized at the latest tick
+  pub fn get_optimization(&self) -> Option<&Optimization> {
+    self.optimization.as_ref()
+  }
+
+  /// Get the elapsed time of this simulation
+  ///
+  /// ## Returns
+  /// Elapsed time (sec) from the start of this simulation to the latest tick
+  pub fn get_elapsed(&self) -> f64 {
+    self.clock.elapsed()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::{Arc, Mutex};
+
+  use super::*;
+  use crate::data::Point;
+
+  /// A planner which records the current inputs given to it and returns a different input at every tick
+  #[derive(Debug)]
+  struct RecordingPlanner {
+    received: Arc<Mutex<Vec<na::Vector2<f64>>>>,
+  }
+
+  impl Planner for RecordingPlanner {
+    fn get_input(&mut self, _: &dyn Agent, _: &na::Vector3<f64>, _: &na::Vector3<f64>,
+                 current_input: &na::Vector2<f64>, _: f64, _: f64) -> na::Vector2<f64> {
+      let mut received = self.received.lock().unwrap();
+      received.push(*current_input);
+      na::Vector2::new(0.1 * received.len() as f64, -0.01 * received.len() as f64)
+    }
+  }
+
+  #[test]
+  fn planner_receives_input_of_previous_tick() {
+    let map = LandmarkMap::from_points(&[Point { x: 1.0, y: 0.0 }, Point { x: 0.0, y: 1.0 }]).unwrap();
+    let mut simulation = create_simulation("circular", &map, &Config::default(), 7).unwrap();
+    let received = Arc::new(Mutex::new(Vec::new()));
+    simulation.planner = Box::new(RecordingPlanner { received: received.clone() });
+
+    for _ in 0..3 {
+      simulation.step();
+    }
+    assert_eq!(*received.lock().unwrap(), vec![
+      na::Vector2::new(0.0, 0.0),
+      na::Vector2::new(0.1, -0.01),
+      na::Vector2::new(0.2, -0.02),
+    ]);
+  }
+
+  #[test]
+  fn slam_reinitializes_from_its_own_landmarks_instead_of_the_map() {
+    let map = LandmarkMap::from_points(&[Point { x: 1.0, y: 0.0 }, Point { x: 0.0, y: 1.0 }]).unwrap();
+    let mut config = Config::default();
+    let localization = create_simulation("circular", &map, &config, 7).unwrap();
+    assert_eq!(localization.recovery_map().unwrap().landmarks().len(), 2);
+
+    config.filter.kind = crate::config::FilterKind::EkfSlam;
+    let mut slam = create_simulation("circular", &map, &config, 7).unwrap();
+    assert!(slam.recovery_map().is_none());
+    for _ in 0..5 {
+      slam.step();
+    }
+    let estimated = slam.estimator.landmarks();
+    assert!(!estimated.is_empty());
+    let recovery = slam.recovery_map().unwrap();
+    assert_eq!(recovery.landmarks().len(), estimated.len());
+    for landmark in estimated.iter() {
+      let point = recovery.get(landmark.id).unwrap().point();
+      assert_eq!((point.x, point.y), (landmark.x, landmark.y));
+    }
+  }
+}
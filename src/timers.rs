@@ -1,45 +1,79 @@
-//! The `timers` module provides the `start` function which executes [EKF] periodically and sends it's results to a drawing engine by ZeroMQ
+//! The `timers` module provides the `start` function which executes [Simulation] periodically and sends it's results to a drawing engine by ZeroMQ,
+//! and the `run_batch` function which executes [Simulation] for the given number of steps as fast as possible without the drawing engine
 
 use std::time::Duration;
 
+extern crate nalgebra as na;
 use tokio;
 use zmq;
 use serde::{Deserialize, Serialize};
 use serde_json;
 
-use crate::filters::kalman_filter::EKF;
-use crate::data::{Pose, Observed};
+use crate::config::TimerConfig;
+use crate::filters::Divergence;
+use crate::simulation::Simulation;
+use crate::data::{Pose, Observed, Particle, EstimatedLandmark};
+use crate::filters::data_association::AssociationStats;
+use crate::map::LandmarkMap;
+use crate::metrics::{Metrics, StepMetrics, MetricsSummary};
+use crate::pose_graph::{PoseGraph, Optimization};
+use crate::utils;
 
-/// **\[private\]** The interval (milliseconds) to call [EKF]
-const INTERVAL_MS: u64 = 200;
-/// **\[private\]** The zeromq port number for the drawing engine
-const PORT: u64 = 5556;
+/// The default value of the interval (milliseconds) to call [Simulation], which is also used as the time step of [crate::clocks::SimulatedClock]
+pub const INTERVAL_MS: u64 = 200;
+/// The default value of the zeromq port number for the drawing engine
+pub const PORT: u64 = 5556;
 
 /// Start an async timer event which executes the following processing
-/// 1. calls [crate::filters::kalman_filter::EKF::step] method and gets the estimated pose and other results of simulated robot
+/// 1. calls [Simulation::step] method and gets the estimated pose and other results of simulated robot
 /// 1. gets the distances and angles of observed markers
 /// 1. gets the hidden actual pose of simulated robot
+/// 1. gets the metrics of the estimator at this tick and aggregates them from the start
 /// 1. sends above data to the drawing engine by using ZeroMQ
 ///
 /// ## Arguments
-/// * `ekf` - EKF object
+/// * `simulation` - the simulation which owns the simulated robot and the estimator such as EKF, UKF and PF
+/// * `config` - the interval and the zeromq port of this timer
 ///
 /// ## Errors
 /// Raises an error when ZeroMQ
-pub fn start(mut ekf: EKF) -> Result<(), Box<dyn std::error::Error>> {
+pub fn start(mut simulation: Simulation, config: &TimerConfig) -> Result<(), Box<dyn std::error::Error>> {
   let rt = tokio::runtime::Runtime::new()?;
-  let zeromq = ZeroMQ::new(PORT)?;
+  let zeromq = ZeroMQ::new(config.port)?;
+  let interval_ms = config.interval_ms;
+  let mut metrics = Metrics::new();
 
   rt.block_on(async {
     let forever = tokio::task::spawn(async move {
-      let mut interval = tokio::time::interval(Duration::from_millis(INTERVAL_MS));
+      let mut interval = tokio::time::interval(Duration::from_millis(interval_ms));
 
       loop {
         interval.tick().await;
-        let (ideal, xhat, p, k) = ekf.step();
-        let actual = Pose::from_vector3(ekf.agent.get_actual());
-        let observed = ekf.agent.get_observed();
-        if let Err(e) = zeromq.send(ideal, actual, xhat, observed, p, k) {
+        let (ideal, xhat, p, k) = simulation.step();
+        let actual = Pose::from_vector3(simulation.get_agent().get_actual());
+        metrics.add(simulation.get_metrics());
+        let payload = Payload {
+          ideal,
+          actual,
+          xhat,
+          observed: simulation.get_agent().get_observed().to_vec(),
+          covariance: p,
+          kalmanGain: k,
+          association: simulation.get_association_stats().clone(),
+          rejected: simulation.get_rejected(),
+          failures: simulation.get_failures(),
+          divergence: simulation.get_divergence(),
+          collision: simulation.get_collision(),
+          particles: simulation.get_particles(),
+          landmarks: simulation.get_landmarks(),
+          optimized: match (simulation.get_optimization(), simulation.get_graph()) {
+            (Some(_), Some(graph)) => graph.trajectory(),
+            _ => Vec::new(),
+          },
+          metrics: simulation.get_metrics().clone(),
+          aggregate: metrics.summary(),
+        };
+        if let Err(e) = zeromq.send(&payload) {
           eprintln!("send message error: {:?}", e);
         }
       }
@@ -49,6 +83,191 @@ pub fn start(mut ekf: EKF) -> Result<(), Box<dyn std::error::Error>> {
   Ok(())
 }
 
+/// Execute the given number of steps in a tight loop without waiting for the timer and without sending any data to the drawing engine
+///
+/// The simulation must be driven by [crate::clocks::SimulatedClock], so that the simulation time advances independently of the wall-clock time
+/// ([crate::run] rejects the wall clock in the headless batch mode and the Monte Carlo experiment)
+///
+/// ## Arguments
+/// * `simulation` - the simulation which owns the simulated robot and the estimator such as EKF, UKF and PF
+/// * `steps` - the number of steps to be executed
+///
+/// ## Returns
+/// The summary of this simulation
+pub fn run_batch(mut simulation: Simulation, steps: u64) -> Summary {
+  let mut metrics = Metrics::new();
+  let mut association = AssociationStats::default();
+  let mut rejected = 0;
+  let mut failures = 0;
+  let mut divergences = 0;
+  let mut collisions = 0;
+  let mut squared_tracking_error = 0.0;
+  let mut max_tracking_error: f64 = 0.0;
+  let mut last: Option<(Pose, Pose, Pose)> = None;
+  let mut actual_trajectory = vec![*simulation.get_agent().get_actual()];
+
+  for _ in 0..steps {
+    let (ideal, xhat, _, _) = simulation.step();
+    let actual = Pose::from_vector3(simulation.get_agent().get_actual());
+    actual_trajectory.push(*simulation.get_agent().get_actual());
+
+    metrics.add(simulation.get_metrics());
+    association.accumulate(simulation.get_association_stats());
+    rejected += simulation.get_rejected();
+    failures += simulation.get_failures();
+    divergences += simulation.get_divergence().map_or(0, |_| 1);
+    collisions += if simulation.get_collision() { 1 } else { 0 };
+    let tracking_error = (actual.x - ideal.x).hypot(actual.y - ideal.y);
+    squared_tracking_error += tracking_error.powi(2);
+    max_tracking_error = max_tracking_error.max(tracking_error);
+    last = Some((ideal, actual, xhat));
+  }
+
+  let landmarks = simulation.get_landmarks();
+  let landmark_rmse = landmark_rmse(&landmarks, simulation.get_agent().get_map());
+  let graph = simulation.optimize_graph().and_then(|optimization| {
+    simulation.get_graph().map(|graph| GraphSummary::new(optimization, graph, &actual_trajectory, simulation.get_agent().get_map()))
+  });
+  let (ideal, actual, xhat) = match last {
+    Some((ideal, actual, xhat)) => (Some(ideal), Some(actual), Some(xhat)),
+    None => (None, None, None),
+  };
+  Summary {
+    steps,
+    elapsed: simulation.get_elapsed(),
+    metrics: metrics.summary(),
+    association,
+    rejected,
+    failures,
+    divergences,
+    collisions,
+    tracking_rmse: (squared_tracking_error / steps.max(1) as f64).sqrt(),
+    max_tracking_error,
+    ideal,
+    actual,
+    xhat,
+    landmarks,
+    landmark_rmse,
+    trajectory: simulation.get_trajectory(),
+    graph,
+  }
+}
+
+/// **\[private\]** Calculate the root mean squared error of the estimated landmarks against the actual landmark map
+///
+/// ## Returns
+/// The root mean squared error of the positions, or `None` when no landmark is estimated
+fn landmark_rmse(landmarks: &[EstimatedLandmark], map: &LandmarkMap) -> Option<f64> {
+  let squared_errors = landmarks.iter()
+                                .filter_map(|estimated| map.get(estimated.id).map(|actual| (actual.x - estimated.x).powi(2) + (actual.y - estimated.y).powi(2)))
+                                .collect::<Vec<_>>();
+  if squared_errors.is_empty() {
+    return None;
+  }
+  Some((squared_errors.iter().sum::<f64>() / squared_errors.len() as f64).sqrt())
+}
+
+/// A struct which stores the summary of a simulation executed by [run_batch]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct Summary {
+  /// the number of executed steps
+  pub steps: u64,
+  /// the simulation time (sec) at the last step
+  pub elapsed: f64,
+  /// the errors and the consistency (NEES, NIS) of the estimator aggregated over all steps
+  #[serde(flatten)]
+  pub metrics: MetricsSummary,
+  /// the total statistics of the data association over all steps
+  pub association: AssociationStats,
+  /// the total number of measurements rejected as outliers by the filter
+  pub rejected: usize,
+  /// the total number of measurements which could not be applied because of the numerical failure
+  pub failures: usize,
+  /// the number of steps at which the filter diverged and was recovered
+  pub divergences: usize,
+  /// the number of steps at which the actual robot collided with the obstacles
+  pub collisions: usize,
+  /// the root mean squared distance between the actual position and the ideal position, which evaluates the planner
+  pub tracking_rmse: f64,
+  /// the maximum distance between the actual position and the ideal position
+  pub max_tracking_error: f64,
+  /// the ideal pose of the simulated robot at the last step
+  pub ideal: Option<Pose>,
+  /// the hidden actual pose of the simulated robot at the last step
+  pub actual: Option<Pose>,
+  /// the estimated pose of the simulated robot at the last step
+  pub xhat: Option<Pose>,
+  /// the landmark map estimated by SLAM at the last step
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub landmarks: Vec<EstimatedLandmark>,
+  /// the root mean squared error of the landmarks estimated by SLAM against the actual landmark map
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub landmark_rmse: Option<f64>,
+  /// the trajectory of the simulated robot estimated by SLAM
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub trajectory: Vec<Pose>,
+  /// the summary of the pose graph optimized at the end of the simulation
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub graph: Option<GraphSummary>,
+}
+
+/// A struct which stores the summary of the pose graph optimized by the graph SLAM back-end at the end of [run_batch]
+#[derive(Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct GraphSummary {
+  /// the result of the final optimization
+  pub optimization: Optimization,
+  /// the root mean squared error of the optimized positions(x, y) against the actual positions
+  pub position_rmse: f64,
+  /// the root mean squared error of the optimized directions(theta) against the actual directions
+  pub theta_rmse: f64,
+  /// the root mean squared error of the optimized landmarks against the actual landmark map
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub landmark_rmse: Option<f64>,
+  /// the optimized landmarks
+  pub landmarks: Vec<EstimatedLandmark>,
+  /// the optimized trajectory from the initial pose
+  pub trajectory: Vec<Pose>,
+  /// the hidden actual trajectory from the initial pose
+  pub actual: Vec<Pose>,
+}
+
+impl GraphSummary {
+  /// Create the summary by comparing the optimized pose graph with the actual trajectory and landmark map
+  ///
+  /// ## Arguments
+  /// * `optimization` - the result of the final optimization
+  /// * `graph` - the optimized pose graph
+  /// * `actual` - the actual poses from the start, whose latest poses are at the same ticks as the poses of the graph
+  /// * `map` - the actual landmark map
+  ///
+  /// ## Returns
+  /// The summary of the pose graph
+  fn new(optimization: Optimization, graph: &PoseGraph, actual: &[na::Vector3<f64>], map: &LandmarkMap) -> GraphSummary {
+    let trajectory = graph.trajectory();
+    let actual = &actual[actual.len().saturating_sub(trajectory.len())..];
+    let n = trajectory.len().min(actual.len()).max(1) as f64;
+    let (squared_position_error, squared_theta_error) = trajectory.iter()
+                                                                  .zip(actual.iter())
+                                                                  .fold((0.0, 0.0), |(position, theta), (optimized, actual)| {
+                                                                    let d = actual[2] - optimized.theta;
+                                                                    (position + (actual[0] - optimized.x).powi(2) + (actual[1] - optimized.y).powi(2),
+                                                                     theta + utils::normalize_angle(d).powi(2))
+                                                                  });
+    let landmarks = graph.landmarks();
+    GraphSummary {
+      optimization,
+      position_rmse: (squared_position_error / n).sqrt(),
+      theta_rmse: (squared_theta_error / n).sqrt(),
+      landmark_rmse: landmark_rmse(&landmarks, map),
+      landmarks,
+      trajectory,
+      actual: actual.iter().map(Pose::from_vector3).collect(),
+    }
+  }
+}
+
 /// **\[private\]** A struct which stores the ZeroMQ Socket
 struct ZeroMQ {
   /// ZeroMQ Socket
@@ -76,26 +295,12 @@ impl ZeroMQ {
   /// **\[private\]** Send the robot's data as JSON to the drawing engine by using ZeroMQ
   ///
   /// ## Arguments
-  /// * `ideal` - the ideal pose of the simulated robot
-  /// * `actual` - the hidden actual pose of the simulated robot
-  /// * `xhat` - the estimated pose of the simulated robot
-  /// * `observed` - the list of the observed marker's data
-  /// * `p` - the error covariance matrix
-  /// * `k` - the Kalman Gain
+  /// * `payload` - the robot's data at this tick
   ///
   /// ## Errors
   /// When the given data cannot be serialized as JSON, or when the serialized data cannot be sent to drawing engine by ZeroMQ, the error is raised
-  fn send(&self, ideal: Pose, actual: Pose, xhat: Pose, observed: &Vec<Observed>, p: Vec<f64>, k: Vec<f64>) -> Result<(), Box<dyn std::error::Error>> {
-    let payload = Payload {
-      ideal: ideal,
-      actual: actual,
-      xhat: xhat,
-      observed: observed.to_vec(),
-      covariance: p,
-      kalmanGain: k,
-    };
-    println!("payload = {:?}", payload);
-    let j = serde_json::to_string(&payload)?;
+  fn send(&self, payload: &Payload) -> Result<(), Box<dyn std::error::Error>> {
+    let j = serde_json::to_string(payload)?;
     self.publisher.send(&j, 0)?;
     Ok(())
   }
@@ -118,4 +323,27 @@ struct Payload {
   covariance: Vec<f64>,
   /// the Kalman Gain
   kalmanGain: Vec<f64>,
+  /// the statistics of the data association
+  association: AssociationStats,
+  /// the number of measurements rejected as outliers
+  rejected: usize,
+  /// the number of measurements which could not be applied because of the numerical failure
+  failures: usize,
+  /// the divergence of the filter detected at this tick
+  divergence: Option<Divergence>,
+  /// whether the actual robot collides with the obstacles at this tick
+  collision: bool,
+  /// the particle cloud of the particle filter
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  particles: Vec<Particle>,
+  /// the landmark map estimated by SLAM
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  landmarks: Vec<EstimatedLandmark>,
+  /// the trajectory optimized by the pose graph SLAM back-end (sent only at the tick when the graph is optimized)
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  optimized: Vec<Pose>,
+  /// the metrics of the estimator at this tick
+  metrics: StepMetrics,
+  /// the metrics of the estimator aggregated from the start
+  aggregate: MetricsSummary,
 }
@@ -6,6 +6,8 @@ use num::Float;
 
 /// Convert the given angle between -pi and pi (-pi <= angle && angle < pi)
 ///
+/// The angle is wrapped by any number of turns in both directions
+///
 /// ## Arguments
 /// * `r` - input angle
 ///
@@ -13,7 +15,12 @@ use num::Float;
 /// Converted angle
 pub fn normalize_angle<F: Float>(r: F) -> F {
   let pi = F::from(PI).unwrap();
-  (r + pi) % (F::from(2.0).unwrap() * pi) - pi
+  let two_pi = F::from(2.0).unwrap() * pi;
+  // the remainder has the same sign as the dividend, so that the negative remainder is shifted by a turn
+  let r = (r + pi) % two_pi;
+  let r = if r < F::zero() { r + two_pi } else { r };
+  // the negative remainder smaller than the rounding error of a turn is shifted to a turn exactly
+  if r < two_pi { r - pi } else { -pi }
 }
 
 /// Get a sequence of float number between `start` and `stop` which are divided by `step` intervals
@@ -26,6 +33,9 @@ pub fn normalize_angle<F: Float>(r: F) -> F {
 /// ## Returns
 /// Sequence of float number
 pub fn step_by_float<F: Float>(start: F, stop: F, step: F) -> Vec<F> {
+  if start == stop {
+    return vec![start];
+  }
   let num = (stop - start) / step + F::from(1).unwrap();
   let r = 0..num.to_usize().unwrap();
   r.map(|i| start + (stop - start) * F::from(i).unwrap() / (num - F::from(1).unwrap())).collect::<Vec<_>>()
@@ -39,8 +49,8 @@ pub fn step_by_float<F: Float>(start: F, stop: F, step: F) -> Vec<F> {
 /// ## Returns
 /// Normalized sequence of float number
 pub fn normalize_min_max<F: Float>(v: Vec<F>) -> Vec<F> {
-  let min = v.iter().fold(F::from(0.0 / 0.0).unwrap(), |m, n| n.min(m));
-  let max = v.iter().fold(F::from(0.0 / 0.0).unwrap(), |m, n| n.max(m));
+  let min = v.iter().fold(F::nan(), |m, n| n.min(m));
+  let max = v.iter().fold(F::nan(), |m, n| n.max(m));
   match max - min {
     d if d == F::from(0.0).unwrap() => {
       vec![F::from(1.0).unwrap(); v.len()]
@@ -50,3 +60,106 @@ pub fn normalize_min_max<F: Float>(v: Vec<F>) -> Vec<F> {
     }
   }
 }
+
+/// Get the quantile (inverse of the cumulative distribution function) of the standard normal distribution
+///
+/// This function uses the rational approximation by Peter J. Acklam whose relative error is less than 1.15e-9
+///
+/// ## Arguments
+/// * `p` - probability (0 < p < 1)
+///
+/// ## Returns
+/// The value `z` which satisfies P(Z <= z) = p
+pub fn normal_quantile(p: f64) -> f64 {
+  const A: [f64; 6] = [-3.969683028665376e+01,  2.209460984245205e+02, -2.759285104469687e+02,
+                        1.38357751867269e+02 , -3.066479806614716e+01,  2.506628277459239e+00];
+  const B: [f64; 5] = [-5.447609879822406e+01,  1.615858368580409e+02, -1.556989798598866e+02,
+                        6.680131188771972e+01, -1.328068155288572e+01];
+  const C: [f64; 6] = [-7.784894002430293e-03, -3.223964580411365e-01, -2.400758277161838e+00,
+                       -2.549732539343734e+00,  4.374664141464968e+00,  2.938163982698783e+00];
+  const D: [f64; 4] = [ 7.784695709041462e-03,  3.224671290700398e-01,  2.445134137142996e+00,
+                        3.754408661907416e+00];
+  const P_LOW: f64 = 0.02425;
+
+  let tail = |q: f64| (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5]) /
+                      ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0);
+  match p {
+    p if p <= 0.0 => f64::NEG_INFINITY,
+    p if p >= 1.0 => f64::INFINITY,
+    p if p < P_LOW => tail((-2.0 * p.ln()).sqrt()),
+    p if p > 1.0 - P_LOW => -tail((-2.0 * (1.0 - p).ln()).sqrt()),
+    p => {
+      let q = p - 0.5;
+      let r = q * q;
+      (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q /
+      (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    },
+  }
+}
+
+/// Get the quantile of the chi-square distribution, which is used as the threshold of the Mahalanobis distance
+///
+/// The quantile is exact for 2 degrees of freedom, and is approximated by the Wilson-Hilferty transformation for the others
+///
+/// ## Arguments
+/// * `p` - probability (0 < p < 1)
+/// * `dof` - degrees of freedom
+///
+/// ## Returns
+/// The value `x` which satisfies P(X <= x) = p
+pub fn chi2_quantile(p: f64, dof: usize) -> f64 {
+  if dof == 2 {
+    return -2.0 * (1.0 - p).ln();
+  }
+  let k = dof as f64;
+  let a = 2.0 / (9.0 * k);
+  k * (1.0 - a + normal_quantile(p) * a.sqrt()).powi(3).max(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn normalize_angle_wraps_in_both_directions() {
+    let cases = [
+      (0.0, 0.0),
+      (0.5, 0.5),
+      (-0.5, -0.5),
+      (PI, -PI),
+      (-PI, -PI),
+      (1.5 * PI, -0.5 * PI),
+      (-1.5 * PI, 0.5 * PI),
+      (-2.5 * PI, -0.5 * PI),
+      (7.0 * PI + 0.25, -PI + 0.25),
+      (-7.0 * PI - 0.25, PI - 0.25),
+    ];
+    for (angle, expected) in cases.iter() {
+      let normalized = normalize_angle(*angle);
+      assert!((normalized - expected).abs() < 1e-9, "normalize_angle({}) = {}, expected {}", angle, normalized, expected);
+      assert!((-PI..PI).contains(&normalized));
+    }
+  }
+
+  #[test]
+  fn normal_quantile_matches_the_table() {
+    let cases = [(0.5, 0.0), (0.8413447460685429, 1.0), (0.95, 1.6448536269514722), (0.975, 1.959963984540054), (0.01, -2.3263478740408408),
+                 (0.001, -3.090232306167813)];
+    for (p, expected) in cases.iter() {
+      assert!((normal_quantile(*p) - expected).abs() < 1e-8, "normal_quantile({}) = {}, expected {}", p, normal_quantile(*p), expected);
+    }
+  }
+
+  #[test]
+  fn chi2_quantile_matches_the_table() {
+    // exact for 2 degrees of freedom
+    for (p, expected) in [(0.95, 5.991), (0.99, 9.210), (0.999, 13.816)].iter() {
+      assert!((chi2_quantile(*p, 2) - expected).abs() < 1e-3, "chi2_quantile({}, 2) = {}, expected {}", p, chi2_quantile(*p, 2), expected);
+    }
+    // the Wilson-Hilferty transformation is accurate within 1% for 3 or more degrees of freedom
+    for (p, dof, expected) in [(0.95, 3, 7.815), (0.99, 4, 13.277), (0.999, 6, 22.458), (0.95, 10, 18.307)].iter() {
+      let quantile = chi2_quantile(*p, *dof);
+      assert!((quantile - expected).abs() / expected < 0.01, "chi2_quantile({}, {}) = {}, expected {}", p, dof, quantile, expected);
+    }
+  }
+}